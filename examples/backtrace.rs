@@ -0,0 +1,97 @@
+//! Backtraces on panics and faults, see `src/backtrace.rs`
+//!
+//! `main` calls through `a`, `b` and `c`, and `c` indexes out of bounds. The
+//! panic handler writes the message and the raw return addresses to ITM port
+//! 0, which `backtrace` (in `host/`) symbolises against this ELF:
+//!
+//! ``` console
+//! > cd host
+//! > cargo run --bin itm -- -F -p 0=- /tmp/itm.fifo | \
+//!   cargo run --bin backtrace -- -e ../target/thumbv7em-none-eabihf/release/examples/backtrace
+//! panicked at 'index out of bounds: the len is 4 but the index is 8', examples/backtrace.rs:65:5
+//! backtrace:
+//!    0: 0x08000a1a in rust_begin_unwind at examples/backtrace.rs:93
+//!    1: 0x080004f6 in core::panicking::panic_bounds_check at ...
+//!    2: 0x08000528 in backtrace::c at examples/backtrace.rs:65
+//!    3: 0x08000540 in backtrace::b at examples/backtrace.rs:60
+//!    4: 0x08000560 in backtrace::a at examples/backtrace.rs:55
+//!    5: 0x0800058a in backtrace::__cortex_m_rt_main at examples/backtrace.rs:46
+//! ```
+//!
+//! This works in release builds too, as long as the frame pointers are kept
+//! (`-C force-frame-pointers=yes` in `.cargo/config`) and the ELF has debug
+//! info (`debug = true` in the `Cargo.toml` profiles). A `HardFault` is
+//! reported the same way, its backtrace going through the faulting `pc`.
+//!
+//! (Run without breakpoints at `HardFault` and `rust_begin_unwind`, see
+//! `openocd.gdb`.)
+//!
+//! ---
+
+// #![deny(unsafe_code)] // this example is using unsafe
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use core::{panic::PanicInfo, ptr};
+
+use app::{backtrace::Backtrace, fault};
+use cortex_m::{interrupt, iprintln, peripheral::ITM};
+use cortex_m_rt::{entry, exception, ExceptionFrame};
+
+static TABLE: [u32; 4] = [1, 2, 3, 5];
+
+#[entry]
+fn main() -> ! {
+    a(1);
+
+    loop {
+        continue;
+    }
+}
+
+#[inline(never)]
+fn a(n: usize) -> u32 {
+    b(n + 1) + 1
+}
+
+#[inline(never)]
+fn b(n: usize) -> u32 {
+    c(n * 4) + 1
+}
+
+#[inline(never)]
+fn c(n: usize) -> u32 {
+    TABLE[black_box(n)]
+}
+
+// keeps the compiler from knowing `n`, and the panic at compile time
+fn black_box(n: usize) -> usize {
+    unsafe { ptr::read_volatile(&n) }
+}
+
+// NOTE(unsafe) the handlers take over the ITM, nothing else runs
+fn stim() -> &'static mut cortex_m::peripheral::itm::Stim {
+    unsafe { &mut (*ITM::ptr()).stim[0] }
+}
+
+#[exception]
+fn HardFault(ef: &ExceptionFrame) -> ! {
+    interrupt::disable();
+    let regs = unsafe { fault::Registers::read() };
+    iprintln!(stim(), "{}{:?}", regs, ef);
+    iprintln!(stim(), "{}", Backtrace::capture());
+    loop {
+        continue;
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    interrupt::disable();
+    iprintln!(stim(), "{}", info);
+    iprintln!(stim(), "{}", Backtrace::capture());
+    loop {
+        continue;
+    }
+}
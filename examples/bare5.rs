@@ -36,6 +36,12 @@ mod stm32f40x {
     }
 
     impl<T> VolatileCell<T> {
+        pub const fn new(value: T) -> Self {
+            VolatileCell {
+                value: cell::UnsafeCell::new(value),
+            }
+        }
+
         #[inline(always)]
         pub fn read(&self) -> T
         where
@@ -154,8 +160,7 @@ fn wait(i: u32) {
 
 // simple test of Your `modify`
 fn test() {
-    let t: VolatileCell<u32> = unsafe { core::mem::uninitialized() };
-    t.write(0);
+    let t: VolatileCell<u32> = VolatileCell::new(0);
     assert!(t.read() == 0);
     t.modify(3, 3, 0b10101);
     //
@@ -0,0 +1,68 @@
+//! Micro-benchmarks, copying a slice three ways (as in `bare3.rs`)
+//!
+//! The results go to ITM port 0, save them for the `bench` host tool (in
+//! `host/`) and compare a dev build to a release build:
+//!
+//! ``` console
+//! > cargo run --example bench                  # itm ... > dev.txt
+//! > cargo run --example bench --release        # itm ... > release.txt
+//! > cd host
+//! > cargo run --bin bench -- dev.txt release.txt
+//! ```
+//!
+//! For semihosting instead, pass `&mut hio::hstdout().unwrap()` to `run`.
+//!
+//! ---
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use app::bench::{Bench, Itm, Runner};
+use cortex_m::{iprintln, peripheral::DWT, Peripherals};
+use cortex_m_rt::entry;
+
+const N: usize = 256;
+
+#[entry]
+fn main() -> ! {
+    let mut p = Peripherals::take().unwrap();
+    p.DCB.enable_trace();
+    p.DWT.enable_cycle_counter();
+    let stim = &mut p.ITM.stim[0];
+
+    let src = [0xA5u8; N];
+    let mut index = [0u8; N];
+    let mut copy = [0u8; N];
+    let mut zip = [0u8; N];
+
+    let runner: Runner<16> = Runner::new(DWT::get_cycle_count).warmup(2);
+    runner
+        .run(
+            &mut [
+                Bench::new("index", &mut || {
+                    for i in 0..N {
+                        index[i] = src[i];
+                    }
+                }),
+                Bench::new("copy_from_slice", &mut || copy.copy_from_slice(&src)),
+                Bench::new("zip", &mut || {
+                    for (d, s) in zip.iter_mut().zip(src.iter()) {
+                        *d = *s;
+                    }
+                }),
+            ],
+            &mut Itm(&mut *stim),
+        )
+        .unwrap();
+
+    // the copies are used, so they are not optimized out
+    let ok = index == src && copy == src && zip == src;
+    iprintln!(stim, "done, copies {}", if ok { "ok" } else { "differ" });
+
+    loop {
+        continue;
+    }
+}
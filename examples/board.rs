@@ -0,0 +1,43 @@
+//! Board support
+//!
+//! Connect using e.g., `moserial` to `/dev/ttyACM0` (ACM number may change)
+//! 115200 8N1
+//!
+//! LD2 is lit while the user button (B1) is held, each press is reported
+//! over the virtual COM port and traced over ITM.
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use app::board::{self, Board};
+use core::fmt::Write;
+use cortex_m::iprintln;
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let mut board = Board::take().unwrap();
+    let stim = &mut board.itm.stim[0];
+    let (mut tx, _rx) = board.vcp.split();
+
+    iprintln!(stim, "{} @ {} Hz", board::NAME, board.clocks.sysclk().0);
+
+    let mut was_pressed = false;
+    loop {
+        let pressed = board.button.is_pressed();
+        match (was_pressed, pressed) {
+            (false, true) => {
+                board.led.on();
+                let _ = writeln!(tx, "pressed\r");
+                iprintln!(stim, "pressed");
+            }
+            (true, false) => board.led.off(),
+            _ => {}
+        }
+        was_pressed = pressed;
+    }
+}
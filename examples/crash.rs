@@ -10,6 +10,7 @@ use panic_semihosting as _;
 
 use core::ptr;
 
+use app::{coredump, fault};
 use cortex_m_rt::{entry, exception};
 
 #[entry]
@@ -28,5 +29,11 @@ fn main() -> ! {
 #[exception]
 #[inline(never)]
 fn HardFault(ef: &cortex_m_rt::ExceptionFrame) -> ! {
-    panic!("Exception frame {:?}", ef);
+    // RAM and registers over ITM port 4, for `coredump` in `host` (see
+    // `src/coredump.rs`), or over the serial port by `coredump::Usart2`
+    coredump::write(ef, &[coredump::RAM], &mut coredump::Itm);
+
+    // why, e.g., "precise data bus error at 0x2fffffff" (see `src/fault.rs`)
+    let regs = unsafe { fault::Registers::read() };
+    panic!("{}Exception frame {:?}", regs, ef);
 }
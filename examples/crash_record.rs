@@ -0,0 +1,90 @@
+//! Crash records kept over a reset, see `src/crash.rs`
+//!
+//! Press the user button (B1) to crash, alternately by a HardFault (reading
+//! outside of RAM, as in `crash.rs`) and by a panic. The handlers record the
+//! crash, press reset (B2) and the reason of the start (see `src/boot.rs`)
+//! and the record are reported over the virtual COM port (115200 8N1) and
+//! ITM port 0:
+//!
+//! ``` text
+//! boot: fault, then reset pin
+//! crash: HardFault, escalated from a configurable fault
+//!   precise data bus error at 0x2fffffff
+//! CFSR=0x00008200 HFSR=0x40000000 MMFAR=0x2fffffff BFAR=0x2fffffff AFSR=0x00000000
+//! r0=0x2fffffff r1=0x00000000 r2=0x00000000 r3=0x00000000
+//! ...
+//! ```
+//!
+//! (Run without breakpoints at `HardFault` and `rust_begin_unwind`, see
+//! `openocd.gdb`.)
+//!
+//! ---
+
+// #![deny(unsafe_code)] // this example is using unsafe
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::{
+    board::Board,
+    boot::{self, BootReason},
+    crash::{self, Kind},
+    stm32f40x::RCC,
+};
+use core::{fmt::Write, panic::PanicInfo, ptr};
+use cortex_m::{iprint, iprintln};
+use cortex_m_rt::{entry, exception, ExceptionFrame};
+
+#[entry]
+fn main() -> ! {
+    let mut board = Board::take().unwrap();
+    let (mut tx, _rx) = board.vcp.split();
+    let stim = &mut board.itm.stim[0];
+
+    let last = crash::take();
+    let reason = BootReason::new(
+        boot::take_flags(unsafe { &*RCC::get() }),
+        last.as_ref().map(|record| record.kind().into()),
+    );
+    let _ = write!(tx, "boot: {}\r\n", reason);
+    iprintln!(stim, "boot: {}", reason);
+    match &last {
+        Some(record) => {
+            let _ = write!(tx, "{}\r\n", record);
+            iprint!(stim, "{}", record);
+        }
+        None => {
+            let _ = write!(tx, "no crash recorded\r\n");
+            iprintln!(stim, "no crash recorded");
+        }
+    }
+
+    while !board.button.is_pressed() {}
+    match last.map(|record| record.kind()) {
+        Some(Kind::Fault) => panic!("crashing on request"),
+        _ => unsafe {
+            // read an address outside of the RAM region to cause a HardFault exception
+            ptr::read_volatile(0x2FFF_FFFF as *const u32);
+        },
+    }
+
+    loop {
+        continue;
+    }
+}
+
+#[exception]
+fn HardFault(ef: &ExceptionFrame) -> ! {
+    crash::record_fault(ef);
+    loop {
+        continue;
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    crash::record_panic(info);
+    loop {
+        continue;
+    }
+}
@@ -7,12 +7,16 @@
 #[allow(unused_extern_crates)]
 use panic_halt as _;
 
+use app::{clocks, time::U32Ext as _};
 use cortex_m::{iprint, peripheral::syst::SystClkSource};
 use cortex_m_rt::entry;
 use cortex_m_semihosting::hprintln;
 
 // `svd2rust` generated Peripheral Access Crate (PAC).
+#[cfg(feature = "f401")]
 use stm32f4::stm32f401::{interrupt, Interrupt, ITM, NVIC};
+#[cfg(feature = "f411")]
+use stm32f4::stm32f411::{interrupt, Interrupt, ITM, NVIC};
 
 #[entry]
 fn main() -> ! {
@@ -25,7 +29,7 @@ fn main() -> ! {
 
     // configure the system timer to wrap around every second
     syst.set_clock_source(SystClkSource::Core);
-    syst.set_reload(16_000_000); // 1s
+    syst.set_reload(1.s().systick_reload(&clocks::RESET).unwrap()); // 1s
     syst.enable_counter();
 
     loop {
@@ -13,6 +13,7 @@
 
 use panic_halt as _;
 
+use app::{clocks, time::U32Ext as _};
 use cortex_m::peripheral::syst::SystClkSource;
 use cortex_m::Peripherals;
 use cortex_m_rt::{entry, exception};
@@ -25,7 +26,7 @@ fn main() -> ! {
 
     // configures the system timer to trigger a SysTick exception every second
     syst.set_clock_source(SystClkSource::Core);
-    syst.set_reload(16_000_000); // period = 1s
+    syst.set_reload(1.s().systick_reload(&clocks::RESET).unwrap()); // period = 1s
     syst.enable_counter();
     syst.enable_interrupt();
 
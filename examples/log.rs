@@ -0,0 +1,48 @@
+//! Leveled logging over ITM
+//!
+//! Records go to ITM ports 0 (info), 1 (error, warn) and 2 (debug, trace),
+//! view them with the `itm` host tool (in `host/`):
+//!
+//! ``` console
+//! > cargo run --bin itm -- -F -l trace /tmp/itm.fifo
+//! ```
+//!
+//! Only `info!` and above are compiled in by default, try
+//! `--features log-debug` or `--features log-trace`.
+//!
+//! With `--features log-deferred` the format strings stay in the ELF (the
+//! records are a few bytes each), decode them by giving the ELF to `itm`:
+//!
+//! ``` console
+//! > cargo run --bin itm -- -F -e ../target/thumbv7em-none-eabihf/debug/examples/log /tmp/itm.fifo
+//! ```
+//!
+//! ---
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use app::log::prelude::*;
+use cortex_m::Peripherals;
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+    app::log::init(p.ITM);
+
+    info!("Hello, logging!");
+    for i in 0..3 {
+        debug!("iteration {}", i);
+        trace!("i * i = {}", i * i);
+    }
+    warn!("about to loop forever");
+    error!("not really an error");
+
+    loop {
+        continue;
+    }
+}
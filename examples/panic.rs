@@ -20,6 +20,12 @@ use panic_halt as _;
 // Logs panic messages using the ITM (Instrumentation Trace Macrocell)
 //use panic_itm as _;
 
+// Records the panic message and resets, comment out all of the above and
+// build with `--features panic-reboot` (see `src/reboot.rs`)
+
+// Prints the panic message over USART2, comment out all of the above and
+// build with `--features panic-serial` (see `src/panic_serial.rs`)
+
 use cortex_m_rt::entry;
 
 #[entry]
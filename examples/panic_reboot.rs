@@ -0,0 +1,48 @@
+//! Recording panics and rebooting, see `src/reboot.rs`
+//!
+//! ``` shell
+//! > cargo run --example panic_reboot --features panic-reboot
+//! ```
+//!
+//! At start the reason of the last reset is written to ITM port 0, then the
+//! program panics after a second. The panic handler of the `panic-reboot`
+//! feature records the panic and resets, and the next start reports it:
+//!
+//! ``` text
+//! boot: panic, then software reset, resets=1 crashes=1
+//! crash: panicked at 'run 1 failed', examples/panic_reboot.rs:46:5
+//! stack at 0x20007b58:
+//!   ...
+//! ```
+//!
+//! The third panic in a row halts instead (`reboot::LIMIT`). The record of
+//! that panic is left to a debugger, or reported after pressing reset (B2);
+//! a power cycle loses it and starts over.
+//!
+//! (Run without the breakpoint at `rust_begin_unwind`, see `openocd.gdb`.)
+//!
+//! ---
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app::reboot;
+use cortex_m::{asm, iprint};
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let mut p = cortex_m::Peripherals::take().unwrap();
+    let stim = &mut p.ITM.stim[0];
+
+    let boot = reboot::boot();
+    iprint!(stim, "{}", boot);
+
+    // a second at the 16 MHz HSI
+    asm::delay(16_000_000);
+
+    // a program that is up and running would call `reboot::healthy()` here
+    panic!("run {} failed", boot.crashes + 1);
+}
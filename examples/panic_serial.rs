@@ -0,0 +1,43 @@
+//! Panic messages over USART2, see `src/panic_serial.rs`
+//!
+//! ``` shell
+//! > cargo run --example panic_serial --features panic-serial
+//! ```
+//!
+//! Open a terminal on the virtual COM port (e.g., `screen /dev/ttyACM0
+//! 115200`), no debugger needed, and reset the board. After a second the
+//! program indexes out of bounds, and the handler of the `panic-serial`
+//! feature prints:
+//!
+//! ``` text
+//! panicked at 'index out of bounds: the len is 4 but the index is 4', examples/panic_serial.rs:40:16
+//! ```
+//!
+//! The program never sets up USART2 (nor the clocks), the handler does it
+//! all.
+//!
+//! (Run without the breakpoint at `rust_begin_unwind`, see `openocd.gdb`.)
+//!
+//! ---
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use app as _;
+use cortex_m::asm;
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    // a second at the 16 MHz HSI
+    asm::delay(16_000_000);
+
+    let table = [1u32, 2, 3, 4];
+    let mut sum = 0;
+    for i in 0..=table.len() {
+        sum += table[i];
+    }
+    panic!("sum {}", sum);
+}
@@ -3,6 +3,7 @@
 #![no_main]
 #![no_std]
 
+use app::{clocks, time::U32Ext as _};
 use cortex_m::peripheral::syst::SystClkSource;
 use cortex_m_semihosting::hprintln;
 use panic_halt as _;
@@ -21,7 +22,7 @@ const APP: () = {
 
         // configures the system timer to trigger a SysTick exception every second
         syst.set_clock_source(SystClkSource::Core);
-        syst.set_reload(16_000_000); // period = 1s
+        syst.set_reload(1.s().systick_reload(&clocks::RESET).unwrap()); // period = 1s
         syst.enable_counter();
         syst.enable_interrupt();
 
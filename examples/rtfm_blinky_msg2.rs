@@ -3,10 +3,11 @@
 #![no_main]
 #![no_std]
 
+use app::{clocks, time::U32Ext as _};
 use cortex_m::peripheral::DWT;
 use cortex_m_semihosting::hprintln;
 use panic_halt as _;
-use rtfm::cyccnt::{Instant, U32Ext as _};
+use rtfm::cyccnt::Instant;
 use stm32f4xx_hal::stm32;
 
 #[rtfm::app(device = stm32f4xx_hal::stm32, monotonic = rtfm::cyccnt::CYCCNT, peripherals = true)]
@@ -30,8 +31,8 @@ const APP: () = {
         // NOTE do *not* call `Instant::now` in this context; it will return a nonsense value
         let now = cx.start; // the start time of the system
 
-        // Schedule `toggle` to run 8e6 cycles (clock cycles) in the future
-        cx.schedule.toggle(now + 8_000_000.cycles(), true).unwrap();
+        // Schedule `toggle` to run 500ms (8e6 clock cycles at 16 MHz) in the future
+        cx.schedule.toggle(now + 500.ms().cyccnt(&clocks::RESET).unwrap(), true).unwrap();
 
         // power on GPIOA, RM0368 6.3.11
         device.RCC.ahb1enr.modify(|_, w| w.gpioaen().set_bit());
@@ -55,7 +56,7 @@ const APP: () = {
         }
 
         cx.schedule
-            .toggle(cx.scheduled + 8_000_000.cycles(), !toggle)
+            .toggle(cx.scheduled + 500.ms().cyccnt(&clocks::RESET).unwrap(), !toggle)
             .unwrap();
     }
 
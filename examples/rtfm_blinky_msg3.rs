@@ -3,10 +3,11 @@
 #![no_main]
 #![no_std]
 
+use app::{clocks, time::U32Ext as _};
 use cortex_m::peripheral::DWT;
 use cortex_m_semihosting::hprintln;
 use panic_halt as _;
-use rtfm::cyccnt::{Instant, U32Ext as _};
+use rtfm::cyccnt::Instant;
 use stm32f4xx_hal::stm32;
 
 #[rtfm::app(device = stm32f4xx_hal::stm32, monotonic = rtfm::cyccnt::CYCCNT, peripherals = true)]
@@ -32,7 +33,7 @@ const APP: () = {
         device.GPIOA.moder.modify(|_, w| w.moder5().bits(1));
 
         cx.schedule
-            .toggle(now + 8_000_000.cycles(), true, device.GPIOA)
+            .toggle(now + 500.ms().cyccnt(&clocks::RESET).unwrap(), true, device.GPIOA)
             .ok();
     }
 
@@ -47,7 +48,7 @@ const APP: () = {
         }
 
         cx.schedule
-            .toggle(cx.scheduled + 8_000_000.cycles(), !toggle, gpioa)
+            .toggle(cx.scheduled + 500.ms().cyccnt(&clocks::RESET).unwrap(), !toggle, gpioa)
             .ok();
     }
 
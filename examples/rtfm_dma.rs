@@ -0,0 +1,97 @@
+//! DMA serial echo
+//!
+//! Connect using e.g., `moserial` to `/dev/ttyACM0` (ACM number may change)
+//! 115200 8N1
+//!
+//! As `rtfm_serial.rs`, but bytes are moved by DMA and the CPU only sees
+//! whole frames (ended by a pause on the line, or a full buffer). Each frame
+//! is sent back using the very same buffer, and traced over ITM.
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use app::{
+    board,
+    dma::{self, Frame, Rx, Tx},
+};
+use cortex_m::{iprintln, peripheral::ITM};
+
+const N: usize = 64;
+
+#[rtfm::app(device = stm32f4xx_hal::stm32, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        // late resources
+        rx: Rx<N>,
+        tx: Option<Tx>,
+        itm: ITM,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        static mut A: [u8; N] = [0; N];
+        static mut B: [u8; N] = [0; N];
+        static mut C: [u8; N] = [0; N];
+
+        let device = cx.device;
+        let (_led, _button, vcp, _clocks) =
+            board::setup(device.RCC, device.GPIOA, device.GPIOC, device.USART2);
+        let (rx, tx) = dma::usart2(vcp, [A, B, C]);
+
+        init::LateResources {
+            rx,
+            tx: Some(tx),
+            itm: cx.core.ITM,
+        }
+    }
+
+    // the line went idle
+    #[task(binds = USART2, priority = 2, resources = [rx], spawn = [echo])]
+    fn usart2(cx: usart2::Context) {
+        if let Some(frame) = cx.resources.rx.on_interrupt() {
+            if let Err(frame) = cx.spawn.echo(frame) {
+                cx.resources.rx.release(frame);
+            }
+        }
+    }
+
+    // a buffer was filled
+    #[task(binds = DMA1_STREAM5, priority = 2, resources = [rx], spawn = [echo])]
+    fn dma1_stream5(cx: dma1_stream5::Context) {
+        if let Some(frame) = cx.resources.rx.on_interrupt() {
+            if let Err(frame) = cx.spawn.echo(frame) {
+                cx.resources.rx.release(frame);
+            }
+        }
+    }
+
+    #[task(priority = 1, capacity = 2, resources = [rx, tx, itm])]
+    fn echo(mut cx: echo::Context, frame: Frame<N>) {
+        static mut REPORTED: u32 = 0;
+
+        iprintln!(&mut cx.resources.itm.stim[0], "{} bytes", frame.len());
+
+        // the `Tx` is always put back, so it is there to take
+        let tx = cx.resources.tx.take().unwrap();
+        let (frame, tx) = tx.write(frame).wait();
+        *cx.resources.tx = Some(tx);
+
+        let errors = cx.resources.rx.lock(|rx| {
+            rx.release(frame);
+            rx.errors()
+        });
+        let total = errors.overrun + errors.framing + errors.noise + errors.dropped;
+        if total != *REPORTED {
+            iprintln!(&mut cx.resources.itm.stim[0], "{:?}", errors);
+            *REPORTED = total;
+        }
+    }
+
+    extern "C" {
+        fn EXTI0();
+    }
+};
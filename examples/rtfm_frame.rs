@@ -0,0 +1,122 @@
+//! Framed packet echo
+//!
+//! Talk to it from the host using the `host` library (see `host/`), e.g.,
+//!
+//! ``` text
+//! > stty -F /dev/ttyACM0 115200 raw -echo min 0 time 1
+//! ```
+//!
+//! followed by `Port::open("/dev/ttyACM0")`, `port.send(b"hello")` and
+//! `port.recv()`.
+//!
+//! Each received `Data` packet is acknowledged and its payload sent back,
+//! reliably. Link statistics are traced over ITM when they change.
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use app::{
+    board, clocks,
+    frame::{Link, Stats, MAX_PAYLOAD},
+    ring::Queue,
+    serial::{self, Isr, Reader, Writer},
+    time::U32Ext as _,
+};
+use cortex_m::{
+    iprintln,
+    peripheral::{syst::SystClkSource, ITM},
+};
+
+const N: usize = 256;
+
+#[rtfm::app(device = stm32f4xx_hal::stm32, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        // late resources
+        isr: Isr<N>,
+        reader: Reader<N>,
+        writer: Writer<N>,
+        itm: ITM,
+        #[init(Link::new())]
+        link: Link,
+        #[init(0)]
+        millis: u32,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        static mut RX: Queue<u8, N> = Queue::new();
+        static mut TX: Queue<u8, N> = Queue::new();
+
+        let device = cx.device;
+        let (_led, _button, vcp, _clocks) =
+            board::setup(device.RCC, device.GPIOA, device.GPIOC, device.USART2);
+        let (isr, reader, writer) = serial::usart2(vcp, RX, TX);
+
+        let mut syst = cx.core.SYST;
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(1.ms().systick_reload(&clocks::RESET).unwrap());
+        syst.clear_current();
+        syst.enable_counter();
+        syst.enable_interrupt();
+
+        init::LateResources {
+            isr,
+            reader,
+            writer,
+            itm: cx.core.ITM,
+        }
+    }
+
+    #[task(binds = SysTick, priority = 3, resources = [millis], spawn = [service])]
+    fn tick(cx: tick::Context) {
+        *cx.resources.millis = cx.resources.millis.wrapping_add(1);
+        // retransmission timeouts are checked every 10 ms
+        if *cx.resources.millis % 10 == 0 {
+            let _ = cx.spawn.service();
+        }
+    }
+
+    #[task(binds = USART2, priority = 2, resources = [isr], spawn = [service])]
+    fn usart2(cx: usart2::Context) {
+        cx.resources.isr.on_interrupt();
+        // a pending `service` is already on its way
+        let _ = cx.spawn.service();
+    }
+
+    #[task(priority = 1, resources = [reader, writer, link, millis, itm])]
+    fn service(mut cx: service::Context) {
+        static mut STATS: Stats = Stats::new();
+
+        let now = cx.resources.millis.lock(|millis| *millis);
+        let link = cx.resources.link;
+        let writer = cx.resources.writer;
+
+        while let Some(byte) = cx.resources.reader.read() {
+            let mut echo = [0; MAX_PAYLOAD];
+            let n = match link.receive(byte, now, writer) {
+                Some(payload) => {
+                    echo[..payload.len()].copy_from_slice(payload);
+                    payload.len()
+                }
+                None => continue,
+            };
+            // dropped if the previous echo is still in flight
+            let _ = link.send(&echo[..n], now, writer);
+        }
+        let _ = link.poll(now, writer);
+
+        if link.stats() != *STATS {
+            *STATS = link.stats();
+            iprintln!(&mut cx.resources.itm.stim[0], "{:?}", *STATS);
+        }
+    }
+
+    extern "C" {
+        fn EXTI0();
+    }
+};
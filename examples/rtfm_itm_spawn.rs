@@ -1,11 +1,31 @@
+//! Spawning from `init` and `idle`, with task trace events
+//!
+//! Besides the messages on port 0, `init`, `idle`, `task1` and the `itm`
+//! lock emit events on port 3 (see `src/event.rs`). View their interleaving
+//! in a waveform viewer, or in `chrome://tracing`:
+//!
+//! ``` console
+//! > cd host
+//! > cargo run --bin events -- -o spawn.vcd /tmp/itm.fifo
+//! > gtkwave spawn.vcd
+//! ```
+//!
+//! ---
 #![no_main]
 #![no_std]
 
 use cortex_m::iprintln;
 
+use app::event;
 use panic_semihosting as _;
 use rtfm::app;
 
+// event ids
+const INIT: u16 = 0;
+const IDLE: u16 = 1;
+const TASK1: u16 = 2;
+const ITM: u16 = 0;
+
 #[app(device = stm32f4xx_hal::stm32, peripherals = true )]
 const APP: () = {
     struct Resources {
@@ -13,7 +33,18 @@ const APP: () = {
     }
     #[init(spawn = [task1])]
     fn init(cx: init::Context) -> init::LateResources {
-        let mut itm = cx.core.ITM;
+        let mut core = cx.core;
+        // events are stamped with the cycle counter
+        core.DCB.enable_trace();
+        core.DWT.enable_cycle_counter();
+
+        event::task(INIT, "init");
+        event::task(IDLE, "idle");
+        event::task(TASK1, "task1");
+        event::resource(ITM, "itm");
+        let _event = event::enter(INIT);
+
+        let mut itm = core.ITM;
         let stim = &mut itm.stim[0];
         iprintln!(stim, "in init");
 
@@ -23,8 +54,11 @@ const APP: () = {
 
     #[idle (resources = [itm], spawn = [task1])]
     fn idle(cx: idle::Context) -> ! {
+        // never exits
+        let _event = event::enter(IDLE);
         let (mut itm, spawn) = (cx.resources.itm, cx.spawn);
         itm.lock(|itm| {
+            let _event = event::lock(ITM);
             let stim = &mut itm.stim[0];
             spawn.task1("from idle, itm locked").unwrap();
             iprintln!(stim, "idle");
@@ -38,6 +72,7 @@ const APP: () = {
 
     #[task (resources = [itm])]
     fn task1(cx: task1::Context, called_from: &'static str) {
+        let _event = event::enter(TASK1);
         let stim = &mut cx.resources.itm.stim[0];
         iprintln!(stim, "task1 {}", called_from);
     }
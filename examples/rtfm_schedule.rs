@@ -3,10 +3,11 @@
 #![no_main]
 #![no_std]
 
+use app::{clocks, time::U32Ext as _};
 use cortex_m::peripheral::DWT;
 use cortex_m_semihosting::hprintln;
 use panic_halt as _;
-use rtfm::cyccnt::{Instant, U32Ext as _};
+use rtfm::cyccnt::Instant;
 
 #[rtfm::app(device = stm32f4xx_hal::stm32, monotonic = rtfm::cyccnt::CYCCNT)]
 const APP: () = {
@@ -26,11 +27,11 @@ const APP: () = {
 
         hprintln!("init @ {:?}", now).unwrap();
 
-        // Schedule `foo` to run 8e6 cycles (clock cycles) in the future
-        cx.schedule.foo(now + 8_000_000.cycles()).unwrap();
+        // Schedule `foo` to run 500ms (8e6 clock cycles at 16 MHz) in the future
+        cx.schedule.foo(now + 500.ms().cyccnt(&clocks::RESET).unwrap()).unwrap();
 
-        // Schedule `bar` to run 4e6 cycles in the future
-        cx.schedule.bar(now + 4_000_000.cycles()).unwrap();
+        // Schedule `bar` to run 250ms (4e6 clock cycles at 16 MHz) in the future
+        cx.schedule.bar(now + 250.ms().cyccnt(&clocks::RESET).unwrap()).unwrap();
     }
 
     #[task]
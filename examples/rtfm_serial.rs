@@ -0,0 +1,80 @@
+//! Buffered serial echo
+//!
+//! Connect using e.g., `moserial` to `/dev/ttyACM0` (ACM number may change)
+//! 115200 8N1
+//!
+//! As `serial.rs`, but received bytes are buffered by the `USART2` interrupt,
+//! so sending multiple characters at once no longer overflows the input.
+//! Receive errors are traced over ITM.
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use app::{
+    board,
+    ring::Queue,
+    serial::{self, Isr, Reader, Writer},
+};
+use cortex_m::{iprintln, peripheral::ITM};
+
+const N: usize = 64;
+
+#[rtfm::app(device = stm32f4xx_hal::stm32, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        // late resources
+        isr: Isr<N>,
+        reader: Reader<N>,
+        writer: Writer<N>,
+        itm: ITM,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        static mut RX: Queue<u8, N> = Queue::new();
+        static mut TX: Queue<u8, N> = Queue::new();
+
+        let device = cx.device;
+        let (_led, _button, vcp, _clocks) =
+            board::setup(device.RCC, device.GPIOA, device.GPIOC, device.USART2);
+        let (isr, reader, writer) = serial::usart2(vcp, RX, TX);
+
+        init::LateResources {
+            isr,
+            reader,
+            writer,
+            itm: cx.core.ITM,
+        }
+    }
+
+    #[task(binds = USART2, priority = 2, resources = [isr], spawn = [echo])]
+    fn usart2(cx: usart2::Context) {
+        cx.resources.isr.on_interrupt();
+        // a pending `echo` is already on its way
+        let _ = cx.spawn.echo();
+    }
+
+    #[task(priority = 1, resources = [isr, reader, writer, itm])]
+    fn echo(mut cx: echo::Context) {
+        static mut REPORTED: u32 = 0;
+
+        while let Some(byte) = cx.resources.reader.read() {
+            let _ = cx.resources.writer.write(byte);
+        }
+
+        let errors = cx.resources.isr.lock(|isr| isr.errors());
+        let total = errors.overrun + errors.framing + errors.noise + errors.dropped;
+        if total != *REPORTED {
+            iprintln!(&mut cx.resources.itm.stim[0], "{:?}", errors);
+            *REPORTED = total;
+        }
+    }
+
+    extern "C" {
+        fn EXTI0();
+    }
+};
@@ -0,0 +1,151 @@
+//! Command shell
+//!
+//! Connect using e.g., `moserial` or `screen /dev/ttyACM0 115200` to
+//! `/dev/ttyACM0` (ACM number may change), 115200 8N1
+//!
+//! Type `help` for the list of commands. Take care with `peek` and `poke`,
+//! an address outside of mapped memory raises a HardFault.
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use app::{
+    board::{self, Led},
+    clocks::{self, Clocks},
+    ring::Queue,
+    serial::{self, Isr, Reader, Writer},
+    shell::{self, Shell, Target},
+    time::{Duration, U32Ext as _},
+};
+use core::{fmt, ptr};
+use cortex_m::peripheral::{syst::SystClkSource, SCB};
+
+const N: usize = 64;
+
+#[rtfm::app(device = stm32f4xx_hal::stm32, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        // late resources
+        isr: Isr<N>,
+        reader: Reader<N>,
+        writer: Writer<N>,
+        led: Led,
+        shell: Shell<N, 4>,
+        #[init(0)]
+        millis: u32,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        static mut RX: Queue<u8, N> = Queue::new();
+        static mut TX: Queue<u8, N> = Queue::new();
+
+        let device = cx.device;
+        let (led, _button, vcp, _clocks) =
+            board::setup(device.RCC, device.GPIOA, device.GPIOC, device.USART2);
+        let (isr, reader, mut writer) = serial::usart2(vcp, RX, TX);
+
+        let mut syst = cx.core.SYST;
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(1.ms().systick_reload(&clocks::RESET).unwrap());
+        syst.clear_current();
+        syst.enable_counter();
+        syst.enable_interrupt();
+
+        let shell = Shell::new(shell::COMMANDS);
+        let _ = shell.prompt(&mut writer);
+
+        init::LateResources {
+            isr,
+            reader,
+            writer,
+            led,
+            shell,
+        }
+    }
+
+    #[task(binds = SysTick, priority = 3, resources = [millis])]
+    fn tick(cx: tick::Context) {
+        *cx.resources.millis = cx.resources.millis.wrapping_add(1);
+    }
+
+    #[task(binds = USART2, priority = 2, resources = [isr], spawn = [input])]
+    fn usart2(cx: usart2::Context) {
+        cx.resources.isr.on_interrupt();
+        // a pending `input` is already on its way
+        let _ = cx.spawn.input();
+    }
+
+    #[task(priority = 1, resources = [reader, writer, led, shell, millis])]
+    fn input(mut cx: input::Context) {
+        let millis = cx.resources.millis.lock(|millis| *millis);
+        let mut hw = Hw {
+            led: cx.resources.led,
+            millis,
+        };
+        let mut out = Blocking(cx.resources.writer);
+
+        while let Some(byte) = cx.resources.reader.read() {
+            let _ = cx.resources.shell.feed(byte, &mut hw, &mut out);
+        }
+    }
+
+    extern "C" {
+        fn EXTI0();
+    }
+};
+
+// the board, as seen by the shell
+struct Hw<'a> {
+    led: &'a mut Led,
+    millis: u32,
+}
+
+impl Target for Hw<'_> {
+    fn led(&mut self, on: bool) {
+        if on {
+            self.led.on()
+        } else {
+            self.led.off()
+        }
+    }
+
+    #[allow(unsafe_code)]
+    fn peek(&mut self, address: u32) -> u32 {
+        unsafe { ptr::read_volatile(address as *const u32) }
+    }
+
+    #[allow(unsafe_code)]
+    fn poke(&mut self, address: u32, value: u32) {
+        unsafe { ptr::write_volatile(address as *mut u32, value) }
+    }
+
+    fn clocks(&self) -> Clocks {
+        clocks::RESET
+    }
+
+    fn reset(&mut self) {
+        SCB::sys_reset()
+    }
+
+    fn uptime(&self) -> Duration {
+        Duration::from_millis(self.millis)
+    }
+}
+
+// waits for room in the TX queue, drained by the (higher priority) USART2
+// interrupt, so long outputs such as `help` are not truncated
+struct Blocking<'a>(&'a mut Writer<N>);
+
+impl fmt::Write for Blocking<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            while self.0.write(byte).is_err() {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! Execution times of RTFM tasks, see `src/wcet.rs`
+//!
+//! `sample` runs every millisecond at priority 2, `blink` every 500 ms at
+//! priority 1, and they share `count`. Press the user button (B1) to write
+//! the measurements to ITM port 0:
+//!
+//! ``` console
+//! > cargo run --example rtfm_wcet --features rtfm,wcet
+//! ```
+//!
+//! Save the report for the response time analysis (`srp` in `host/`).
+//!
+//! ---
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use app::{
+    bench::Itm,
+    board::{self, Led, UserButton},
+    clocks,
+    time::U32Ext as _,
+    wcet::{self, Probe},
+};
+use cortex_m::peripheral::{DWT, ITM};
+
+// periods in cycles at 16 MHz
+const SAMPLE_PERIOD: u32 = 16_000;
+const BLINK_PERIOD: u32 = 8_000_000;
+
+static SAMPLE: Probe = Probe::task("sample", 2).period(SAMPLE_PERIOD);
+static BLINK: Probe = Probe::task("blink", 1).period(BLINK_PERIOD);
+static BLINK_COUNT: Probe = Probe::lock("blink", "count", 2);
+
+#[rtfm::app(device = stm32f4xx_hal::stm32, monotonic = rtfm::cyccnt::CYCCNT, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        // late resources
+        led: Led,
+        button: UserButton,
+        itm: ITM,
+        #[init(0)]
+        count: u32,
+    }
+
+    #[init(schedule = [sample, blink])]
+    fn init(cx: init::Context) -> init::LateResources {
+        let mut core = cx.core;
+        let device = cx.device;
+
+        // Initialize (enable) the monotonic timer (CYCCNT), also used by the probes
+        core.DCB.enable_trace();
+        DWT::unlock();
+        core.DWT.enable_cycle_counter();
+
+        let (led, button, _vcp, _clocks) =
+            board::setup(device.RCC, device.GPIOA, device.GPIOC, device.USART2);
+
+        let now = cx.start;
+        cx.schedule.sample(now + 1.ms().cyccnt(&clocks::RESET).unwrap()).unwrap();
+        cx.schedule.blink(now + 500.ms().cyccnt(&clocks::RESET).unwrap()).unwrap();
+
+        init::LateResources {
+            led,
+            button,
+            itm: core.ITM,
+        }
+    }
+
+    #[idle(resources = [button], spawn = [report])]
+    fn idle(cx: idle::Context) -> ! {
+        let mut pressed = false;
+        loop {
+            // report once per press
+            let now = cx.resources.button.is_pressed();
+            if now && !pressed {
+                let _ = cx.spawn.report();
+            }
+            pressed = now;
+        }
+    }
+
+    #[task(priority = 2, resources = [count], schedule = [sample])]
+    fn sample(cx: sample::Context) {
+        let _wcet = SAMPLE.start();
+        *cx.resources.count += 1;
+        cx.schedule
+            .sample(cx.scheduled + 1.ms().cyccnt(&clocks::RESET).unwrap())
+            .unwrap();
+    }
+
+    #[task(priority = 1, resources = [led, count], schedule = [blink])]
+    fn blink(mut cx: blink::Context) {
+        let _wcet = BLINK.start();
+        let count = cx.resources.count.lock(|count| {
+            let _wcet = BLINK_COUNT.start();
+            let c = *count;
+            *count = 0;
+            c
+        });
+        // `sample` ran about 500 times since the last blink
+        if count > 0 {
+            cx.resources.led.toggle();
+        }
+        cx.schedule
+            .blink(cx.scheduled + 500.ms().cyccnt(&clocks::RESET).unwrap())
+            .unwrap();
+    }
+
+    #[task(priority = 1, resources = [itm])]
+    fn report(cx: report::Context) {
+        let _ = wcet::report(
+            &[&SAMPLE, &BLINK, &BLINK_COUNT],
+            &mut Itm(&mut cx.resources.itm.stim[0]),
+        );
+    }
+
+    extern "C" {
+        fn EXTI0();
+    }
+};
@@ -0,0 +1,57 @@
+//! Timestamped ITM output, configured from Rust
+//!
+//! Runs the core at 48 MHz and sets up the TPIU (SWO at 2 Mbit/s, as the
+//! `stlink` expects) and ITM local timestamps to match (see `src/swo.rs`).
+//! View the output with each line stamped by the `itm` host tool (in
+//! `host/`), giving it the core clock:
+//!
+//! ``` console
+//! > cargo run --bin itm -- -F -t 48000000 -l info /tmp/itm.fifo
+//! ```
+//!
+//! Compare the time between the lines with the cycles measured by the DWT.
+//!
+//! ---
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use app::{
+    clocks::Config,
+    log::prelude::*,
+    stm32f40x::{FLASH, PWR, RCC},
+    swo,
+};
+use cortex_m::{asm, peripheral::DWT, Peripherals};
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let mut p = Peripherals::take().unwrap();
+
+    #[allow(unsafe_code)]
+    let (rcc, flash, pwr) = unsafe { (&*RCC::get(), &*FLASH::get(), &*PWR::get()) };
+    let clocks = Config::hsi()
+        .sysclk(48_000_000)
+        .freeze(rcc, flash, pwr)
+        .unwrap();
+    swo::Config::new(&clocks)
+        .apply(&mut p.DCB, &p.TPIU, &mut p.ITM, &mut p.DWT)
+        .unwrap();
+    app::log::init(p.ITM);
+
+    info!("sysclk {} Hz", clocks.sysclk());
+    for i in 1..=5 {
+        let start = DWT::get_cycle_count();
+        asm::delay(i * 1_000_000);
+        let cycles = DWT::get_cycle_count().wrapping_sub(start);
+        info!("delay {} cycles, {} us", cycles, cycles / 48);
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -0,0 +1,242 @@
+//! Backtraces, as written by `src/backtrace.rs`
+//!
+//! A `backtrace:` line of raw addresses is symbolised against the ELF of
+//! the firmware: the function from the symbol table, the file and line from
+//! the DWARF line tables (see `dwarf.rs`).
+//!
+//! ``` no_run
+//! use host::backtrace::{addresses, Symbols};
+//! use host::elf::Elf;
+//!
+//! let elf = Elf::parse(std::fs::read("target/thumbv7em-none-eabihf/release/examples/backtrace")?)?;
+//! let symbols = Symbols::new(&elf)?;
+//! let line = "backtrace: 0x08000a1b 0x080004f7 0x08000529";
+//! for (i, address) in addresses(line).unwrap().into_iter().enumerate() {
+//!     println!("{:>4}: {}", i, symbols.frame(address));
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! A return address (Thumb bit set) is looked up one byte back, in the call
+//! instruction, as the return may be to the next line or function. A function
+//! inlined into another is reported under the name of the other, at the line
+//! of the inlined code.
+
+use std::fmt;
+
+use crate::dwarf::{self, Lines, Location};
+use crate::elf::{self, Elf, STT_FUNC};
+
+/// Starts the line of addresses.
+pub const PREFIX: &str = "backtrace:";
+
+/// The addresses of a `backtrace:` line, `None` for other lines.
+pub fn addresses(line: &str) -> Option<Vec<u32>> {
+    let rest = line.trim().strip_prefix(PREFIX)?;
+    rest.split_whitespace()
+        .map(|word| {
+            let hex = word.strip_prefix("0x").unwrap_or(word);
+            u32::from_str_radix(hex, 16).ok()
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Elf(elf::Error),
+    Dwarf(dwarf::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Elf(e) => e.fmt(f),
+            Error::Dwarf(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<elf::Error> for Error {
+    fn from(e: elf::Error) -> Self {
+        Error::Elf(e)
+    }
+}
+
+impl From<dwarf::Error> for Error {
+    fn from(e: dwarf::Error) -> Self {
+        Error::Dwarf(e)
+    }
+}
+
+/// A symbolised address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// The address, Thumb bit cleared.
+    pub address: u32,
+    /// The function, demangled.
+    pub function: Option<String>,
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for Frame {
+    /// As `gdb` does, `0x08000528 in backtrace::c at examples/backtrace.rs:65`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#010x} in ", self.address)?;
+        f.write_str(self.function.as_deref().unwrap_or("??"))?;
+        if let Some(location) = &self.location {
+            write!(f, " at {}:{}", location.file, location.line)?;
+        }
+        Ok(())
+    }
+}
+
+// a function, `start..end`
+#[derive(Debug)]
+struct Function {
+    start: u32,
+    end: u32,
+    name: String,
+}
+
+/// The functions and line tables of an ELF.
+#[derive(Debug)]
+pub struct Symbols {
+    functions: Vec<Function>,
+    lines: Lines,
+}
+
+impl Symbols {
+    pub fn new(elf: &Elf) -> Result<Self, Error> {
+        let mut functions: Vec<Function> = elf
+            .symbols()?
+            .into_iter()
+            .filter(|s| s.kind == STT_FUNC && s.value != 0)
+            .map(|s| {
+                let start = s.value & !1;
+                Function {
+                    start,
+                    end: start + s.size.max(1),
+                    name: demangle(&s.name),
+                }
+            })
+            .collect();
+        functions.sort_by_key(|f| f.start);
+        Ok(Symbols {
+            functions,
+            lines: Lines::parse(elf)?,
+        })
+    }
+
+    /// The function and location of `address`, as sent by the firmware.
+    pub fn frame(&self, address: u32) -> Frame {
+        let pc = address & !1;
+        // a return address, in the call before
+        let at = if address & 1 != 0 {
+            pc.wrapping_sub(1)
+        } else {
+            pc
+        };
+        let function = self
+            .functions
+            .iter()
+            .rev()
+            .find(|f| f.start <= at && at < f.end)
+            .map(|f| f.name.clone());
+        Frame {
+            address: pc,
+            function,
+            location: self.lines.find(at),
+        }
+    }
+}
+
+/// Demangles a Rust (legacy, `_ZN...E`) symbol, without its hash; other
+/// symbols are returned as they are.
+pub fn demangle(symbol: &str) -> String {
+    let mangled = match symbol
+        .strip_prefix("_ZN")
+        .or_else(|| symbol.strip_prefix("__ZN"))
+    {
+        Some(rest) => rest,
+        None => return symbol.to_string(),
+    };
+
+    let (mut parts, mut rest) = (vec![], mangled);
+    while !rest.starts_with('E') {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let n: usize = match rest[..digits].parse() {
+            Ok(n) if digits > 0 => n,
+            _ => return symbol.to_string(),
+        };
+        match rest.get(digits..digits + n) {
+            Some(part) => parts.push(part),
+            None => return symbol.to_string(),
+        }
+        rest = &rest[digits + n..];
+    }
+
+    // the hash, `h` and 16 hex digits
+    if let Some(last) = parts.last() {
+        if last.len() == 17
+            && last.starts_with('h')
+            && last[1..].bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            parts.pop();
+        }
+    }
+    let parts: Vec<String> = parts.into_iter().map(unescape).collect();
+    parts.join("::")
+}
+
+// `$LT$` and such, and `..` for `::`
+fn unescape(part: &str) -> String {
+    // a leading `$` is escaped by an `_`
+    let part = if part.starts_with("_$") {
+        &part[1..]
+    } else {
+        part
+    };
+    let mut out = String::new();
+    let mut rest = part;
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix("..") {
+            out.push_str("::");
+            rest = tail;
+        } else if rest.starts_with('$') {
+            let end = match rest[1..].find('$') {
+                Some(end) => end + 1,
+                None => {
+                    out.push_str(rest);
+                    break;
+                }
+            };
+            let escape = &rest[1..end];
+            match escape {
+                "SP" => out.push('@'),
+                "BP" => out.push('*'),
+                "RF" => out.push('&'),
+                "LT" => out.push('<'),
+                "GT" => out.push('>'),
+                "LP" => out.push('('),
+                "RP" => out.push(')'),
+                "C" => out.push(','),
+                _ => match escape
+                    .strip_prefix('u')
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(std::char::from_u32)
+                {
+                    Some(c) => out.push(c),
+                    None => out.push_str(&rest[..=end]),
+                },
+            }
+            rest = &rest[end + 1..];
+        } else {
+            let n = rest.find(['$', '.']).unwrap_or(rest.len()).max(1);
+            out.push_str(&rest[..n]);
+            rest = &rest[n..];
+        }
+    }
+    out
+}
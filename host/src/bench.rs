@@ -0,0 +1,118 @@
+//! Benchmark reports, as written by `src/bench.rs`
+//!
+//! Each report is a line, possibly among other output (or after a log or
+//! time prefix):
+//!
+//! ``` text
+//! bench copy_from_slice: n=32 min=93 median=93 max=101 overhead=12
+//! ```
+
+use std::fmt::Write;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub name: String,
+    pub iterations: u32,
+    pub min: u32,
+    pub median: u32,
+    pub max: u32,
+    pub overhead: u32,
+}
+
+impl Report {
+    /// Parses a result line, `None` for other lines.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = &line[line.find("bench ")? + "bench ".len()..];
+        let (name, fields) = line.split_at(line.find(": ")?);
+
+        let mut values = [None; 5];
+        for field in fields[2..].split_whitespace() {
+            let (key, value) = field.split_at(field.find('=')?);
+            let i = ["n", "min", "median", "max", "overhead"]
+                .iter()
+                .position(|&k| k == key)?;
+            values[i] = Some(value[1..].parse().ok()?);
+        }
+        Some(Report {
+            name: name.to_string(),
+            iterations: values[0]?,
+            min: values[1]?,
+            median: values[2]?,
+            max: values[3]?,
+            overhead: values[4]?,
+        })
+    }
+
+    /// All results of `text`, in order.
+    pub fn parse_all(text: &str) -> Vec<Self> {
+        text.lines().filter_map(Report::parse).collect()
+    }
+}
+
+// `+12.5%`, the change of the median
+fn change(before: &Report, after: &Report) -> String {
+    if before.median == 0 {
+        return "-".to_string();
+    }
+    let change = 100.0 * (after.median as f64 - before.median as f64) / before.median as f64;
+    format!("{:+.1}%", change)
+}
+
+/// A table of the cycles (min, median and max) of `before`, and if given,
+/// of `after` with the change of the median. Benchmarks are matched by name,
+/// in the order of `before` (then those only in `after`).
+pub fn table(before: &[Report], after: Option<&[Report]>) -> String {
+    let mut names: Vec<&str> = before.iter().map(|r| r.name.as_str()).collect();
+    for r in after.unwrap_or(&[]) {
+        if !names.contains(&r.name.as_str()) {
+            names.push(&r.name);
+        }
+    }
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0).max(4);
+    let find = |results: &'_ [Report], name: &str| results.iter().find(|r| r.name == name).cloned();
+    let cycles = |r: &Option<Report>| match r {
+        Some(r) => format!("{:>8} {:>8} {:>8}", r.min, r.median, r.max),
+        None => format!("{:>8} {:>8} {:>8}", "-", "-", "-"),
+    };
+
+    let mut out = String::new();
+    let header = format!("{:>8} {:>8} {:>8}", "min", "median", "max");
+    match after {
+        Some(_) => writeln!(
+            out,
+            "{:w$}  {}  {}  {:>8}",
+            "name",
+            header,
+            header,
+            "change",
+            w = width
+        ),
+        None => writeln!(out, "{:w$}  {}", "name", header, w = width),
+    }
+    .unwrap();
+
+    for name in names {
+        let b = find(before, name);
+        match after {
+            Some(after) => {
+                let a = find(after, name);
+                let change = match (&b, &a) {
+                    (Some(b), Some(a)) => change(b, a),
+                    _ => "-".to_string(),
+                };
+                writeln!(
+                    out,
+                    "{:w$}  {}  {}  {:>8}",
+                    name,
+                    cycles(&b),
+                    cycles(&a),
+                    change,
+                    w = width
+                )
+            }
+            None => writeln!(out, "{:w$}  {}", name, cycles(&b), w = width),
+        }
+        .unwrap();
+    }
+    out
+}
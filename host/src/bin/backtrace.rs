@@ -0,0 +1,90 @@
+//! `backtrace`, symbolises backtraces (see `src/backtrace.rs`)
+//!
+//! ``` text
+//! > backtrace -e ELF [FILE]
+//! ```
+//!
+//! Copies the text of `FILE` (default stdin, e.g., piped from `itm` or a
+//! serial terminal) to stdout, line by line, replacing each `backtrace:`
+//! line of addresses by its frames, symbolised against the firmware `ELF`:
+//!
+//! ``` text
+//! panicked at 'index out of bounds: the len is 4 but the index is 8', examples/backtrace.rs:65:5
+//! backtrace:
+//!    0: 0x08000a1a in rust_begin_unwind at examples/backtrace.rs:93
+//!    1: 0x080004f6 in core::panicking::panic_bounds_check at ...
+//!    2: 0x08000528 in backtrace::c at examples/backtrace.rs:65
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process;
+
+use host::backtrace::{addresses, Symbols, PREFIX};
+use host::elf::Elf;
+
+const USAGE: &str = "usage: backtrace -e ELF [FILE]";
+
+struct Options {
+    elf: String,
+    input: String,
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let (mut elf, mut input) = (None, "-".to_string());
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" => elf = Some(args.next().ok_or("-e needs ELF")?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            a if a.starts_with('-') && a != "-" => return Err(format!("unknown option `{}`", a)),
+            _ => input = arg,
+        }
+    }
+    Ok(Options {
+        elf: elf.ok_or(USAGE)?,
+        input,
+    })
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let elf = Elf::parse(std::fs::read(&options.elf)?)?;
+    let symbols = Symbols::new(&elf)?;
+
+    let input: Box<dyn BufRead> = if options.input == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(&options.input)?))
+    };
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in input.lines() {
+        let line = line?;
+        match addresses(&line) {
+            Some(addresses) => {
+                writeln!(out, "{}", PREFIX)?;
+                for (i, &address) in addresses.iter().enumerate() {
+                    writeln!(out, "{:>4}: {}", i, symbols.frame(address))?;
+                }
+            }
+            None => writeln!(out, "{}", line)?,
+        }
+        // as it comes, when following a live stream
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("backtrace: {}", e);
+        process::exit(1);
+    }
+}
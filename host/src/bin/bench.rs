@@ -0,0 +1,44 @@
+//! `bench`, compares benchmark runs (see `src/bench.rs`)
+//!
+//! ``` text
+//! > bench BEFORE [AFTER]
+//! ```
+//!
+//! Reads the results of one run from `BEFORE` (e.g., the ITM port 0 output
+//! of a dev build) and prints them as a table of cycles. Given the results
+//! of another run in `AFTER` (e.g., a release build), prints both side by
+//! side, with the change of the median.
+
+use std::process;
+
+use host::bench::{self, Report};
+
+const USAGE: &str = "usage: bench BEFORE [AFTER]";
+
+fn read(path: &str) -> Vec<Report> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Report::parse_all(&text),
+        Err(e) => {
+            eprintln!("bench: {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (before, after) = match args.as_slice() {
+        [before] => (read(before), None),
+        [before, after] => (read(before), Some(read(after))),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if before.is_empty() {
+        eprintln!("bench: no results in {}", args[0]);
+        process::exit(1);
+    }
+
+    print!("{}", bench::table(&before, after.as_deref()));
+}
@@ -0,0 +1,104 @@
+//! `coredump`, converts a core dump (see `src/coredump.rs`) to an ELF core
+//!
+//! ``` text
+//! > coredump [-i] [-o OUT] [FILE]
+//! ```
+//!
+//! Reads a capture from `FILE` (default `/tmp/itm.fifo`, `-` for stdin)
+//! until its end, prints the fault and registers of the dump in it and
+//! writes it as an ELF core file to `OUT` (default `core`):
+//! - `-i` the capture is an ITM stream, the dump being the payloads of port
+//!   4, otherwise the raw bytes of the serial port (without `FILE`, the
+//!   default `/tmp/itm.fifo` is taken as ITM)
+//!
+//! ``` console
+//! > coredump -o core /tmp/serial.bin
+//! > arm-none-eabi-gdb target/thumbv7em-none-eabihf/debug/examples/crash core
+//! (gdb) bt
+//! ```
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::process;
+
+use host::coredump::{Dump, PORT};
+use host::itm::{Decoder, Packet};
+
+const USAGE: &str = "usage: coredump [-i] [-o OUT] [FILE]";
+
+struct Options {
+    input: String,
+    output: String,
+    itm: bool,
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        input: "/tmp/itm.fifo".to_string(),
+        output: "core".to_string(),
+        itm: false,
+    };
+
+    let mut input = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-i" => options.itm = true,
+            "-o" => options.output = args.next().ok_or("-o needs OUT")?,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            a if a.starts_with('-') && a != "-" => return Err(format!("unknown option `{}`", a)),
+            _ => {
+                options.input = arg;
+                input = true;
+            }
+        }
+    }
+    if !input {
+        options.itm = true;
+    }
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let mut bytes = vec![];
+    if options.input == "-" {
+        io::stdin().read_to_end(&mut bytes)?;
+    } else {
+        File::open(&options.input)?.read_to_end(&mut bytes)?;
+    }
+
+    if options.itm {
+        let mut payloads = vec![];
+        for packet in Decoder::new().decode(&bytes) {
+            match packet {
+                Ok(Packet::Instrumentation { port, payload }) if port == PORT => {
+                    payloads.extend_from_slice(&payload)
+                }
+                Ok(Packet::Overflow) => eprintln!("coredump: overflow, bytes were lost"),
+                Ok(_) => {}
+                Err(e) => eprintln!("coredump: {}", e),
+            }
+        }
+        bytes = payloads;
+    }
+
+    let dump = Dump::parse(&bytes)?;
+    print!("{}", dump);
+    std::fs::write(&options.output, dump.to_elf())?;
+    println!("core written to {}", options.output);
+    Ok(())
+}
+
+fn main() {
+    let options = match parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("coredump: {}", e);
+        process::exit(1);
+    }
+}
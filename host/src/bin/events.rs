@@ -0,0 +1,121 @@
+//! `events`, converts task trace events (see `src/event.rs`)
+//!
+//! ``` text
+//! > events [-c HZ] [-f vcd|json] [-o OUT] [FILE]
+//! ```
+//!
+//! Reads an ITM stream from `FILE` (default `/tmp/itm.fifo`, `-` for stdin)
+//! until its end, and writes the events of port 3 to `OUT` (default stdout):
+//! - `-f vcd` as a VCD waveform (e.g., for `gtkwave`), the default
+//! - `-f json` as Chrome `trace_event` JSON (for `chrome://tracing` or
+//!   <https://ui.perfetto.dev>), the default if `OUT` ends in `.json`
+//! - `-c HZ` the core clock, converting cycles to time (default 16000000,
+//!   the HSI as after reset)
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+
+use host::event::{Trace, PORT};
+use host::itm::{Decoder, Packet};
+
+const USAGE: &str = "usage: events [-c HZ] [-f vcd|json] [-o OUT] [FILE]";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Vcd,
+    Json,
+}
+
+struct Options {
+    input: String,
+    output: Option<String>,
+    format: Option<Format>,
+    hz: u32,
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        input: "/tmp/itm.fifo".to_string(),
+        output: None,
+        format: None,
+        hz: 16_000_000,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" => {
+                let hz = args.next().ok_or("-c needs HZ")?;
+                options.hz = match hz.parse() {
+                    Ok(hz) if hz > 0 => hz,
+                    _ => return Err(format!("bad clock `{}`", hz)),
+                };
+            }
+            "-f" => {
+                options.format = match args.next().ok_or("-f needs vcd or json")?.as_str() {
+                    "vcd" => Some(Format::Vcd),
+                    "json" => Some(Format::Json),
+                    f => return Err(format!("unknown format `{}`, vcd or json", f)),
+                }
+            }
+            "-o" => options.output = Some(args.next().ok_or("-o needs OUT")?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            a if a.starts_with('-') && a != "-" => return Err(format!("unknown option `{}`", a)),
+            _ => options.input = arg,
+        }
+    }
+    Ok(options)
+}
+
+fn run(options: &Options) -> io::Result<()> {
+    let mut bytes = vec![];
+    if options.input == "-" {
+        io::stdin().read_to_end(&mut bytes)?;
+    } else {
+        File::open(&options.input)?.read_to_end(&mut bytes)?;
+    }
+
+    let mut trace = Trace::new();
+    for packet in Decoder::new().decode(&bytes) {
+        match packet {
+            Ok(Packet::Instrumentation { port, payload }) if port == PORT => {
+                if let Err(e) = trace.push(&payload) {
+                    eprintln!("events: {}", e);
+                }
+            }
+            Ok(Packet::Overflow) => eprintln!("events: overflow, events were lost"),
+            Ok(_) => {}
+            Err(e) => eprintln!("events: {}", e),
+        }
+    }
+
+    let json = match (options.format, &options.output) {
+        (Some(format), _) => format == Format::Json,
+        (None, Some(path)) => path.ends_with(".json"),
+        (None, None) => false,
+    };
+    let text = if json {
+        trace.chrome(options.hz)
+    } else {
+        trace.vcd(options.hz)
+    };
+    match &options.output {
+        Some(path) => File::create(path)?.write_all(text.as_bytes()),
+        None => io::stdout().write_all(text.as_bytes()),
+    }
+}
+
+fn main() {
+    let options = match parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("events: {}", e);
+        process::exit(1);
+    }
+}
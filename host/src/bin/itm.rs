@@ -0,0 +1,308 @@
+//! `itm`, an ITM/SWO stream decoder (replacing `itmdump`)
+//!
+//! ``` text
+//! > itm [-F] [-v] [-t HZ[/DIV]] [-l LEVEL [-n]] [-e ELF [-u]] [-p N=PATH]... [FILE]
+//! ```
+//!
+//! Reads `FILE` (default `/tmp/itm.fifo`, `-` for stdin) and writes the
+//! data of each stimulus port to its own output:
+//! - `-p N=PATH` writes port `N` to `PATH` (`-` for stdout), without any
+//!   `-p` ports 0, 1 and 2 go to stdout, `itm.1` and `itm.2`
+//! - `-l LEVEL` instead prints the log records (see `src/log.rs`) of ports
+//!   0, 1 and 2 up to `LEVEL` (`error`, `warn`, `info`, `debug` or `trace`)
+//!   to stdout, coloured by level unless `-n` is given
+//! - `-e ELF` decodes deferred log records (`log-deferred`, see
+//!   `src/defer.rs`) with the interned strings of `ELF`, implies `-l trace`
+//!   unless `-l` is given
+//! - `-u` reads deferred log records sent over the serial port (e.g.,
+//!   `/dev/ttyACM0`) instead of ITM
+//! - `-t HZ[/DIV]` starts each line with its time (seconds since the first
+//!   packet), from the ITM local timestamps of a core clock of `HZ` divided
+//!   by `DIV` (`1`, `4`, `16` or `64`, see `src/swo.rs`), marked `~` after
+//!   an overflow lost timestamps
+//! - `-F` follows the input, waiting for more data at its end
+//! - `-v` prints all other packets (timestamps, DWT, ...) to stderr
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use host::elf::Elf;
+use host::itm::{Clock, Decoder, Packet};
+use host::log::{self, Frames, Level, Lines, Record, Strings};
+
+const USAGE: &str =
+    "usage: itm [-F] [-v] [-t HZ[/DIV]] [-l LEVEL [-n]] [-e ELF [-u]] [-p N=PATH]... [FILE]";
+
+struct Options {
+    input: String,
+    ports: Vec<(u8, String)>,
+    follow: bool,
+    verbose: bool,
+    log: Option<Level>,
+    color: bool,
+    elf: Option<String>,
+    uart: bool,
+    clock: Option<Clock>,
+}
+
+// `HZ[/DIV]`
+fn clock(arg: &str) -> Result<Clock, String> {
+    let bad = || format!("bad clock `{}`, HZ[/DIV]", arg);
+    let (hz, div) = match arg.find('/') {
+        Some(i) => (&arg[..i], &arg[i + 1..]),
+        None => (arg, "1"),
+    };
+    match (hz.parse(), div.parse()) {
+        (Ok(hz), Ok(div @ (1 | 4 | 16 | 64))) if hz > 0 => Ok(Clock::new(hz, div)),
+        _ => Err(bad()),
+    }
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        input: "/tmp/itm.fifo".to_string(),
+        ports: vec![],
+        follow: false,
+        verbose: false,
+        log: None,
+        color: true,
+        elf: None,
+        uart: false,
+        clock: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-F" => options.follow = true,
+            "-v" => options.verbose = true,
+            "-n" => options.color = false,
+            "-u" => options.uart = true,
+            "-t" => options.clock = Some(clock(&args.next().ok_or("-t needs HZ")?)?),
+            "-e" => options.elf = Some(args.next().ok_or("-e needs an ELF")?),
+            "-l" => options.log = Some(args.next().ok_or("-l needs a LEVEL")?.parse()?),
+            "-p" => {
+                let map = args.next().ok_or("-p needs N=PATH")?;
+                let (port, path) = match map.find('=') {
+                    Some(i) => (&map[..i], &map[i + 1..]),
+                    None => return Err(format!("bad port mapping `{}`", map)),
+                };
+                let port = match port.parse() {
+                    Ok(port) if port < 32 => port,
+                    _ => return Err(format!("bad port `{}`, 0 to 31", port)),
+                };
+                options.ports.push((port, path.to_string()));
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            a if a.starts_with('-') && a != "-" => return Err(format!("unknown option `{}`", a)),
+            _ => options.input = arg,
+        }
+    }
+
+    if options.elf.is_some() && options.log.is_none() {
+        options.log = Some(Level::Trace);
+    }
+    if options.uart && options.elf.is_none() {
+        return Err("-u needs -e ELF".to_string());
+    }
+    if options.uart && options.clock.is_some() {
+        return Err("-t needs ITM input, not -u".to_string());
+    }
+    if options.ports.is_empty() && options.log.is_none() {
+        options.ports = vec![
+            (0, "-".to_string()),
+            (1, "itm.1".to_string()),
+            (2, "itm.2".to_string()),
+        ];
+    }
+    Ok(options)
+}
+
+fn open(path: &str) -> io::Result<Box<dyn Write>> {
+    Ok(if path == "-" {
+        Box::new(io::stdout())
+    } else {
+        Box::new(File::create(path)?)
+    })
+}
+
+// the interned strings of a `log-deferred` firmware
+fn strings(path: &str) -> io::Result<Strings> {
+    let invalid =
+        |e: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e));
+    let elf = Elf::parse(std::fs::read(path)?).map_err(|e| invalid(e.to_string()))?;
+    Strings::from_elf(&elf).ok_or_else(|| invalid("no .log section".to_string()))
+}
+
+// where decoded data goes
+struct Output {
+    options: Options,
+    ports: BTreeMap<u8, Port>,
+    strings: Option<Strings>,
+    lines: [Lines; 3],
+    frames: [Frames; 3],
+}
+
+struct Port {
+    out: Box<dyn Write>,
+    // at the start of a line, to be stamped
+    start: bool,
+}
+
+// `[   1.234567] `, `~` if timestamps were lost
+fn stamp(clock: &Clock) -> String {
+    let now = clock.now();
+    let exact = if clock.is_exact() { ' ' } else { '~' };
+    format!("[{}{:3}.{:06}] ", exact, now.as_secs(), now.subsec_micros())
+}
+
+impl Output {
+    // data of stimulus `port` (with `-u` all goes to port 0)
+    fn data(&mut self, port: u8, payload: &[u8], clock: Option<&Clock>) -> io::Result<()> {
+        if self.options.log.is_some() && port < 3 {
+            let port = port as usize;
+            if let Some(strings) = &self.strings {
+                for record in self.frames[port].push(payload) {
+                    deferred(&self.options, strings, record, clock);
+                }
+            } else {
+                for line in self.lines[port].push(payload) {
+                    log(&self.options, &line, clock);
+                }
+            }
+            return Ok(());
+        }
+
+        if let Some(port) = self.ports.get_mut(&port) {
+            match clock {
+                Some(clock) => {
+                    for line in payload.split_inclusive(|&b| b == b'\n') {
+                        if port.start {
+                            port.out.write_all(stamp(clock).as_bytes())?;
+                        }
+                        port.out.write_all(line)?;
+                        port.start = line.ends_with(b"\n");
+                    }
+                }
+                None => port.out.write_all(payload)?,
+            }
+            port.out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn run(options: Options) -> io::Result<()> {
+    let mut ports = BTreeMap::new();
+    for (port, path) in &options.ports {
+        let out = open(path)?;
+        ports.insert(*port, Port { out, start: true });
+    }
+
+    let mut input: Box<dyn Read> = if options.input == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(&options.input)?)
+    };
+
+    let strings = match &options.elf {
+        Some(path) => Some(strings(path)?),
+        None => None,
+    };
+    let mut clock = options.clock;
+    let mut output = Output {
+        options,
+        ports,
+        strings,
+        lines: [Lines::new(), Lines::new(), Lines::new()],
+        frames: [Frames::new(), Frames::new(), Frames::new()],
+    };
+    // data waiting for the timestamp that follows it
+    let mut pending: Vec<(u8, Vec<u8>)> = vec![];
+    let mut decoder = Decoder::new();
+    let mut buf = [0; 1024];
+    loop {
+        let n = input.read(&mut buf)?;
+        if n == 0 {
+            if output.options.follow {
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            for (port, payload) in pending {
+                output.data(port, &payload, clock.as_ref())?;
+            }
+            return Ok(());
+        }
+
+        if output.options.uart {
+            output.data(0, &buf[..n], None)?;
+            continue;
+        }
+
+        for result in decoder.decode(&buf[..n]) {
+            match result {
+                Ok(Packet::Instrumentation { port, payload }) if clock.is_some() => {
+                    pending.push((port, payload))
+                }
+                Ok(Packet::Instrumentation { port, payload }) => {
+                    output.data(port, &payload, None)?
+                }
+                Ok(packet) => {
+                    if let Some(clock) = &mut clock {
+                        if clock.feed(&packet) {
+                            for (port, payload) in pending.drain(..) {
+                                output.data(port, &payload, Some(clock))?;
+                            }
+                        }
+                    }
+                    if output.options.verbose {
+                        eprintln!("{:?}", packet);
+                    }
+                }
+                Err(e) => eprintln!("itm: {}", e),
+            }
+        }
+    }
+}
+
+// prints a record up to the log level, other lines as they are
+fn log(options: &Options, line: &str, clock: Option<&Clock>) {
+    let time = clock.map(stamp).unwrap_or_default();
+    match Record::parse(line) {
+        Some(record) if Some(record.level) <= options.log => {
+            println!("{}{}", time, record.render(options.color))
+        }
+        Some(_) => {}
+        None => println!("{}{}", time, line),
+    }
+}
+
+fn deferred(
+    options: &Options,
+    strings: &Strings,
+    record: Result<Vec<u8>, log::Error>,
+    clock: Option<&Clock>,
+) {
+    match record.and_then(|record| strings.line(&record)) {
+        Ok(line) => log(options, &line, clock),
+        Err(e) => eprintln!("itm: {}", e),
+    }
+}
+
+fn main() {
+    let options = match parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(options) {
+        eprintln!("itm: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,69 @@
+//! `srp`, response time analysis of RTFM tasks (see `src/srp.rs`)
+//!
+//! ``` text
+//! > srp FILE...
+//! ```
+//!
+//! Reads the tasks and locks of a system from each `FILE`, a TOML
+//! description if it ends in `.toml`, the report of `src/wcet.rs` (e.g., the
+//! ITM port 0 output of `examples/rtfm_wcet.rs`) otherwise. Later files add
+//! to or update the earlier ones, e.g., measured execution times on top of
+//! the periods and deadlines of a description:
+//!
+//! ``` text
+//! > srp system.toml wcet.txt
+//! ```
+//!
+//! Prints the response time of each task, and exits with 1 if a deadline
+//! is missed.
+
+use std::process;
+
+use host::srp::{self, System};
+
+const USAGE: &str = "usage: srp FILE...";
+
+fn read(path: &str) -> Result<System, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    if path.ends_with(".toml") {
+        System::from_toml(&text).map_err(|e| format!("{}: {}", path, e))
+    } else {
+        Ok(System::from_report(&text))
+    }
+}
+
+fn main() {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() || paths.iter().any(|p| p.starts_with('-')) {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let mut system = System::default();
+    for path in &paths {
+        match read(path) {
+            Ok(other) => system.merge(other),
+            Err(e) => {
+                eprintln!("srp: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+    if system.tasks.is_empty() {
+        eprintln!("srp: no tasks");
+        process::exit(1);
+    }
+
+    match system.analyse() {
+        Ok(responses) => {
+            print!("{}", srp::table(&responses));
+            if !responses.iter().all(|r| r.meets_deadline()) {
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("srp: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Core dumps, as streamed by `src/coredump.rs`
+//!
+//! A capture (the bytes of the serial port, or the payloads of ITM port
+//! `PORT`) is parsed into a `Dump`, which is written as an ELF core file for
+//! `gdb`: the registers in an `NT_PRSTATUS` note, as for an ARM Linux
+//! process, and a `PT_LOAD` segment per region of memory.
+//!
+//! ``` no_run
+//! use host::coredump::Dump;
+//!
+//! let dump = Dump::parse(&std::fs::read("/tmp/core.bin")?)?;
+//! std::fs::write("core", dump.to_elf())?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::convert::TryInto;
+use std::fmt;
+
+use crate::fault;
+use crate::frame::crc16_update;
+
+pub const MAGIC: &[u8; 4] = b"CORE";
+pub const VERSION: u8 = 1;
+/// The ITM stimulus port of the dump.
+pub const PORT: u8 = 4;
+
+/// The index of the stack pointer, link register, program counter and xPSR
+/// in `Dump::regs`.
+pub const SP: usize = 13;
+pub const LR: usize = 14;
+pub const PC: usize = 15;
+pub const XPSR: usize = 16;
+
+// the fixed part after the magic, version, regs, fault and region count
+const HEADER: usize = 1 + 17 * 4 + 5 * 4 + 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// No `MAGIC` in the capture.
+    NoMagic,
+    /// A version other than `VERSION`.
+    Version(u8),
+    /// The capture ends before the dump does.
+    Truncated,
+    /// The CRC does not match, bytes were lost or changed.
+    Crc,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoMagic => f.write_str("no core dump found"),
+            Error::Version(v) => write!(f, "unknown core dump version {}", v),
+            Error::Truncated => f.write_str("truncated core dump"),
+            Error::Crc => f.write_str("core dump CRC mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A region of memory, from `address`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub address: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dump {
+    /// r0-r12, sp, lr, pc and xpsr; r4-r11 are not known (0).
+    pub regs: [u32; 17],
+    pub fault: fault::Registers,
+    pub regions: Vec<Region>,
+}
+
+// a cursor over the dump
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let end = self.at.checked_add(n).ok_or(Error::Truncated)?;
+        let bytes = self.bytes.get(self.at..end).ok_or(Error::Truncated)?;
+        self.at = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+impl Dump {
+    /// Parses the first dump in `capture`, anything before its magic is
+    /// skipped.
+    pub fn parse(capture: &[u8]) -> Result<Self, Error> {
+        let start = capture
+            .windows(MAGIC.len())
+            .position(|w| w == MAGIC)
+            .ok_or(Error::NoMagic)?;
+        let bytes = &capture[start + MAGIC.len()..];
+        if bytes.len() < HEADER {
+            return Err(Error::Truncated);
+        }
+        let mut r = Reader { bytes, at: 0 };
+
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(Error::Version(version));
+        }
+        let mut regs = [0; 17];
+        for reg in regs.iter_mut() {
+            *reg = r.u32()?;
+        }
+        let fault = fault::Registers {
+            cfsr: r.u32()?,
+            hfsr: r.u32()?,
+            mmfar: r.u32()?,
+            bfar: r.u32()?,
+            afsr: r.u32()?,
+        };
+        let mut regions = vec![];
+        for _ in 0..r.u8()? {
+            let address = r.u32()?;
+            let length = r.u32()? as usize;
+            regions.push(Region {
+                address,
+                bytes: r.take(length)?.to_vec(),
+            });
+        }
+
+        let crc = crc16_update(0xFFFF, &bytes[..r.at]);
+        if u16::from_le_bytes(r.take(2)?.try_into().unwrap()) != crc {
+            return Err(Error::Crc);
+        }
+        Ok(Dump {
+            regs,
+            fault,
+            regions,
+        })
+    }
+
+    /// The word at `address`, if in a region.
+    pub fn word(&self, address: u32) -> Option<u32> {
+        self.regions.iter().find_map(|region| {
+            let at = address.checked_sub(region.address)? as usize;
+            let bytes = region.bytes.get(at..at.checked_add(4)?)?;
+            Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+        })
+    }
+
+    /// An ELF core file of the dump.
+    pub fn to_elf(&self) -> Vec<u8> {
+        let phnum = 1 + self.regions.len();
+        let note = note(&self.regs);
+        let mut offset = EHSIZE + phnum * PHENTSIZE;
+
+        let mut elf = vec![];
+        // e_ident: magic, 32-bit, little endian, version 1, System V ABI
+        elf.extend_from_slice(b"\x7fELF\x01\x01\x01\x00");
+        elf.extend_from_slice(&[0; 8]);
+        push16(&mut elf, ET_CORE);
+        push16(&mut elf, EM_ARM);
+        push32(&mut elf, 1); // e_version
+        push32(&mut elf, 0); // e_entry
+        push32(&mut elf, EHSIZE as u32); // e_phoff
+        push32(&mut elf, 0); // e_shoff
+        push32(&mut elf, EF_ARM_EABI_VER5);
+        push16(&mut elf, EHSIZE as u16);
+        push16(&mut elf, PHENTSIZE as u16);
+        push16(&mut elf, phnum as u16);
+        push16(&mut elf, 0); // e_shentsize
+        push16(&mut elf, 0); // e_shnum
+        push16(&mut elf, 0); // e_shstrndx
+
+        // p_type, p_offset, p_vaddr, p_paddr, p_filesz, p_memsz, p_flags, p_align
+        let mut header = |elf: &mut Vec<u8>, kind, address, size: usize, flags, align| {
+            for &field in &[kind, offset as u32, address, address, size as u32] {
+                push32(elf, field);
+            }
+            for &field in &[size as u32, flags, align] {
+                push32(elf, field);
+            }
+            offset += size;
+        };
+        header(&mut elf, PT_NOTE, 0, note.len(), 0, 4);
+        for region in &self.regions {
+            header(
+                &mut elf,
+                PT_LOAD,
+                region.address,
+                region.bytes.len(),
+                PF_R | PF_W,
+                1,
+            );
+        }
+
+        elf.extend_from_slice(&note);
+        for region in &self.regions {
+            elf.extend_from_slice(&region.bytes);
+        }
+        elf
+    }
+}
+
+impl fmt::Display for Dump {
+    /// The fault, the registers and the regions.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.fault)?;
+        for (i, reg) in self.regs.iter().enumerate() {
+            let name = match i {
+                SP => "sp".to_string(),
+                LR => "lr".to_string(),
+                PC => "pc".to_string(),
+                XPSR => "xpsr".to_string(),
+                i => format!("r{}", i),
+            };
+            let sep = if i % 4 == 3 || i == XPSR { "\n" } else { " " };
+            write!(f, "{}={:#010x}{}", name, reg, sep)?;
+        }
+        for region in &self.regions {
+            writeln!(
+                f,
+                "region {:#010x}..{:#010x} ({} bytes)",
+                region.address,
+                region.address as u64 + region.bytes.len() as u64,
+                region.bytes.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+const EHSIZE: usize = 52;
+const PHENTSIZE: usize = 32;
+const ET_CORE: u16 = 4;
+const EM_ARM: u16 = 40;
+const EF_ARM_EABI_VER5: u32 = 0x0500_0000;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+// `struct elf_prstatus` of 32-bit ARM Linux
+const PRSTATUS_SIZE: usize = 148;
+const PR_CURSIG: usize = 12;
+const PR_PID: usize = 24;
+const PR_REG: usize = 72;
+// SIGSEGV, as gdb reports it
+const SIGSEGV: u16 = 11;
+
+fn push16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+// the `NT_PRSTATUS` note, `pr_reg` being r0-r15, cpsr (the xpsr) and orig_r0
+fn note(regs: &[u32; 17]) -> Vec<u8> {
+    let mut desc = vec![0; PRSTATUS_SIZE];
+    desc[PR_CURSIG..PR_CURSIG + 2].copy_from_slice(&SIGSEGV.to_le_bytes());
+    desc[PR_PID..PR_PID + 4].copy_from_slice(&1u32.to_le_bytes());
+    for (i, reg) in regs.iter().enumerate() {
+        let at = PR_REG + i * 4;
+        desc[at..at + 4].copy_from_slice(&reg.to_le_bytes());
+    }
+
+    let mut note = vec![];
+    push32(&mut note, 5); // namesz, "CORE\0"
+    push32(&mut note, desc.len() as u32);
+    push32(&mut note, NT_PRSTATUS);
+    note.extend_from_slice(b"CORE\0\0\0\0");
+    note.extend_from_slice(&desc);
+    note
+}
@@ -0,0 +1,419 @@
+//! A minimal DWARF line table reader
+//!
+//! Just enough of DWARF (versions 2 to 5, 32-bit) to map an address to its
+//! source file and line: the line number programs of `.debug_line` are run
+//! into a table of rows, as `addr2line` does.
+//!
+//! ``` no_run
+//! use host::dwarf::Lines;
+//! use host::elf::Elf;
+//!
+//! let elf = Elf::parse(std::fs::read("target/thumbv7em-none-eabihf/debug/examples/crash")?)?;
+//! let lines = Lines::parse(&elf)?;
+//! if let Some(location) = lines.find(0x0800_0412) {
+//!     println!("{}:{}", location.file, location.line);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! see DWARF 4 and 5, section 6.2 (Line Number Information)
+
+use std::convert::TryInto;
+use std::fmt;
+
+use crate::elf::Elf;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// A line number program extends past the end of its section.
+    Truncated,
+    /// An unsupported version, or the 64-bit format.
+    Unsupported(u16),
+    /// An attribute form not expected in a line table header.
+    Form(u64),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated => f.write_str("truncated line table"),
+            Error::Unsupported(v) => write!(f, "unsupported line table version {}", v),
+            Error::Form(form) => write!(f, "unexpected form {:#x} in line table", form),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Where an address comes from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    /// The path as compiled, relative to the compilation directory if not
+    /// absolute.
+    pub file: String,
+    pub line: u32,
+}
+
+// a row of the table, the address and `(file, line)`
+#[derive(Clone, Copy, Debug)]
+struct Row {
+    address: u32,
+    file: usize,
+    line: u32,
+}
+
+// the rows from a `DW_LNE_set_address` to a `DW_LNE_end_sequence`
+#[derive(Debug)]
+struct Sequence {
+    start: u32,
+    end: u32,
+    rows: Vec<Row>,
+}
+
+/// The line tables of an ELF.
+#[derive(Debug, Default)]
+pub struct Lines {
+    files: Vec<String>,
+    sequences: Vec<Sequence>,
+}
+
+// a cursor over a section
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let end = self.at.checked_add(n).ok_or(Error::Truncated)?;
+        let bytes = self.bytes.get(self.at..end).ok_or(Error::Truncated)?;
+        self.at = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn uleb(&mut self) -> Result<u64, Error> {
+        let (mut value, mut shift) = (0u64, 0);
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= ((byte & 0x7F) as u64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> Result<i64, Error> {
+        let (mut value, mut shift) = (0i64, 0);
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= ((byte & 0x7F) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    // a NUL terminated string
+    fn str(&mut self) -> Result<String, Error> {
+        let tail = self.bytes.get(self.at..).ok_or(Error::Truncated)?;
+        let end = tail.iter().position(|&b| b == 0).ok_or(Error::Truncated)?;
+        self.at += end + 1;
+        Ok(String::from_utf8_lossy(&tail[..end]).into_owned())
+    }
+}
+
+// a string at `offset` of a string section
+fn str_at(section: &[u8], offset: u64) -> Result<String, Error> {
+    Reader {
+        bytes: section,
+        at: offset as usize,
+    }
+    .str()
+}
+
+// forms of the version 5 directory and file entries
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_DATA1: u64 = 0x0b;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_DATA16: u64 = 0x1e;
+const DW_FORM_LINE_STRP: u64 = 0x1f;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_UDATA: u64 = 0x0f;
+// and their content
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+// standard opcodes
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+// extended opcodes
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+const DW_LNE_DEFINE_FILE: u8 = 3;
+
+// the strings of an ELF, for `DW_FORM_strp` and `DW_FORM_line_strp`
+struct Strings<'a> {
+    str: &'a [u8],
+    line_str: &'a [u8],
+}
+
+// a version 5 entry (directory or file), its path and directory index
+fn entry(r: &mut Reader, format: &[(u64, u64)], s: &Strings) -> Result<(String, u64), Error> {
+    let (mut path, mut dir) = (String::new(), 0);
+    for &(content, form) in format {
+        let (string, value) = match form {
+            DW_FORM_STRING => (Some(r.str()?), 0),
+            DW_FORM_LINE_STRP => (Some(str_at(s.line_str, r.u32()? as u64)?), 0),
+            DW_FORM_STRP => (Some(str_at(s.str, r.u32()? as u64)?), 0),
+            DW_FORM_UDATA => (None, r.uleb()?),
+            DW_FORM_DATA1 => (None, r.u8()? as u64),
+            DW_FORM_DATA2 => (None, r.u16()? as u64),
+            DW_FORM_DATA4 => (None, r.u32()? as u64),
+            DW_FORM_DATA8 => (None, u64::from_le_bytes(r.take(8)?.try_into().unwrap())),
+            DW_FORM_DATA16 => (None, r.take(16).map(|_| 0)?),
+            DW_FORM_BLOCK => {
+                let n = r.uleb()? as usize;
+                (None, r.take(n).map(|_| 0)?)
+            }
+            form => return Err(Error::Form(form)),
+        };
+        match content {
+            DW_LNCT_PATH => path = string.unwrap_or_default(),
+            DW_LNCT_DIRECTORY_INDEX => dir = value,
+            _ => {}
+        }
+    }
+    Ok((path, dir))
+}
+
+// the path of a file in directory `dir`, relative ones kept relative to the
+// compilation directory (directory 0)
+fn join(dirs: &[String], dir: u64, file: String) -> String {
+    match dirs.get(dir as usize) {
+        Some(dir) if !dir.is_empty() && !file.starts_with('/') => {
+            format!("{}/{}", dir.trim_end_matches('/'), file)
+        }
+        _ => file,
+    }
+}
+
+impl Lines {
+    /// The line tables of `elf`, empty without `.debug_line`.
+    pub fn parse(elf: &Elf) -> Result<Self, Error> {
+        let section = |name| elf.section(name).map(|s| elf.data(s)).unwrap_or(&[]);
+        let strings = Strings {
+            str: section(".debug_str"),
+            line_str: section(".debug_line_str"),
+        };
+        let mut lines = Lines::default();
+        let mut r = Reader {
+            bytes: section(".debug_line"),
+            at: 0,
+        };
+        while r.at < r.bytes.len() {
+            let length = r.u32()?;
+            if length >= 0xFFFF_FFF0 {
+                return Err(Error::Unsupported(0));
+            }
+            let end = r.at + length as usize;
+            let unit = Reader {
+                bytes: r.bytes.get(..end).ok_or(Error::Truncated)?,
+                at: r.at,
+            };
+            lines.program(unit, &strings)?;
+            r.at = end;
+        }
+        for sequence in &mut lines.sequences {
+            sequence.rows.sort_by_key(|row| row.address);
+        }
+        lines.sequences.sort_by_key(|s| s.start);
+        Ok(lines)
+    }
+
+    // runs the line number program of a unit
+    fn program(&mut self, mut r: Reader, strings: &Strings) -> Result<(), Error> {
+        let version = r.u16()?;
+        if !(2..=5).contains(&version) {
+            return Err(Error::Unsupported(version));
+        }
+        if version >= 5 {
+            // address_size, segment_selector_size
+            r.take(2)?;
+        }
+        let header_length = r.u32()? as usize;
+        let program = r.at + header_length;
+        let min_length = r.u8()? as u32;
+        if version >= 4 {
+            // maximum_operations_per_instruction, 1 but for VLIW
+            r.u8()?;
+        }
+        r.u8()?; // default_is_stmt
+        let line_base = r.u8()? as i8 as i64;
+        let line_range = r.u8()?.max(1);
+        let opcode_base = r.u8()?;
+        let lengths = r.take(opcode_base.saturating_sub(1) as usize)?.to_vec();
+
+        // the files of the unit, as indices into `self.files`
+        let mut files = vec![];
+        if version >= 5 {
+            // directory 0 is the compilation directory, file 0 the first
+            let mut dirs = vec![];
+            for i in 0..2 {
+                let mut format = vec![];
+                for _ in 0..r.u8()? {
+                    format.push((r.uleb()?, r.uleb()?));
+                }
+                for _ in 0..r.uleb()? {
+                    let (path, dir) = entry(&mut r, &format, strings)?;
+                    if i == 0 {
+                        dirs.push(if dirs.is_empty() { String::new() } else { path });
+                    } else {
+                        files.push(self.file(join(&dirs, dir, path)));
+                    }
+                }
+            }
+        } else {
+            // directory 0 is the compilation directory, file 1 the first
+            let mut dirs = vec![String::new()];
+            loop {
+                let dir = r.str()?;
+                if dir.is_empty() {
+                    break;
+                }
+                dirs.push(dir);
+            }
+            files.push(self.file(String::new()));
+            loop {
+                let path = r.str()?;
+                if path.is_empty() {
+                    break;
+                }
+                let dir = r.uleb()?;
+                r.uleb()?; // modification time
+                r.uleb()?; // length
+                files.push(self.file(join(&dirs, dir, path)));
+            }
+        }
+        r.at = program;
+
+        let initial = Row {
+            address: 0,
+            file: 1,
+            line: 1,
+        };
+        let (mut row, mut rows) = (initial, vec![]);
+        let file = |files: &Vec<usize>, i: usize| files.get(i).copied().unwrap_or(usize::MAX);
+        while r.at < r.bytes.len() {
+            let opcode = r.u8()?;
+            if opcode >= opcode_base {
+                let adjusted = (opcode - opcode_base) as u32;
+                row.address += adjusted / line_range as u32 * min_length;
+                row.line =
+                    (row.line as i64 + line_base + (adjusted % line_range as u32) as i64) as u32;
+                rows.push(Row {
+                    file: file(&files, row.file),
+                    ..row
+                });
+                continue;
+            }
+            match opcode {
+                0 => {
+                    let n = r.uleb()? as usize;
+                    let mut op = Reader {
+                        bytes: r.take(n)?,
+                        at: 0,
+                    };
+                    match op.u8()? {
+                        DW_LNE_END_SEQUENCE => {
+                            if let Some(first) = rows.first() {
+                                self.sequences.push(Sequence {
+                                    start: first.address,
+                                    end: row.address,
+                                    rows: std::mem::take(&mut rows),
+                                });
+                            }
+                            row = initial;
+                        }
+                        DW_LNE_SET_ADDRESS => row.address = op.u32()?,
+                        DW_LNE_DEFINE_FILE => {
+                            let path = op.str()?;
+                            files.push(self.file(path));
+                        }
+                        _ => {}
+                    }
+                }
+                DW_LNS_COPY => rows.push(Row {
+                    file: file(&files, row.file),
+                    ..row
+                }),
+                DW_LNS_ADVANCE_PC => row.address += r.uleb()? as u32 * min_length,
+                DW_LNS_ADVANCE_LINE => row.line = (row.line as i64 + r.sleb()?) as u32,
+                DW_LNS_SET_FILE => row.file = r.uleb()? as usize,
+                DW_LNS_CONST_ADD_PC => {
+                    row.address += (255 - opcode_base as u32) / line_range as u32 * min_length
+                }
+                DW_LNS_FIXED_ADVANCE_PC => row.address += r.u16()? as u32,
+                // operands skipped, as many as the header says
+                _ => {
+                    for _ in 0..lengths.get(opcode as usize - 1).copied().unwrap_or(0) {
+                        r.uleb()?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // the index of `path` in `files`, added if new
+    fn file(&mut self, path: String) -> usize {
+        match self.files.iter().position(|f| *f == path) {
+            Some(i) => i,
+            None => {
+                self.files.push(path);
+                self.files.len() - 1
+            }
+        }
+    }
+
+    /// The location of the instruction at `address`.
+    pub fn find(&self, address: u32) -> Option<Location> {
+        let sequence = self
+            .sequences
+            .iter()
+            .find(|s| s.start <= address && address < s.end)?;
+        let i = sequence.rows.partition_point(|row| row.address <= address);
+        let row = sequence.rows.get(i.checked_sub(1)?)?;
+        Some(Location {
+            file: self.files.get(row.file)?.clone(),
+            line: row.line,
+        })
+    }
+}
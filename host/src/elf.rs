@@ -0,0 +1,177 @@
+//! A minimal ELF reader
+//!
+//! Just enough of the ELF format (32-bit, little endian, as linked for the
+//! Cortex-M) to look up the sections of a firmware image, e.g., the `.log`
+//! section holding the interned strings of deferred logging.
+//!
+//! ``` no_run
+//! use host::elf::Elf;
+//!
+//! let elf = Elf::parse(std::fs::read("target/thumbv7em-none-eabihf/debug/examples/log")?)?;
+//! for section in elf.sections() {
+//!     println!("{:<20} {:#010x} {}", section.name, section.addr, section.size);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::convert::TryInto;
+use std::fmt;
+
+/// Section without data in the file (`.bss`).
+pub const SHT_NOBITS: u32 = 8;
+/// Symbol of a function.
+pub const STT_FUNC: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// No ELF magic.
+    NotElf,
+    /// Not a 32-bit little endian ELF.
+    Unsupported,
+    /// A header or section extends past the end of the file.
+    Truncated,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::NotElf => "not an ELF file",
+            Error::Unsupported => "not a 32-bit little endian ELF file",
+            Error::Truncated => "truncated ELF file",
+        })
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub name: String,
+    /// `sh_type`, e.g., `SHT_NOBITS`.
+    pub kind: u32,
+    pub flags: u32,
+    pub addr: u32,
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    /// The address, with the Thumb bit of a function.
+    pub value: u32,
+    pub size: u32,
+    /// `STT_*`, the low 4 bits of `st_info`, e.g., `STT_FUNC`.
+    pub kind: u8,
+}
+
+#[derive(Debug)]
+pub struct Elf {
+    bytes: Vec<u8>,
+    sections: Vec<Section>,
+}
+
+// little endian fields, `Truncated` past the end
+fn u16_at(bytes: &[u8], at: usize) -> Result<u16, Error> {
+    let field = bytes.get(at..at + 2).ok_or(Error::Truncated)?;
+    Ok(u16::from_le_bytes(field.try_into().unwrap()))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32, Error> {
+    let field = bytes.get(at..at + 4).ok_or(Error::Truncated)?;
+    Ok(u32::from_le_bytes(field.try_into().unwrap()))
+}
+
+// a NUL terminated string
+fn str_at(bytes: &[u8], at: usize) -> Result<String, Error> {
+    let tail = bytes.get(at..).ok_or(Error::Truncated)?;
+    let end = tail.iter().position(|&b| b == 0).ok_or(Error::Truncated)?;
+    Ok(String::from_utf8_lossy(&tail[..end]).into_owned())
+}
+
+impl Elf {
+    pub fn parse(bytes: Vec<u8>) -> Result<Self, Error> {
+        if bytes.get(..4) != Some(b"\x7fELF") {
+            return Err(Error::NotElf);
+        }
+        // EI_CLASS 32-bit, EI_DATA little endian
+        if bytes.get(4..6) != Some(&[1, 1]) {
+            return Err(Error::Unsupported);
+        }
+
+        let shoff = u32_at(&bytes, 32)? as usize;
+        let shentsize = u16_at(&bytes, 46)? as usize;
+        let shnum = u16_at(&bytes, 48)? as usize;
+        let shstrndx = u16_at(&bytes, 50)? as usize;
+
+        let mut headers = vec![];
+        for i in 0..shnum {
+            let at = shoff + i * shentsize;
+            headers.push((
+                u32_at(&bytes, at)?,
+                Section {
+                    name: String::new(),
+                    kind: u32_at(&bytes, at + 4)?,
+                    flags: u32_at(&bytes, at + 8)?,
+                    addr: u32_at(&bytes, at + 12)?,
+                    offset: u32_at(&bytes, at + 16)?,
+                    size: u32_at(&bytes, at + 20)?,
+                },
+            ));
+        }
+
+        let names = match headers.get(shstrndx) {
+            Some((_, names)) => names.offset as usize,
+            None if shnum == 0 => 0,
+            None => return Err(Error::Truncated),
+        };
+        let mut sections = vec![];
+        for (name, mut section) in headers {
+            section.name = str_at(&bytes, names + name as usize)?;
+            if section.kind != SHT_NOBITS
+                && bytes.len() < section.offset as usize + section.size as usize
+            {
+                return Err(Error::Truncated);
+            }
+            sections.push(section);
+        }
+
+        Ok(Elf { bytes, sections })
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// The contents of `section`, empty for `SHT_NOBITS`.
+    pub fn data(&self, section: &Section) -> &[u8] {
+        if section.kind == SHT_NOBITS {
+            return &[];
+        }
+        let start = section.offset as usize;
+        &self.bytes[start..start + section.size as usize]
+    }
+
+    /// The symbols of `.symtab` (named from `.strtab`), none if stripped.
+    pub fn symbols(&self) -> Result<Vec<Symbol>, Error> {
+        let (symtab, strtab) = match (self.section(".symtab"), self.section(".strtab")) {
+            (Some(symtab), Some(strtab)) => (self.data(symtab), self.data(strtab)),
+            _ => return Ok(vec![]),
+        };
+        // `Elf32_Sym`, 16 bytes
+        let mut symbols = vec![];
+        for entry in symtab.chunks_exact(16) {
+            symbols.push(Symbol {
+                name: str_at(strtab, u32_at(entry, 0)? as usize)?,
+                value: u32_at(entry, 4)?,
+                size: u32_at(entry, 8)?,
+                kind: entry[12] & 0xF,
+            });
+        }
+        Ok(symbols)
+    }
+}
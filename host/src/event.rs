@@ -0,0 +1,345 @@
+//! Task trace events, as written by `src/event.rs`
+//!
+//! The events of stimulus port 3 are decoded into a `Trace` of task
+//! enter/exit and resource lock/unlock events, timed by the (unwrapped)
+//! cycle counter, which is written as a VCD waveform (`vcd`) or as Chrome
+//! `trace_event` JSON (`chrome`, for `chrome://tracing` or Perfetto):
+//!
+//! ``` no_run
+//! use host::event::{Trace, PORT};
+//! use host::itm::{Decoder, Packet};
+//!
+//! let mut trace = Trace::new();
+//! for packet in Decoder::new().decode(&std::fs::read("/tmp/itm.fifo")?) {
+//!     if let Ok(Packet::Instrumentation { port: PORT, payload }) = packet {
+//!         trace.push(&payload)?;
+//!     }
+//! }
+//! print!("{}", trace.vcd(16_000_000));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt::{self, Write};
+
+/// The stimulus port of the events.
+pub const PORT: u8 = 3;
+
+// event kinds, the high 4 bits of the header
+const TASK: u16 = 0;
+const RESOURCE: u16 = 1;
+const ENTER: u16 = 2;
+const EXIT: u16 = 3;
+const LOCK: u16 = 4;
+const UNLOCK: u16 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// A write of unexpected size, e.g., a cycle count without an event.
+    Unexpected(usize),
+    /// An event of unknown kind.
+    Kind(u16),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unexpected(n) => write!(f, "unexpected {} byte event write", n),
+            Error::Kind(k) => write!(f, "unknown event kind {}", k),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Enter,
+    Exit,
+    Lock,
+    Unlock,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    /// Cycles since the counter was enabled.
+    pub time: u64,
+    pub kind: Kind,
+    /// The task (`Enter`, `Exit`) or resource (`Lock`, `Unlock`).
+    pub id: u16,
+}
+
+// where the decoder is within an event
+#[derive(Debug)]
+enum State {
+    Header,
+    Cycles(u16),
+    Name(u16, u16, Vec<u8>),
+}
+
+/// The events of a run, and the names of its tasks and resources.
+#[derive(Debug)]
+pub struct Trace {
+    pub events: Vec<Event>,
+    tasks: BTreeMap<u16, String>,
+    resources: BTreeMap<u16, String>,
+    state: State,
+    // the unwrapped time of the previous event
+    time: u64,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Trace {
+            events: vec![],
+            tasks: BTreeMap::new(),
+            resources: BTreeMap::new(),
+            state: State::Header,
+            time: 0,
+        }
+    }
+
+    /// Adds the payload of a write to the event port.
+    pub fn push(&mut self, payload: &[u8]) -> Result<(), Error> {
+        let state = std::mem::replace(&mut self.state, State::Header);
+        match (state, payload.len()) {
+            (State::Header, 2) => {
+                let header = u16::from_le_bytes(payload.try_into().unwrap());
+                if header >> 12 > UNLOCK {
+                    return Err(Error::Kind(header >> 12));
+                }
+                self.state = State::Cycles(header);
+            }
+            (State::Cycles(header), 4) => {
+                let cycles = u32::from_le_bytes(payload.try_into().unwrap());
+                // the counter wraps every 2^32 cycles
+                let mut time = self.time & !0xFFFF_FFFF | cycles as u64;
+                if time < self.time {
+                    time += 1 << 32;
+                }
+                self.time = time;
+
+                let (kind, id) = (header >> 12, header & 0xFFF);
+                let kind = match kind {
+                    TASK | RESOURCE => {
+                        self.state = State::Name(kind, id, vec![]);
+                        return Ok(());
+                    }
+                    ENTER => Kind::Enter,
+                    EXIT => Kind::Exit,
+                    LOCK => Kind::Lock,
+                    _ => Kind::Unlock,
+                };
+                self.events.push(Event { time, kind, id });
+            }
+            (State::Name(kind, id, mut name), 1) => {
+                if payload[0] != 0 {
+                    name.push(payload[0]);
+                    self.state = State::Name(kind, id, name);
+                    return Ok(());
+                }
+                let name = String::from_utf8_lossy(&name).into_owned();
+                match kind {
+                    TASK => self.tasks.insert(id, name),
+                    _ => self.resources.insert(id, name),
+                };
+            }
+            (_, n) => return Err(Error::Unexpected(n)),
+        }
+        Ok(())
+    }
+
+    /// The name of task `id`, `task<id>` if not named.
+    pub fn task(&self, id: u16) -> String {
+        self.tasks
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| format!("task{}", id))
+    }
+
+    /// The name of resource `id`, `resource<id>` if not named.
+    pub fn resource(&self, id: u16) -> String {
+        self.resources
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| format!("resource{}", id))
+    }
+
+    // the tasks and resources of the events, in order of id
+    fn ids(&self, kinds: [Kind; 2]) -> Vec<u16> {
+        let mut ids: Vec<u16> = self
+            .events
+            .iter()
+            .filter(|e| kinds.contains(&e.kind))
+            .map(|e| e.id)
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// A VCD waveform at a core clock of `hz`: a wire per task (high from
+    /// enter to exit), a wire per resource (high while locked), and `running`,
+    /// the id of the task executing.
+    pub fn vcd(&self, hz: u32) -> String {
+        let tasks = self.ids([Kind::Enter, Kind::Exit]);
+        let resources = self.ids([Kind::Lock, Kind::Unlock]);
+        // VCD identifiers, printable characters from `!`
+        let code = |i: usize| {
+            let mut code = String::new();
+            let mut i = i;
+            loop {
+                code.push((b'!' + (i % 94) as u8) as char);
+                i /= 94;
+                if i == 0 {
+                    return code;
+                }
+            }
+        };
+        let task_code = |id| code(1 + tasks.iter().position(|&t| t == id).unwrap());
+        let resource_code =
+            |id| code(1 + tasks.len() + resources.iter().position(|&r| r == id).unwrap());
+        let ns = |time: u64| (time as u128 * 1_000_000_000 / hz.max(1) as u128) as u64;
+
+        let mut out = String::new();
+        out.push_str("$timescale 1 ns $end\n$scope module rtfm $end\n");
+        writeln!(out, "$var wire 12 {} running $end", code(0)).unwrap();
+        for &id in &tasks {
+            writeln!(out, "$var wire 1 {} {} $end", task_code(id), self.task(id)).unwrap();
+        }
+        out.push_str("$upscope $end\n$scope module resources $end\n");
+        for &id in &resources {
+            writeln!(
+                out,
+                "$var wire 1 {} {} $end",
+                resource_code(id),
+                self.resource(id)
+            )
+            .unwrap();
+        }
+        out.push_str("$upscope $end\n$enddefinitions $end\n");
+
+        // all low, nothing running
+        out.push_str("#0\n$dumpvars\nbz ");
+        out.push_str(&code(0));
+        out.push('\n');
+        for i in 1..=tasks.len() + resources.len() {
+            writeln!(out, "0{}", code(i)).unwrap();
+        }
+        out.push_str("$end\n");
+
+        let mut stack: Vec<u16> = vec![];
+        let mut last = None;
+        for event in &self.events {
+            let t = ns(event.time);
+            if last != Some(t) {
+                writeln!(out, "#{}", t).unwrap();
+                last = Some(t);
+            }
+            let running = |stack: &Vec<u16>| match stack.last() {
+                Some(id) => format!("b{:b} {}", id, code(0)),
+                None => format!("bz {}", code(0)),
+            };
+            match event.kind {
+                Kind::Enter => {
+                    stack.push(event.id);
+                    writeln!(out, "1{}", task_code(event.id)).unwrap();
+                    writeln!(out, "{}", running(&stack)).unwrap();
+                }
+                Kind::Exit => {
+                    if let Some(i) = stack.iter().rposition(|&id| id == event.id) {
+                        stack.remove(i);
+                    }
+                    writeln!(out, "0{}", task_code(event.id)).unwrap();
+                    writeln!(out, "{}", running(&stack)).unwrap();
+                }
+                Kind::Lock => writeln!(out, "1{}", resource_code(event.id)).unwrap(),
+                Kind::Unlock => writeln!(out, "0{}", resource_code(event.id)).unwrap(),
+            }
+        }
+        out
+    }
+
+    /// Chrome `trace_event` JSON at a core clock of `hz`: a thread per task,
+    /// its runs and locks as nested duration events.
+    pub fn chrome(&self, hz: u32) -> String {
+        let us = |time: u64| time as f64 * 1e6 / hz.max(1) as f64;
+        let mut out = String::from("{\"traceEvents\":[\n");
+        let mut first = true;
+        let mut line = |out: &mut String, s: String| {
+            if !first {
+                out.push_str(",\n");
+            }
+            first = false;
+            out.push_str(&s);
+        };
+
+        for id in self.ids([Kind::Enter, Kind::Exit]) {
+            line(
+                &mut out,
+                format!(
+                    "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\"args\":{{\"name\":{}}}}}",
+                    id,
+                    json(&self.task(id))
+                ),
+            );
+        }
+
+        // locks are shown on the task that runs when they are taken
+        let mut stack: Vec<u16> = vec![];
+        for event in &self.events {
+            let (ph, name, cat, tid) = match event.kind {
+                Kind::Enter => {
+                    stack.push(event.id);
+                    ("B", self.task(event.id), "task", event.id)
+                }
+                Kind::Exit => {
+                    if let Some(i) = stack.iter().rposition(|&id| id == event.id) {
+                        stack.remove(i);
+                    }
+                    ("E", self.task(event.id), "task", event.id)
+                }
+                Kind::Lock | Kind::Unlock => {
+                    let ph = if event.kind == Kind::Lock { "B" } else { "E" };
+                    let tid = stack.last().copied().unwrap_or(0);
+                    (ph, self.resource(event.id), "lock", tid)
+                }
+            };
+            line(
+                &mut out,
+                format!(
+                    "{{\"name\":{},\"cat\":\"{}\",\"ph\":\"{}\",\"ts\":{:.3},\"pid\":0,\"tid\":{}}}",
+                    json(&name),
+                    cat,
+                    ph,
+                    us(event.time),
+                    tid
+                ),
+            );
+        }
+        out.push_str("\n]}\n");
+        out
+    }
+}
+
+// a JSON string
+fn json(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
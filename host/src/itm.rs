@@ -0,0 +1,442 @@
+//! ITM/SWO packet decoder
+//!
+//! Decodes the byte stream written by `openocd` to `/tmp/itm.fifo` (see
+//! `monitor tpiu config internal` in `openocd.gdb`), following the ARMv7-M
+//! Architecture Reference Manual, appendix D4 (Debug ITM and DWT Packet
+//! Protocol).
+//!
+//! Every packet starts with a header byte:
+//!
+//! ``` text
+//! 0000_0000 ... 1000_0000     synchronization (at least 47 zeros, then a one)
+//! 0111_0000                   overflow
+//! CDDD_0000                   local timestamp (C continues, DDD the data or TC)
+//! 1001_0100, 1011_0100        global timestamp 1 and 2
+//! CPPP_1S00                   extension (e.g., stimulus port page)
+//! AAAA_A0SS                   instrumentation, port A, SS = 1, 2 or 4 bytes
+//! AAAA_A1SS                   hardware source (DWT), discriminator A
+//! ```
+//!
+//! ``` no_run
+//! use host::itm::{Decoder, Packet};
+//!
+//! let mut decoder = Decoder::new();
+//! for byte in std::fs::read("/tmp/itm.fifo")? {
+//!     if let Some(Ok(Packet::Instrumentation { port: 0, payload })) = decoder.feed(byte) {
+//!         print!("{}", String::from_utf8_lossy(&payload));
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+/// How a local timestamp relates to the packet it follows (`TC`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimestampQuality {
+    /// Synchronous to the corresponding data.
+    Sync,
+    /// The timestamp was delayed.
+    TimestampDelayed,
+    /// The data packet was delayed.
+    PacketDelayed,
+    /// Both were delayed.
+    BothDelayed,
+}
+
+/// What an exception did (`FN` of the exception trace packet).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExceptionAction {
+    Entered,
+    Exited,
+    Returned,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Packet {
+    Sync,
+    /// Packets were lost, the ITM or DWT FIFO was full.
+    Overflow,
+    /// Data written to a stimulus port (1, 2 or 4 bytes).
+    Instrumentation {
+        port: u8,
+        payload: Vec<u8>,
+    },
+    /// Cycles since the previous local timestamp.
+    LocalTimestamp {
+        delta: u32,
+        quality: TimestampQuality,
+    },
+    /// Bits `[25:0]` of the global timestamp, only the low `bits` bits
+    /// changed. `wrap` and `clock_change` are only set by a full packet.
+    GlobalTimestamp1 {
+        value: u32,
+        bits: u8,
+        wrap: bool,
+        clock_change: bool,
+    },
+    /// The high bits (`[47:26]` or `[63:26]`) of the global timestamp,
+    /// shifted down 26 bits.
+    GlobalTimestamp2 {
+        value: u64,
+    },
+    /// E.g., the page (`value`) of the stimulus ports that follow (`sh` clear).
+    Extension {
+        sh: bool,
+        value: u32,
+    },
+    /// A DWT counter wrapped (bit mask: `Cyc` 5, `Fold` 4, `LSU` 3, `Sleep` 2,
+    /// `Exc` 1, `CPI` 0).
+    EventCounter {
+        wrapped: u8,
+    },
+    ExceptionTrace {
+        number: u16,
+        action: ExceptionAction,
+    },
+    /// Periodic PC sample, `None` if the core was sleeping.
+    PcSample {
+        pc: Option<u32>,
+    },
+    /// PC of an access matching DWT comparator `comparator`.
+    DataTracePc {
+        comparator: u8,
+        pc: u32,
+    },
+    /// Address (low 16 bits) of an access matching `comparator`.
+    DataTraceAddress {
+        comparator: u8,
+        address: u16,
+    },
+    /// Value read or written by an access matching `comparator`.
+    DataTraceValue {
+        comparator: u8,
+        write: bool,
+        value: u32,
+        size: u8,
+    },
+    /// A hardware source packet with an unknown discriminator.
+    Hardware {
+        discriminator: u8,
+        payload: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// A reserved header byte.
+    Reserved(u8),
+    /// A packet known by its header, but not its payload.
+    Malformed(u8),
+    /// Zeros not ended by `0x80`, or too few to be a synchronization packet.
+    BadSync,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Reserved(h) => write!(f, "reserved header {:#04x}", h),
+            Error::Malformed(h) => write!(f, "malformed packet, header {:#04x}", h),
+            Error::BadSync => f.write_str("bad synchronization packet"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Header,
+    Zeros(u8),
+    // a fixed size payload (source packets)
+    Fixed { header: u8, size: usize },
+    // bytes with a continuation bit (timestamps, extension), at most `max`
+    Continued { header: u8, max: usize },
+}
+
+/// A streaming decoder, fed one byte at a time.
+#[derive(Debug)]
+pub struct Decoder {
+    state: State,
+    payload: Vec<u8>,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder {
+            state: State::Header,
+            payload: Vec::with_capacity(8),
+        }
+    }
+
+    /// Adds a byte, returning the packet it completes (if any).
+    pub fn feed(&mut self, byte: u8) -> Option<Result<Packet, Error>> {
+        match self.state {
+            State::Header => self.header(byte),
+            State::Zeros(n) => match byte {
+                0 => {
+                    self.state = State::Zeros(n.saturating_add(1));
+                    None
+                }
+                // 47 zero bits, the 0x80 byte holds the last 7
+                0x80 if n >= 5 => self.done(Ok(Packet::Sync)),
+                _ => self.done(Err(Error::BadSync)),
+            },
+            State::Fixed { header, size } => {
+                self.payload.push(byte);
+                if self.payload.len() < size {
+                    None
+                } else {
+                    let packet = source(header, &self.payload);
+                    self.done(packet)
+                }
+            }
+            State::Continued { header, max } => {
+                self.payload.push(byte);
+                if byte & 0x80 != 0 && self.payload.len() < max {
+                    None
+                } else if byte & 0x80 != 0 {
+                    self.done(Err(Error::Malformed(header)))
+                } else {
+                    let packet = continued(header, &self.payload);
+                    self.done(packet)
+                }
+            }
+        }
+    }
+
+    /// Decodes all of `bytes`, packets left incomplete are kept for the next
+    /// call.
+    pub fn decode(&mut self, bytes: &[u8]) -> Vec<Result<Packet, Error>> {
+        bytes.iter().filter_map(|&b| self.feed(b)).collect()
+    }
+
+    fn done(&mut self, packet: Result<Packet, Error>) -> Option<Result<Packet, Error>> {
+        self.state = State::Header;
+        self.payload.clear();
+        Some(packet)
+    }
+
+    fn header(&mut self, header: u8) -> Option<Result<Packet, Error>> {
+        self.payload.clear();
+        match header {
+            0x00 => {
+                self.state = State::Zeros(1);
+                None
+            }
+            0x70 => Some(Ok(Packet::Overflow)),
+            // local timestamp, format 2 (a single byte, TS = 1..=6)
+            h if h & 0x8F == 0x00 => Some(Ok(Packet::LocalTimestamp {
+                delta: (h >> 4) as u32,
+                quality: TimestampQuality::Sync,
+            })),
+            // local timestamp, format 1
+            h if h & 0xCF == 0xC0 => {
+                self.state = State::Continued { header, max: 4 };
+                None
+            }
+            0x94 => {
+                self.state = State::Continued { header, max: 4 };
+                None
+            }
+            0xB4 => {
+                self.state = State::Continued { header, max: 6 };
+                None
+            }
+            // extension
+            h if h & 0x0B == 0x08 => {
+                if h & 0x80 == 0 {
+                    Some(Ok(Packet::Extension {
+                        sh: h & 0x04 != 0,
+                        value: ((h >> 4) & 0x07) as u32,
+                    }))
+                } else {
+                    self.state = State::Continued { header, max: 4 };
+                    None
+                }
+            }
+            h if h & 0x03 != 0 => {
+                let size = match h & 0x03 {
+                    1 => 1,
+                    2 => 2,
+                    _ => 4,
+                };
+                self.state = State::Fixed { header, size };
+                None
+            }
+            h => Some(Err(Error::Reserved(h))),
+        }
+    }
+}
+
+// the payload of a source packet, little endian
+fn value(payload: &[u8]) -> u32 {
+    payload
+        .iter()
+        .rev()
+        .fold(0, |acc, &byte| (acc << 8) | byte as u32)
+}
+
+fn source(header: u8, payload: &[u8]) -> Result<Packet, Error> {
+    let a = header >> 3;
+    if header & 0x04 == 0 {
+        return Ok(Packet::Instrumentation {
+            port: a,
+            payload: payload.to_vec(),
+        });
+    }
+
+    let size = payload.len();
+    let v = value(payload);
+    Ok(match (a, size) {
+        (0, 1) => Packet::EventCounter {
+            wrapped: payload[0] & 0x3F,
+        },
+        (1, 2) => Packet::ExceptionTrace {
+            number: (v & 0x1FF) as u16,
+            action: match (payload[1] >> 4) & 0x3 {
+                1 => ExceptionAction::Entered,
+                2 => ExceptionAction::Exited,
+                3 => ExceptionAction::Returned,
+                _ => return Err(Error::Malformed(header)),
+            },
+        },
+        (2, 1) if v == 0 => Packet::PcSample { pc: None },
+        (2, 4) => Packet::PcSample { pc: Some(v) },
+        // 0b01_NN_X, X = 0 PC value, 1 address
+        (8..=15, _) => {
+            let comparator = (a >> 1) & 0x3;
+            match (a & 1, size) {
+                (0, 4) => Packet::DataTracePc { comparator, pc: v },
+                (1, 2) => Packet::DataTraceAddress {
+                    comparator,
+                    address: v as u16,
+                },
+                _ => return Err(Error::Malformed(header)),
+            }
+        }
+        // 0b10_NN_W, W = 1 write, 0 read
+        (16..=23, _) => Packet::DataTraceValue {
+            comparator: (a >> 1) & 0x3,
+            write: a & 1 != 0,
+            value: v,
+            size: size as u8,
+        },
+        (0..=2, _) => return Err(Error::Malformed(header)),
+        _ => Packet::Hardware {
+            discriminator: a,
+            payload: payload.to_vec(),
+        },
+    })
+}
+
+// the 7 bit groups of a continued payload, least significant first
+fn septets(payload: &[u8]) -> u64 {
+    payload
+        .iter()
+        .enumerate()
+        .fold(0, |acc, (i, &byte)| acc | ((byte & 0x7F) as u64) << (7 * i))
+}
+
+fn continued(header: u8, payload: &[u8]) -> Result<Packet, Error> {
+    Ok(match header {
+        0x94 => {
+            let full = payload.len() == 4;
+            let last = payload[payload.len() - 1];
+            let value = septets(payload) as u32;
+            // a full packet has flags in bits 6 and 5 of its last byte
+            let (value, bits) = if full {
+                (value & 0x03FF_FFFF, 26)
+            } else {
+                (value, 7 * payload.len() as u8)
+            };
+            Packet::GlobalTimestamp1 {
+                value,
+                bits,
+                wrap: full && last & 0x20 != 0,
+                clock_change: full && last & 0x40 != 0,
+            }
+        }
+        0xB4 => Packet::GlobalTimestamp2 {
+            value: septets(payload),
+        },
+        h if h & 0xCF == 0xC0 => Packet::LocalTimestamp {
+            delta: septets(payload) as u32,
+            quality: match (h >> 4) & 0x3 {
+                0 => TimestampQuality::Sync,
+                1 => TimestampQuality::TimestampDelayed,
+                2 => TimestampQuality::PacketDelayed,
+                _ => TimestampQuality::BothDelayed,
+            },
+        },
+        // extension, the header holds the low 3 bits
+        h => Packet::Extension {
+            sh: h & 0x04 != 0,
+            value: ((h >> 4) & 0x07) as u32 | (septets(payload) as u32) << 3,
+        },
+    })
+}
+
+/// Turns local timestamps into time since the first packet.
+///
+/// Local timestamps count the core clock (`hz`), divided by the `prescaler`
+/// configured on the target (`TSPrescale`, see `src/swo.rs`). Each holds the
+/// ticks since the previous one, and follows the packets it stamps.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    hz: u32,
+    prescaler: u32,
+    ticks: u64,
+    exact: bool,
+}
+
+impl Clock {
+    pub fn new(hz: u32, prescaler: u32) -> Self {
+        Clock {
+            hz,
+            prescaler,
+            ticks: 0,
+            exact: true,
+        }
+    }
+
+    /// Advances the clock by a timestamp packet, returns `true` for those.
+    ///
+    /// An `Overflow` loses timestamps, the clock is no longer exact.
+    pub fn feed(&mut self, packet: &Packet) -> bool {
+        match packet {
+            Packet::LocalTimestamp { delta, .. } => {
+                self.ticks += *delta as u64;
+                true
+            }
+            Packet::Overflow => {
+                self.exact = false;
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Core clock cycles since the first packet.
+    pub fn cycles(&self) -> u64 {
+        self.ticks * self.prescaler as u64
+    }
+
+    pub fn now(&self) -> Duration {
+        let cycles = self.cycles();
+        let hz = self.hz as u64;
+        Duration::new(cycles / hz, ((cycles % hz) * 1_000_000_000 / hz) as u32)
+    }
+
+    /// `false` once timestamps were lost.
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+}
@@ -0,0 +1,47 @@
+//! Host side of `app`
+//!
+//! Tools talking to the board from the development machine. Modules shared
+//! with the firmware are included from `../src`, so both ends always agree
+//! on the wire format, and the firmware logic that does not need the MCU is
+//! tested here (see `tests/`).
+//!
+//! Run with `cargo test` (or `cargo run --bin ...`) in this directory, the
+//! `.cargo/config` here selects the host target.
+
+pub mod backtrace;
+pub mod bench;
+#[path = "../../src/boot.rs"]
+pub mod boot;
+#[path = "../../src/clocks.rs"]
+pub mod clocks;
+pub mod coredump;
+#[path = "../../src/defer.rs"]
+pub mod defer;
+pub mod dwarf;
+pub mod elf;
+pub mod event;
+#[path = "../../src/fault.rs"]
+pub mod fault;
+#[path = "../../src/field.rs"]
+pub mod field;
+#[path = "../../src/frame.rs"]
+pub mod frame;
+pub mod itm;
+#[path = "../../src/led.rs"]
+pub mod led;
+pub mod log;
+#[path = "../../src/mock.rs"]
+pub mod mock;
+pub mod port;
+#[path = "../../src/ring.rs"]
+pub mod ring;
+#[path = "../../src/shell.rs"]
+pub mod shell;
+pub mod srp;
+#[path = "../../src/stm32f40x.rs"]
+pub mod stm32f40x;
+#[path = "../../src/time.rs"]
+pub mod time;
+pub mod toml;
+#[path = "../../src/trace.rs"]
+pub mod trace;
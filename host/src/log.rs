@@ -0,0 +1,478 @@
+//! Log records, as written by `src/log.rs` to ITM ports 0 to 2
+//!
+//! A record is a line `<tag> <module>: <message>`, the tag being the first
+//! letter of the level (`E`, `W`, `I`, `D` or `T`).
+//!
+//! With the `log-deferred` feature the target sends COBS frames instead,
+//! holding the index of an interned format string and the raw arguments
+//! (see `src/defer.rs`). `Strings`, read from the `.log` section of the
+//! firmware ELF, turns them back into the same lines:
+//!
+//! ``` no_run
+//! use host::elf::Elf;
+//! use host::log::{Frames, Strings};
+//!
+//! let elf = Elf::parse(std::fs::read("target/thumbv7em-none-eabihf/debug/examples/log")?)?;
+//! let strings = Strings::from_elf(&elf).ok_or("no .log section")?;
+//! let mut frames = Frames::new();
+//! for frame in frames.push(&std::fs::read("/dev/ttyACM0")?) {
+//!     println!("{}", strings.line(&frame?)?);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::defer::tag;
+use crate::elf::Elf;
+use crate::frame::cobs;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub fn from_tag(tag: char) -> Option<Self> {
+        Some(match tag {
+            'E' => Level::Error,
+            'W' => Level::Warn,
+            'I' => Level::Info,
+            'D' => Level::Debug,
+            'T' => Level::Trace,
+            _ => return None,
+        })
+    }
+
+    /// ANSI escape sequence setting the colour of the level.
+    pub fn color(self) -> &'static str {
+        match self {
+            Level::Error => "\x1b[1;31m", // bold red
+            Level::Warn => "\x1b[33m",    // yellow
+            Level::Info => "\x1b[32m",    // green
+            Level::Debug => "\x1b[36m",   // cyan
+            Level::Trace => "\x1b[2m",    // dim
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "error" => Level::Error,
+            "warn" => Level::Warn,
+            "info" => Level::Info,
+            "debug" => Level::Debug,
+            "trace" => Level::Trace,
+            _ => return Err(format!("unknown level `{}`", s)),
+        })
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record<'a> {
+    pub level: Level,
+    pub module: &'a str,
+    pub message: &'a str,
+}
+
+impl<'a> Record<'a> {
+    /// Parses a line (without the newline), `None` if it is not a record
+    /// (e.g., plain `iprintln!` output).
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let mut chars = line.chars();
+        let level = Level::from_tag(chars.next()?)?;
+        let rest = chars.as_str().strip_prefix(' ')?;
+        let (module, message) = rest.split_at(rest.find(": ")?);
+        if module.is_empty() || module.contains(' ') {
+            return None;
+        }
+        Some(Record {
+            level,
+            module,
+            message: &message[2..],
+        })
+    }
+
+    /// The record for a terminal, coloured by level if `color`.
+    pub fn render(&self, color: bool) -> String {
+        if color {
+            format!(
+                "{}{:<5}\x1b[0m \x1b[2m{}\x1b[0m {}",
+                self.level.color(),
+                self.level,
+                self.module,
+                self.message
+            )
+        } else {
+            format!("{:<5} {} {}", self.level, self.module, self.message)
+        }
+    }
+}
+
+/// Splits the data of a stimulus port into lines.
+#[derive(Debug, Default)]
+pub struct Lines {
+    partial: Vec<u8>,
+}
+
+impl Lines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `bytes`, returning the lines they complete.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut lines = vec![];
+        for &byte in bytes {
+            if byte == b'\n' {
+                let line = String::from_utf8_lossy(&self.partial);
+                lines.push(line.trim_end_matches('\r').to_string());
+                self.partial.clear();
+            } else {
+                self.partial.push(byte);
+            }
+        }
+        lines
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Malformed COBS, or too short to hold an index.
+    Frame,
+    /// No interned string at the index (built from another ELF?).
+    Index(u16),
+    /// An unknown argument tag, or an argument cut short.
+    Arg,
+    /// A placeholder not understood, or not enough arguments.
+    Format,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Frame => f.write_str("malformed frame"),
+            Error::Index(i) => write!(f, "no string at index {:#06x}, wrong ELF?", i),
+            Error::Arg => f.write_str("malformed argument"),
+            Error::Format => f.write_str("unsupported format string"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Splits a byte stream into (COBS decoded) deferred records.
+#[derive(Debug, Default)]
+pub struct Frames {
+    partial: Vec<u8>,
+}
+
+impl Frames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `bytes`, returning the records they complete.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Result<Vec<u8>, Error>> {
+        let mut records = vec![];
+        for &byte in bytes {
+            if byte != 0 {
+                self.partial.push(byte);
+            } else if !self.partial.is_empty() {
+                let mut record = vec![0; self.partial.len()];
+                records.push(match cobs::decode(&self.partial, &mut record) {
+                    Ok(n) => {
+                        record.truncate(n);
+                        Ok(record)
+                    }
+                    Err(_) => Err(Error::Frame),
+                });
+                self.partial.clear();
+            }
+        }
+        records
+    }
+}
+
+/// An argument of a deferred record.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unsigned(u64),
+    /// The value, and its size in bytes.
+    Signed(i64, u8),
+    Bool(bool),
+    Char(char),
+    F32(f32),
+    Str(String),
+    Bytes(Vec<u8>),
+    /// The arguments from here on did not fit in the record.
+    Truncated,
+}
+
+impl Value {
+    /// Decodes the arguments of a record (following the index).
+    pub fn decode_all(mut bytes: &[u8]) -> Result<Vec<Value>, Error> {
+        let mut values = vec![];
+        while let Some((&tag, rest)) = bytes.split_first() {
+            let size = match tag {
+                tag::U8 | tag::I8 | tag::BOOL => 1,
+                tag::U16 | tag::I16 => 2,
+                tag::U32 | tag::I32 | tag::CHAR | tag::F32 => 4,
+                tag::U64 | tag::I64 => 8,
+                tag::STR | tag::BYTES => 1 + *rest.first().ok_or(Error::Arg)? as usize,
+                tag::TRUNCATED => {
+                    values.push(Value::Truncated);
+                    break;
+                }
+                _ => return Err(Error::Arg),
+            };
+            let value = rest.get(..size).ok_or(Error::Arg)?;
+            bytes = &rest[size..];
+
+            let mut word = [0; 8];
+            word[..size.min(8)].copy_from_slice(&value[..size.min(8)]);
+            let unsigned = u64::from_le_bytes(word);
+            values.push(match tag {
+                tag::U8 | tag::U16 | tag::U32 | tag::U64 => Value::Unsigned(unsigned),
+                tag::I8 => Value::Signed(value[0] as i8 as i64, 1),
+                tag::I16 => Value::Signed(unsigned as u16 as i16 as i64, 2),
+                tag::I32 => Value::Signed(unsigned as u32 as i32 as i64, 4),
+                tag::I64 => Value::Signed(unsigned as i64, 8),
+                tag::BOOL => Value::Bool(value[0] != 0),
+                tag::CHAR => Value::Char(std::char::from_u32(unsigned as u32).ok_or(Error::Arg)?),
+                tag::F32 => Value::F32(f32::from_bits(unsigned as u32)),
+                tag::STR => Value::Str(String::from_utf8_lossy(&value[1..]).into_owned()),
+                _ => Value::Bytes(value[1..].to_vec()),
+            });
+        }
+        Ok(values)
+    }
+}
+
+/// The interned strings of a `log-deferred` firmware.
+#[derive(Debug)]
+pub struct Strings {
+    addr: u32,
+    data: Vec<u8>,
+}
+
+impl Strings {
+    /// The strings of the `.log` section, `None` if there is none.
+    pub fn from_elf(elf: &Elf) -> Option<Self> {
+        let section = elf.section(".log")?;
+        Some(Strings {
+            addr: section.addr,
+            data: elf.data(section).to_vec(),
+        })
+    }
+
+    /// The interned string at `index`.
+    pub fn get(&self, index: u16) -> Option<&str> {
+        let start = (index as u32).checked_sub(self.addr)? as usize;
+        let tail = self.data.get(start..)?;
+        let end = tail.iter().position(|&b| b == 0)?;
+        std::str::from_utf8(&tail[..end]).ok()
+    }
+
+    /// Rebuilds the line of a (COBS decoded) record.
+    pub fn line(&self, record: &[u8]) -> Result<String, Error> {
+        let index = u16::from_le_bytes(record.get(..2).ok_or(Error::Frame)?.try_into().unwrap());
+        let fmt = self.get(index).ok_or(Error::Index(index))?;
+        format(fmt, &Value::decode_all(&record[2..])?)
+    }
+}
+
+// a parsed `{:spec}`
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    plus: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    kind: String,
+}
+
+impl Spec {
+    fn parse(spec: &str) -> Option<Self> {
+        let mut s = Spec::default();
+        let mut chars = spec.chars().peekable();
+        let mut ahead = spec.chars().skip(1);
+        if let Some(align @ ('<' | '^' | '>')) = ahead.next() {
+            s.fill = chars.next();
+            s.align = Some(align);
+            chars.next();
+        } else if let Some(&align @ ('<' | '^' | '>')) = chars.peek() {
+            s.align = Some(align);
+            chars.next();
+        }
+        s.plus = chars.next_if_eq(&'+').is_some();
+        s.alternate = chars.next_if_eq(&'#').is_some();
+        s.zero = chars.next_if_eq(&'0').is_some();
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            s.width = s.width * 10 + digit as usize;
+            chars.next();
+        }
+        if chars.next_if_eq(&'.').is_some() {
+            let mut precision = 0;
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                precision = precision * 10 + digit as usize;
+                chars.next();
+            }
+            s.precision = Some(precision);
+        }
+        s.kind = chars.collect();
+        match s.kind.as_str() {
+            "" | "?" | "x" | "X" | "b" | "o" | "e" | "x?" | "X?" => Some(s),
+            _ => None,
+        }
+    }
+
+    // the value without padding, split in sign, prefix (`0x`) and digits, the
+    // zeros of zero padding going in between
+    fn render(&self, value: &Value) -> Option<(&'static str, &'static str, String)> {
+        let radix = |v: u64| {
+            let (prefix, digits) = match self.kind.trim_end_matches('?') {
+                "x" => ("0x", format!("{:x}", v)),
+                "X" => ("0x", format!("{:X}", v)),
+                "b" => ("0b", format!("{:b}", v)),
+                "o" => ("0o", format!("{:o}", v)),
+                "" => ("", v.to_string()),
+                _ => return None,
+            };
+            Some((if self.alternate { prefix } else { "" }, digits))
+        };
+        let sign = |negative: bool| match (negative, self.plus) {
+            (true, _) => "-",
+            (false, true) => "+",
+            (false, false) => "",
+        };
+
+        Some(match value {
+            Value::Unsigned(v) => {
+                let (prefix, digits) = radix(*v)?;
+                (sign(false), prefix, digits)
+            }
+            // as Rust, two's complement in hex, octal and binary
+            Value::Signed(v, size) if !self.kind.is_empty() && self.kind != "?" => {
+                let (prefix, digits) = radix(*v as u64 & u64::MAX >> (64 - 8 * *size as u32))?;
+                ("", prefix, digits)
+            }
+            Value::Signed(v, _) => (sign(*v < 0), "", v.unsigned_abs().to_string()),
+            Value::F32(v) => {
+                let abs = v.abs();
+                let digits = match (self.kind.as_str(), self.precision) {
+                    ("e", Some(p)) => format!("{:.*e}", p, abs),
+                    ("e", None) => format!("{:e}", abs),
+                    ("?", _) => format!("{:?}", abs),
+                    (_, Some(p)) => format!("{:.*}", p, abs),
+                    (_, None) => abs.to_string(),
+                };
+                (sign(v.is_sign_negative()), "", digits)
+            }
+            Value::Bool(v) => ("", "", v.to_string()),
+            Value::Char(c) if self.kind == "?" => ("", "", format!("{:?}", c)),
+            Value::Char(c) => ("", "", c.to_string()),
+            Value::Str(s) if self.kind == "?" => ("", "", format!("{:?}", s)),
+            Value::Str(s) => match self.precision {
+                Some(p) => ("", "", s.chars().take(p).collect()),
+                None => ("", "", s.clone()),
+            },
+            Value::Bytes(b) => match self.kind.as_str() {
+                "x?" => ("", "", format!("{:x?}", b)),
+                "X?" => ("", "", format!("{:X?}", b)),
+                _ => ("", "", format!("{:?}", b)),
+            },
+            Value::Truncated => ("", "", "…".to_string()),
+        })
+    }
+
+    fn format(&self, value: &Value) -> Option<String> {
+        let (sign, prefix, digits) = self.render(value)?;
+        let numeric = matches!(
+            value,
+            Value::Unsigned(_) | Value::Signed(..) | Value::F32(_)
+        );
+
+        let len = sign.len() + prefix.len() + digits.chars().count();
+        if self.zero && numeric {
+            let zeros = "0".repeat(self.width.saturating_sub(len));
+            return Some(format!("{}{}{}{}", sign, prefix, zeros, digits));
+        }
+
+        let text = format!("{}{}{}", sign, prefix, digits);
+        let pad = self.width.saturating_sub(len);
+        let fill = self.fill.unwrap_or(' ').to_string();
+        let default = if numeric { '>' } else { '<' };
+        Some(match self.align.unwrap_or(default) {
+            '<' => text + &fill.repeat(pad),
+            '^' => fill.repeat(pad / 2) + &text + &fill.repeat(pad - pad / 2),
+            _ => fill.repeat(pad) + &text,
+        })
+    }
+}
+
+/// Formats `values` as Rust would by `fmt`, for the subset of format
+/// strings deferred logging supports: `{}` and `{:spec}` placeholders in
+/// order (no positional or named arguments), with fill, alignment, `+`, `#`,
+/// `0`, width, precision and the `?`, `x`, `X`, `b`, `o`, `e`, `x?` and `X?`
+/// types. Arguments cut off by a full record show as `…`.
+pub fn format(fmt: &str, values: &[Value]) -> Result<String, Error> {
+    let mut out = String::new();
+    let mut values = values.iter();
+    let mut truncated = false;
+    let mut rest = fmt;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err(Error::Format);
+        }
+
+        let end = tail.find('}').ok_or(Error::Format)?;
+        let spec = match &tail[1..end] {
+            "" => Spec::default(),
+            s if s.starts_with(':') => Spec::parse(&s[1..]).ok_or(Error::Format)?,
+            _ => return Err(Error::Format),
+        };
+        let value = if truncated {
+            &Value::Truncated
+        } else {
+            values.next().ok_or(Error::Format)?
+        };
+        truncated = *value == Value::Truncated;
+        out.push_str(&spec.format(value).ok_or(Error::Format)?);
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
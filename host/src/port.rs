@@ -0,0 +1,148 @@
+//! Framed packets over the virtual COM port
+//!
+//! `Port` drives a `frame::Link` over anything `Read + Write`, typically the
+//! serial device of the board. Configure the device first, e.g.,
+//!
+//! ``` text
+//! > stty -F /dev/ttyACM0 115200 raw -echo min 0 time 1
+//! ```
+//!
+//! so that reads return after at most 100 ms without data.
+//!
+//! ``` no_run
+//! use host::port::Port;
+//!
+//! let mut port = Port::open("/dev/ttyACM0")?;
+//! port.send(b"hello")?;
+//! while let Some(payload) = port.recv()? {
+//!     println!("{:?}", payload);
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::frame::{Error, Link, Sink};
+
+pub struct Port<P> {
+    port: P,
+    link: Link,
+    start: Instant,
+    // payloads received while waiting for an `Ack`
+    inbox: VecDeque<Vec<u8>>,
+}
+
+impl Port<File> {
+    /// Opens a (configured) serial device.
+    pub fn open<A: AsRef<Path>>(path: A) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Port::new(file))
+    }
+}
+
+impl<P: Read + Write> Port<P> {
+    pub fn new(port: P) -> Self {
+        Port {
+            port,
+            link: Link::new(),
+            start: Instant::now(),
+            inbox: VecDeque::new(),
+        }
+    }
+
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    /// Sends `payload`, blocking until it is acknowledged.
+    pub fn send(&mut self, payload: &[u8]) -> io::Result<()> {
+        let now = self.now();
+        let mut out = Out::new(&mut self.port);
+        let sent = self.link.send(payload, now, &mut out);
+        out.result()?;
+        sent.map_err(to_io)?;
+
+        while !self.link.is_idle() {
+            self.read()?;
+            let now = self.now();
+            let mut out = Out::new(&mut self.port);
+            let polled = self.link.poll(now, &mut out);
+            out.result()?;
+            polled.map_err(to_io)?;
+        }
+        Ok(())
+    }
+
+    /// The next received payload, `None` if nothing arrived within one read.
+    pub fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.inbox.is_empty() {
+            self.read()?;
+        }
+        Ok(self.inbox.pop_front())
+    }
+
+    // feeds one read worth of bytes to the link
+    fn read(&mut self) -> io::Result<()> {
+        let mut buf = [0; 256];
+        let n = match self.port.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => 0,
+            Err(e) => return Err(e),
+        };
+
+        let now = self.now();
+        let mut out = Out::new(&mut self.port);
+        for &byte in &buf[..n] {
+            if let Some(payload) = self.link.receive(byte, now, &mut out) {
+                self.inbox.push_back(payload.to_vec());
+            }
+        }
+        out.result()
+    }
+
+    fn now(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+}
+
+// collects the first write error, as `Sink::send` cannot fail
+struct Out<'a, W> {
+    port: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<'a, W: Write> Out<'a, W> {
+    fn new(port: &'a mut W) -> Self {
+        Out { port, error: None }
+    }
+
+    fn result(self) -> io::Result<()> {
+        match self.error {
+            Some(e) => Err(e),
+            None => self.port.flush(),
+        }
+    }
+}
+
+impl<W: Write> Sink for Out<'_, W> {
+    fn send(&mut self, frame: &[u8]) {
+        if self.error.is_none() {
+            if let Err(e) = self.port.write_all(frame) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+fn to_io(e: Error) -> io::Error {
+    let kind = match e {
+        Error::Timeout => ErrorKind::TimedOut,
+        Error::TooLong => ErrorKind::InvalidInput,
+        _ => ErrorKind::InvalidData,
+    };
+    io::Error::new(kind, format!("{:?}", e))
+}
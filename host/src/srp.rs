@@ -0,0 +1,424 @@
+//! Response time analysis under the Stack Resource Policy
+//!
+//! RTFM schedules tasks by fixed priorities and locks resources by the SRP,
+//! so a task can be delayed by higher priority tasks (interference) and by at
+//! most one lock held by a lower priority task whose ceiling is at least its
+//! own priority (blocking). For task `i` of execution time `C(i)` the worst
+//! case response time is the smallest fixed point of
+//!
+//! ``` text
+//! R(i) = C(i) + B(i) + sum over j != i, P(j) >= P(i) of ceil(R(i) / T(j)) * C(j)
+//! ```
+//!
+//! where `B(i)` is the longest such lock and `T(j)` the period (minimum
+//! inter-arrival time) of task `j`. Tasks of the same priority do not
+//! preempt each other, but may be queued ahead, so they are counted as
+//! interference too. A task meets its deadline (by default its period) if
+//! `R(i)` does not exceed it.
+//!
+//! All times are in cycles. A `System` is read from a TOML description (see
+//! `toml.rs`) or the report of `src/wcet.rs`, and several can be merged,
+//! e.g., periods and deadlines from a description, execution times from a
+//! measurement:
+//!
+//! ``` text
+//! [[task]]
+//! name = "toggle"
+//! priority = 1
+//! period = 8_000_000
+//! deadline = 4_000_000   # optional, the period by default
+//! wcet = 1_200
+//!
+//! [[lock]]
+//! task = "toggle"
+//! resource = "GPIOA"
+//! ceiling = 2
+//! wcet = 60
+//! ```
+
+use std::fmt::{self, Write};
+
+use crate::toml::{self, Table, Value};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    Toml(toml::Error),
+    /// A task or lock that is incomplete or inconsistent.
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Toml(e) => e.fmt(f),
+            Error::Invalid(e) => f.write_str(e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<toml::Error> for Error {
+    fn from(e: toml::Error) -> Self {
+        Error::Toml(e)
+    }
+}
+
+/// A task, all but the name may be given by a later source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Task {
+    pub name: String,
+    pub priority: Option<u8>,
+    pub period: Option<u32>,
+    pub deadline: Option<u32>,
+    pub wcet: Option<u32>,
+}
+
+/// A critical section of `task` on `resource`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lock {
+    pub task: String,
+    pub resource: String,
+    pub ceiling: u8,
+    pub wcet: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct System {
+    pub tasks: Vec<Task>,
+    pub locks: Vec<Lock>,
+}
+
+// `key=value` pairs of a report line
+fn fields(s: &str) -> Option<Vec<(&str, u32)>> {
+    s.split_whitespace()
+        .map(|field| {
+            let (key, value) = field.split_at(field.find('=')?);
+            Some((key, value[1..].parse().ok()?))
+        })
+        .collect()
+}
+
+fn field(fields: &[(&str, u32)], key: &str) -> Option<u32> {
+    fields.iter().find(|(k, _)| *k == key).map(|&(_, v)| v)
+}
+
+fn invalid<T>(message: String) -> Result<T, Error> {
+    Err(Error::Invalid(message))
+}
+
+// a value of a table, checking its type and range
+fn get<T: std::convert::TryFrom<i64>>(table: &Table, key: &str) -> Result<Option<T>, Error> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::Integer(i)) => match T::try_from(*i) {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => invalid(format!(
+                "[[{}]] {} = {} is out of range",
+                table.name, key, i
+            )),
+        },
+        Some(_) => invalid(format!("[[{}]] {} must be an integer", table.name, key)),
+    }
+}
+
+fn name(table: &Table, key: &str) -> Result<String, Error> {
+    match table.get(key) {
+        Some(Value::String(s)) => Ok(s.clone()),
+        _ => invalid(format!("[[{}]] needs a string `{}`", table.name, key)),
+    }
+}
+
+fn required<T>(table: &Table, key: &str, value: Option<T>) -> Result<T, Error> {
+    value.ok_or_else(|| Error::Invalid(format!("[[{}]] needs `{}`", table.name, key)))
+}
+
+fn check_keys(table: &Table, keys: &[&str]) -> Result<(), Error> {
+    for (key, _) in &table.pairs {
+        if !keys.contains(&key.as_str()) {
+            return invalid(format!("[[{}]] has unknown key `{}`", table.name, key));
+        }
+    }
+    Ok(())
+}
+
+impl System {
+    /// Reads `[[task]]` and `[[lock]]` tables.
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        let mut system = System::default();
+        for table in toml::parse(text)? {
+            match (table.name.as_str(), table.array) {
+                ("", _) if table.pairs.is_empty() => {}
+                ("task", true) => {
+                    check_keys(&table, &["name", "priority", "period", "deadline", "wcet"])?;
+                    system.add_task(Task {
+                        name: name(&table, "name")?,
+                        priority: get(&table, "priority")?,
+                        period: get(&table, "period")?,
+                        deadline: get(&table, "deadline")?,
+                        wcet: get(&table, "wcet")?,
+                    });
+                }
+                ("lock", true) => {
+                    check_keys(&table, &["task", "resource", "ceiling", "wcet"])?;
+                    let ceiling = get(&table, "ceiling")?;
+                    let wcet = get(&table, "wcet")?;
+                    system.add_lock(Lock {
+                        task: name(&table, "task")?,
+                        resource: name(&table, "resource")?,
+                        ceiling: required(&table, "ceiling", ceiling)?,
+                        wcet: required(&table, "wcet", wcet)?,
+                    });
+                }
+                ("", _) => return invalid("keys outside of [[task]] or [[lock]]".to_string()),
+                (name, _) => return invalid(format!("unknown table `{}`", name)),
+            }
+        }
+        Ok(system)
+    }
+
+    /// Reads the `wcet task` and `wcet lock` lines (possibly among other
+    /// output) of a report, the max cycles being the execution time.
+    pub fn from_report(text: &str) -> Self {
+        let mut system = System::default();
+        for line in text.lines() {
+            let (kind, line) = match line.find("wcet task ") {
+                Some(i) => ("task", &line[i + "wcet task ".len()..]),
+                None => match line.find("wcet lock ") {
+                    Some(i) => ("lock", &line[i + "wcet lock ".len()..]),
+                    None => continue,
+                },
+            };
+            let (name, fields) = match line.find(": ") {
+                Some(i) => (&line[..i], fields(&line[i + 2..])),
+                None => continue,
+            };
+            let fields = match fields {
+                Some(fields) => fields,
+                None => continue,
+            };
+            // nothing measured yet, `analyse` reports a task without a wcet
+            let wcet = field(&fields, "max").filter(|_| field(&fields, "n") != Some(0));
+
+            if kind == "task" {
+                system.add_task(Task {
+                    name: name.to_string(),
+                    priority: field(&fields, "priority").map(|p| p as u8),
+                    period: field(&fields, "period"),
+                    deadline: None,
+                    wcet,
+                });
+            } else if let (Some(dot), Some(ceiling), Some(wcet)) =
+                (name.find('.'), field(&fields, "ceiling"), wcet)
+            {
+                system.add_lock(Lock {
+                    task: name[..dot].to_string(),
+                    resource: name[dot + 1..].to_string(),
+                    ceiling: ceiling as u8,
+                    wcet,
+                });
+            }
+        }
+        system
+    }
+
+    /// Adds a task, or updates the one of the same name with what is given.
+    pub fn add_task(&mut self, task: Task) {
+        match self.tasks.iter_mut().find(|t| t.name == task.name) {
+            Some(t) => {
+                t.priority = task.priority.or(t.priority);
+                t.period = task.period.or(t.period);
+                t.deadline = task.deadline.or(t.deadline);
+                t.wcet = task.wcet.or(t.wcet);
+            }
+            None => self.tasks.push(task),
+        }
+    }
+
+    /// Adds a lock, or replaces the one of the same task and resource.
+    pub fn add_lock(&mut self, lock: Lock) {
+        match self
+            .locks
+            .iter_mut()
+            .find(|l| l.task == lock.task && l.resource == lock.resource)
+        {
+            Some(l) => *l = lock,
+            None => self.locks.push(lock),
+        }
+    }
+
+    /// Adds the tasks and locks of `other`.
+    pub fn merge(&mut self, other: System) {
+        for task in other.tasks {
+            self.add_task(task);
+        }
+        for lock in other.locks {
+            self.add_lock(lock);
+        }
+    }
+
+    /// The response time of every task, in the order of `tasks`.
+    pub fn analyse(&self) -> Result<Vec<Response>, Error> {
+        let mut tasks = vec![];
+        for t in &self.tasks {
+            let missing = |what| Error::Invalid(format!("task `{}` has no {}", t.name, what));
+            let period = t
+                .period
+                .filter(|&p| p > 0)
+                .ok_or_else(|| missing("period"))?;
+            tasks.push(Response {
+                name: t.name.clone(),
+                priority: t.priority.ok_or_else(|| missing("priority"))?,
+                period,
+                deadline: t.deadline.unwrap_or(period),
+                wcet: t.wcet.ok_or_else(|| missing("wcet"))?,
+                blocking: 0,
+                response: None,
+            });
+        }
+
+        for lock in &self.locks {
+            let task = match tasks.iter().find(|t| t.name == lock.task) {
+                Some(task) => task,
+                None => return invalid(format!("lock of unknown task `{}`", lock.task)),
+            };
+            if lock.ceiling < task.priority {
+                return invalid(format!(
+                    "lock of `{}` on `{}` has a ceiling ({}) below the priority of the task ({})",
+                    lock.task, lock.resource, lock.ceiling, task.priority
+                ));
+            }
+            if lock.wcet > task.wcet {
+                return invalid(format!(
+                    "lock of `{}` on `{}` is longer than the task",
+                    lock.task, lock.resource
+                ));
+            }
+        }
+
+        let priority = |name: &str| tasks.iter().find(|t| t.name == name).unwrap().priority;
+        let blocking: Vec<u32> = tasks
+            .iter()
+            .map(|t| {
+                self.locks
+                    .iter()
+                    .filter(|l| priority(&l.task) < t.priority && l.ceiling >= t.priority)
+                    .map(|l| l.wcet)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut responses = tasks.clone();
+        for (i, r) in responses.iter_mut().enumerate() {
+            r.blocking = blocking[i];
+            let interfering: Vec<&Response> = tasks
+                .iter()
+                .enumerate()
+                .filter(|&(j, t)| j != i && t.priority >= r.priority)
+                .map(|(_, t)| t)
+                .collect();
+
+            let base = r.wcet as u64 + r.blocking as u64;
+            let mut response = base;
+            r.response = loop {
+                if response > r.deadline as u64 {
+                    break None;
+                }
+                let next = base
+                    + interfering
+                        .iter()
+                        .map(|t| response.div_ceil(t.period as u64) * t.wcet as u64)
+                        .sum::<u64>();
+                if next == response {
+                    break Some(response);
+                }
+                response = next;
+            };
+        }
+        Ok(responses)
+    }
+}
+
+/// The analysis of a task.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub name: String,
+    pub priority: u8,
+    pub period: u32,
+    pub deadline: u32,
+    pub wcet: u32,
+    /// The longest lock of a lower priority task that can delay this one.
+    pub blocking: u32,
+    /// The worst case response time, `None` if past the deadline.
+    pub response: Option<u64>,
+}
+
+impl Response {
+    pub fn meets_deadline(&self) -> bool {
+        self.response.is_some()
+    }
+}
+
+/// Processor utilization of `responses`, 1.0 for fully loaded.
+pub fn utilization(responses: &[Response]) -> f64 {
+    responses
+        .iter()
+        .map(|r| r.wcet as f64 / r.period as f64)
+        .sum()
+}
+
+/// A table of `responses`, by decreasing priority, and a summary.
+pub fn table(responses: &[Response]) -> String {
+    let mut sorted: Vec<&Response> = responses.iter().collect();
+    sorted.sort_by_key(|r| std::cmp::Reverse(r.priority));
+    let width = sorted
+        .iter()
+        .map(|r| r.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:w$}  {:>4}  {:>10}  {:>10}  {:>8}  {:>8}  {:>10}",
+        "task",
+        "prio",
+        "period",
+        "deadline",
+        "wcet",
+        "blocking",
+        "response",
+        w = width
+    )
+    .unwrap();
+    for r in &sorted {
+        let response = match r.response {
+            Some(response) => format!("{:>10}  ok", response),
+            None => format!("{:>10}  MISSED", format!(">{}", r.deadline)),
+        };
+        writeln!(
+            out,
+            "{:w$}  {:>4}  {:>10}  {:>10}  {:>8}  {:>8}  {}",
+            r.name,
+            r.priority,
+            r.period,
+            r.deadline,
+            r.wcet,
+            r.blocking,
+            response,
+            w = width
+        )
+        .unwrap();
+    }
+
+    let missed = sorted.iter().filter(|r| !r.meets_deadline()).count();
+    write!(out, "utilization {:.1}%, ", 100.0 * utilization(responses)).unwrap();
+    match missed {
+        0 => writeln!(out, "all deadlines met"),
+        n => writeln!(out, "{} of {} deadlines missed", n, sorted.len()),
+    }
+    .unwrap();
+    out
+}
@@ -0,0 +1,181 @@
+//! A minimal TOML reader
+//!
+//! Just the subset the task descriptions of `srp` use: comments, `[table]`
+//! and `[[array]]` headers, and `key = value` pairs, a value being an integer
+//! (decimal with `_` separators, or `0x` hexadecimal), a basic string or a
+//! boolean. Dotted keys, inline tables, arrays and dates are not supported.
+//!
+//! ``` text
+//! # rtfm_blinky_msg1
+//! [[task]]
+//! name = "toggle"
+//! priority = 1
+//! period = 8_000_000
+//! wcet = 1_200
+//! ```
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    String(String),
+    Boolean(bool),
+}
+
+/// A `[table]` or an element of an `[[array]]` of tables, the top level
+/// pairs are in a table with an empty name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Table {
+    pub name: String,
+    pub array: bool,
+    pub pairs: Vec<(String, Value)>,
+}
+
+impl Table {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    /// 1-based.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn is_bare(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// the line up to a `#` outside of a string
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn string(s: &str) -> Result<String, String> {
+    let inner = s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| format!("bad string `{}`", s))?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('"') => '"',
+                Some('\\') => '\\',
+                _ => return Err(format!("bad escape in `{}`", s)),
+            }),
+            '"' => return Err(format!("bad string `{}`", s)),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+fn integer(s: &str) -> Result<i64, String> {
+    let bad = || format!("bad value `{}`", s);
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+        return Err(bad());
+    }
+    let digits = digits.replace('_', "");
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse(),
+    }
+    .map_err(|_| bad())?;
+    Ok(if negative { -value } else { value })
+}
+
+fn value(s: &str) -> Result<Value, String> {
+    match s {
+        "true" => Ok(Value::Boolean(true)),
+        "false" => Ok(Value::Boolean(false)),
+        _ if s.starts_with('"') => string(s).map(Value::String),
+        _ => integer(s).map(Value::Integer),
+    }
+}
+
+/// The tables of `text`, in order.
+pub fn parse(text: &str) -> Result<Vec<Table>, Error> {
+    let mut tables = vec![Table {
+        name: String::new(),
+        array: false,
+        pairs: vec![],
+    }];
+    for (i, line) in text.lines().enumerate() {
+        let error = |message: String| Error {
+            line: i + 1,
+            message,
+        };
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let (name, array) = match header.strip_prefix('[') {
+                Some(header) => (header.strip_suffix("]]"), true),
+                None => (header.strip_suffix(']'), false),
+            };
+            let name = name
+                .map(str::trim)
+                .filter(|name| is_bare(name))
+                .ok_or_else(|| error(format!("bad table header `{}`", line)))?;
+            if !array && tables.iter().any(|t| t.name == name) {
+                return Err(error(format!("duplicate table `{}`", name)));
+            }
+            tables.push(Table {
+                name: name.to_string(),
+                array,
+                pairs: vec![],
+            });
+            continue;
+        }
+
+        let eq = line
+            .find('=')
+            .ok_or_else(|| error(format!("expected `key = value`, found `{}`", line)))?;
+        let key = line[..eq].trim();
+        if !is_bare(key) {
+            return Err(error(format!("bad key `{}`", key)));
+        }
+        let value = value(line[eq + 1..].trim()).map_err(error)?;
+        let table = tables.last_mut().unwrap();
+        if table.get(key).is_some() {
+            return Err(error(format!("duplicate key `{}`", key)));
+        }
+        table.pairs.push((key.to_string(), value));
+    }
+    Ok(tables)
+}
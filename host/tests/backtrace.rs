@@ -0,0 +1,237 @@
+//! Backtrace tests, with a synthetic ELF of `examples/backtrace.rs`
+
+use std::process::Command;
+
+use host::backtrace::{addresses, demangle, Frame, Symbols};
+use host::dwarf::{Lines, Location};
+use host::elf::{Elf, STT_FUNC};
+
+// `c` at 0x0800_0500 and `b` at 0x0800_0530, Thumb functions
+const FUNCTIONS: [(&str, u32, u32); 2] = [
+    ("_ZN9backtrace1c17h0123456789abcdefE", 0x0800_0501, 0x30),
+    ("_ZN9backtrace1b17hfedcba9876543210E", 0x0800_0531, 0x20),
+];
+
+fn uleb(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+// a version 4 line number program: `c` at lines 63 and 65, `b` at 59 and 60
+fn debug_line() -> Vec<u8> {
+    let (line_base, line_range, opcode_base) = (-5i8, 14u8, 13u8);
+    let mut header = vec![1, 1, 1, line_base as u8, line_range, opcode_base];
+    header.extend_from_slice(&[0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+    header.extend_from_slice(b"examples\0\0");
+    header.extend_from_slice(b"backtrace.rs\0\x01\0\0\0");
+
+    let special =
+        |address: u8, line: i8| opcode_base + (line - line_base) as u8 + address * line_range;
+    let mut program = vec![0, 5, 2];
+    program.extend_from_slice(&0x0800_0500u32.to_le_bytes());
+    program.extend_from_slice(&[3, 62]); // advance_line 62, to 63
+    program.push(1); // copy
+    program.push(special(4, 2)); // 0x504, 65
+    program.push(2); // advance_pc 0x2c, to 0x530
+    uleb(&mut program, 0x2C);
+    program.push(3); // advance_line -6, to 59
+    program.push(0x7A);
+    program.push(1);
+    program.push(special(6, 1)); // 0x536, 60
+    program.extend_from_slice(&[2, 0x1A]); // to 0x550, the end
+    program.extend_from_slice(&[0, 1, 1]);
+
+    let mut unit = 4u16.to_le_bytes().to_vec();
+    unit.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    unit.extend_from_slice(&header);
+    unit.extend_from_slice(&program);
+    let mut section = (unit.len() as u32).to_le_bytes().to_vec();
+    section.extend_from_slice(&unit);
+    section
+}
+
+// a 32-bit little endian ARM ELF with `.symtab`, `.strtab` and `.debug_line`
+fn elf() -> Vec<u8> {
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; 16];
+    for &(name, value, size) in FUNCTIONS.iter() {
+        symtab.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+        symtab.extend_from_slice(&value.to_le_bytes());
+        symtab.extend_from_slice(&size.to_le_bytes());
+        symtab.extend_from_slice(&[0x10 | STT_FUNC, 0, 1, 0]);
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+    let sections: [(&str, u32, Vec<u8>); 4] = [
+        (".shstrtab", 3, vec![]),
+        (".symtab", 2, symtab),
+        (".strtab", 3, strtab),
+        (".debug_line", 1, debug_line()),
+    ];
+    let mut shstrtab = vec![0];
+    let mut names = vec![];
+    for (name, _, _) in sections.iter() {
+        names.push(shstrtab.len() as u32);
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+    }
+
+    let mut data = vec![];
+    let mut headers = vec![0; 40];
+    for (i, (_, kind, bytes)) in sections.iter().enumerate() {
+        let bytes = if i == 0 { &shstrtab } else { bytes };
+        let offset = 52 + data.len() as u32;
+        for &field in &[
+            names[i],
+            *kind,
+            0,
+            0,
+            offset,
+            bytes.len() as u32,
+            0,
+            0,
+            1,
+            0,
+        ] {
+            headers.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(bytes);
+    }
+
+    let mut elf = b"\x7fELF\x01\x01\x01\0\0\0\0\0\0\0\0\0".to_vec();
+    elf.extend_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    elf.extend_from_slice(&40u16.to_le_bytes()); // EM_ARM
+    for &field in &[1u32, 0, 0, 52 + data.len() as u32, 0x0500_0000] {
+        elf.extend_from_slice(&field.to_le_bytes());
+    }
+    for &field in &[52u16, 32, 0, 40, 1 + sections.len() as u16, 1] {
+        elf.extend_from_slice(&field.to_le_bytes());
+    }
+    elf.extend_from_slice(&data);
+    elf.extend_from_slice(&headers);
+    elf
+}
+
+fn location(line: u32) -> Option<Location> {
+    Some(Location {
+        file: "examples/backtrace.rs".to_string(),
+        line,
+    })
+}
+
+#[test]
+fn lines() {
+    let lines = Lines::parse(&Elf::parse(elf()).unwrap()).unwrap();
+    assert_eq!(lines.find(0x0800_04FF), None);
+    assert_eq!(lines.find(0x0800_0500), location(63));
+    assert_eq!(lines.find(0x0800_0503), location(63));
+    assert_eq!(lines.find(0x0800_0504), location(65));
+    assert_eq!(lines.find(0x0800_0535), location(59));
+    assert_eq!(lines.find(0x0800_054F), location(60));
+    assert_eq!(lines.find(0x0800_0550), None);
+}
+
+#[test]
+fn symbols() {
+    let symbols = Symbols::new(&Elf::parse(elf()).unwrap()).unwrap();
+    let frame = |address| symbols.frame(address);
+
+    // a return address, into the line of the call
+    assert_eq!(
+        frame(0x0800_0505),
+        Frame {
+            address: 0x0800_0504,
+            function: Some("backtrace::c".to_string()),
+            location: location(63),
+        }
+    );
+    // the return address of a call ending `c`, still in `c`
+    assert_eq!(
+        frame(0x0800_0531).function,
+        Some("backtrace::c".to_string())
+    );
+    // the `pc` of an exception frame, as it is
+    assert_eq!(frame(0x0800_0504).location, location(65));
+    assert_eq!(
+        frame(0x0800_0537).to_string(),
+        "0x08000536 in backtrace::b at examples/backtrace.rs:59"
+    );
+    assert_eq!(frame(0x0900_0001).to_string(), "0x09000000 in ??");
+}
+
+#[test]
+fn parse() {
+    assert_eq!(
+        addresses("backtrace: 0x08000a1b 0x080004f7 0x08000529\r"),
+        Some(vec![0x0800_0A1B, 0x0800_04F7, 0x0800_0529])
+    );
+    assert_eq!(addresses("backtrace:"), Some(vec![]));
+    assert_eq!(addresses("panicked at 'oops', src/main.rs:1:1"), None);
+    assert_eq!(addresses("backtrace: 0x0800zzzz"), None);
+}
+
+#[test]
+fn demangling() {
+    assert_eq!(
+        demangle("_ZN9backtrace1c17h0123456789abcdefE"),
+        "backtrace::c"
+    );
+    assert_eq!(
+        demangle("_ZN4core9panicking18panic_bounds_check17h5fd3a4e4c9d3e6a1E"),
+        "core::panicking::panic_bounds_check"
+    );
+    assert_eq!(
+        demangle("_ZN57_$LT$app..crash..Record$u20$as$u20$core..fmt..Display$GT$3fmt17h0123456789abcdefE"),
+        "<app::crash::Record as core::fmt::Display>::fmt"
+    );
+    assert_eq!(
+        demangle("_ZN4core3ptr13drop_in_place17h0123456789abcdefE"),
+        "core::ptr::drop_in_place"
+    );
+    assert_eq!(demangle("rust_begin_unwind"), "rust_begin_unwind");
+    assert_eq!(demangle("_ZN3bad"), "_ZN3bad");
+}
+
+#[test]
+fn cli() {
+    let dir = std::env::temp_dir().join(format!("backtrace-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (elf_path, input) = (dir.join("backtrace"), dir.join("itm.txt"));
+    std::fs::write(&elf_path, elf()).unwrap();
+    std::fs::write(
+        &input,
+        "panicked at 'index out of bounds', examples/backtrace.rs:65:5\n\
+         backtrace: 0x08000505 0x08000537\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_backtrace"))
+        .arg("-e")
+        .arg(&elf_path)
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "panicked at 'index out of bounds', examples/backtrace.rs:65:5\n\
+         backtrace:\n   \
+         0: 0x08000504 in backtrace::c at examples/backtrace.rs:63\n   \
+         1: 0x08000536 in backtrace::b at examples/backtrace.rs:59\n"
+    );
+
+    let status = Command::new(env!("CARGO_BIN_EXE_backtrace"))
+        .arg(&input)
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(2));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
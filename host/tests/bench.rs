@@ -0,0 +1,89 @@
+//! Benchmark report tests, against the output of `examples/bench.rs`
+
+use std::process::Command;
+
+use host::bench::{table, Report};
+
+const DEV: &str = "\
+bench index: n=16 min=4120 median=4131 max=4190 overhead=14
+bench copy_from_slice: n=16 min=530 median=531 max=560 overhead=14
+bench zip: n=16 min=3600 median=3610 max=3650 overhead=14
+done, copies ok
+";
+
+// timestamped by `itm -t`, and one benchmark less
+const RELEASE: &str = "\
+[   0.000120] bench index: n=16 min=260 median=262 max=270 overhead=6
+[   0.000150] bench copy_from_slice: n=16 min=265 median=265 max=280 overhead=6
+[   0.000190] done, copies ok
+";
+
+#[test]
+fn parse() {
+    assert_eq!(
+        Report::parse("bench copy_from_slice: n=16 min=530 median=531 max=560 overhead=14"),
+        Some(Report {
+            name: "copy_from_slice".to_string(),
+            iterations: 16,
+            min: 530,
+            median: 531,
+            max: 560,
+            overhead: 14,
+        })
+    );
+    assert_eq!(Report::parse_all(DEV).len(), 3);
+    assert_eq!(Report::parse_all(RELEASE)[1].median, 265);
+
+    assert_eq!(Report::parse("done, copies ok"), None);
+    assert_eq!(Report::parse("bench x: n=16 min=1 median=1"), None);
+    assert_eq!(
+        Report::parse("bench x: n=16 min=a median=1 max=1 overhead=0"),
+        None
+    );
+}
+
+#[test]
+fn tables() {
+    let dev = Report::parse_all(DEV);
+    let release = Report::parse_all(RELEASE);
+
+    assert_eq!(
+        table(&dev, None),
+        "\
+name                  min   median      max
+index                4120     4131     4190
+copy_from_slice       530      531      560
+zip                  3600     3610     3650
+"
+    );
+    assert_eq!(
+        table(&dev, Some(&release)),
+        "\
+name                  min   median      max       min   median      max    change
+index                4120     4131     4190       260      262      270    -93.7%
+copy_from_slice       530      531      560       265      265      280    -50.1%
+zip                  3600     3610     3650         -        -        -         -
+"
+    );
+}
+
+#[test]
+fn cli_compares_files() {
+    let dir = std::env::temp_dir().join(format!("bench-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (dev, release) = (dir.join("dev.txt"), dir.join("release.txt"));
+    std::fs::write(&dev, DEV).unwrap();
+    std::fs::write(&release, RELEASE).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+        .arg(&dev)
+        .arg(&release)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 4);
+    assert!(stdout.contains("-50.1%"));
+}
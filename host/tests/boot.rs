@@ -0,0 +1,112 @@
+//! Boot reason tests, with `RCC_CSR` values as left by each kind of reset
+
+use host::boot::{self, BootReason, Crash, Reset};
+use host::stm32f40x::{address::RCC_BASE, rcc::csr, RCC};
+use host::trace::Recorder;
+
+const RMVF: u32 = csr::RMVF.bits(1);
+const BORRSTF: u32 = csr::BORRSTF.bits(1);
+const PINRSTF: u32 = csr::PINRSTF.bits(1);
+const PORRSTF: u32 = csr::PORRSTF.bits(1);
+const SFTRSTF: u32 = csr::SFTRSTF.bits(1);
+const IWDGRSTF: u32 = csr::IWDGRSTF.bits(1);
+const WWDGRSTF: u32 = csr::WWDGRSTF.bits(1);
+const LPWRRSTF: u32 = csr::LPWRRSTF.bits(1);
+
+// any reset is output on the reset pin
+const POWER_ON: u32 = PORRSTF | BORRSTF | PINRSTF;
+const BROWNOUT: u32 = BORRSTF | PINRSTF;
+const SOFTWARE: u32 = SFTRSTF | PINRSTF;
+const PIN: u32 = PINRSTF;
+
+#[test]
+fn resets() {
+    assert_eq!(Reset::decode(POWER_ON), Reset::PowerOn);
+    assert_eq!(Reset::decode(BROWNOUT), Reset::Brownout);
+    assert_eq!(Reset::decode(SOFTWARE), Reset::Software);
+    assert_eq!(Reset::decode(PIN), Reset::Pin);
+    assert_eq!(
+        Reset::decode(IWDGRSTF | PINRSTF),
+        Reset::IndependentWatchdog
+    );
+    assert_eq!(Reset::decode(WWDGRSTF | PINRSTF), Reset::WindowWatchdog);
+    assert_eq!(Reset::decode(LPWRRSTF | PINRSTF), Reset::LowPower);
+    assert_eq!(Reset::decode(0), Reset::Unknown);
+}
+
+#[test]
+fn other_bits() {
+    // LSION and LSIRDY, and `RMVF` (reads as 0, but a value may carry it)
+    assert_eq!(Reset::decode(0b11 | SOFTWARE), Reset::Software);
+    assert_eq!(Reset::decode(0b11 | RMVF), Reset::Unknown);
+}
+
+#[test]
+fn flags_not_cleared() {
+    // a program not clearing the flags sees those of all resets since power on
+    assert_eq!(Reset::decode(POWER_ON | SOFTWARE), Reset::PowerOn);
+    assert_eq!(
+        Reset::decode(SOFTWARE | IWDGRSTF),
+        Reset::IndependentWatchdog
+    );
+}
+
+#[test]
+fn boot_reasons() {
+    assert_eq!(
+        BootReason::new(SOFTWARE, Some(Crash::Panic)),
+        BootReason::Crash(Crash::Panic, Reset::Software)
+    );
+    assert_eq!(
+        BootReason::new(PIN, Some(Crash::Fault)),
+        BootReason::Crash(Crash::Fault, Reset::Pin)
+    );
+    assert_eq!(
+        BootReason::new(SOFTWARE, None),
+        BootReason::Reset(Reset::Software)
+    );
+
+    // the RAM is lost at power on, a record then is by chance
+    let reason = BootReason::new(POWER_ON, Some(Crash::Panic));
+    assert_eq!(reason, BootReason::Reset(Reset::PowerOn));
+    assert!(!reason.is_crash());
+
+    // the watchdog reset a program halted by a fault
+    let reason = BootReason::new(IWDGRSTF | PINRSTF, Some(Crash::Fault));
+    assert!(reason.is_crash());
+    assert_eq!(reason.reset(), Reset::IndependentWatchdog);
+}
+
+#[test]
+fn display() {
+    let reason = |csr, crash| BootReason::new(csr, crash).to_string();
+    assert_eq!(reason(POWER_ON, None), "power on reset");
+    assert_eq!(reason(PIN, None), "reset pin");
+    assert_eq!(reason(0, None), "no reset flag");
+    assert_eq!(
+        reason(SOFTWARE, Some(Crash::Panic)),
+        "panic, then software reset"
+    );
+    assert_eq!(
+        reason(WWDGRSTF, Some(Crash::Fault)),
+        "fault, then window watchdog reset"
+    );
+}
+
+#[test]
+fn take_flags() {
+    let rec: Recorder<16> = Recorder::new();
+    let rcc = RCC::mock(&rec);
+    let csr = RCC_BASE + 0x74;
+
+    // as left by power on (the reset value), with the LSI on
+    csr::LSION.modify(&rcc.CSR, 1);
+    rec.clear();
+    assert_eq!(boot::take_flags(&rcc), POWER_ON | 1);
+    let write = rec.events().find(|e| e.is_write_to(csr));
+    assert_eq!(write.map(|e| e.value), Some(POWER_ON | RMVF | 1));
+    assert_eq!(rcc.CSR.cell().peek(), 1);
+
+    // cleared for the next start
+    assert_eq!(Reset::decode(boot::take_flags(&rcc)), Reset::Unknown);
+}
@@ -0,0 +1,375 @@
+//! Clock tree solver tests, against the RM0368 (F401) and RM0383 (F411)
+//! constraints, and `Setup::apply` against the mock register file
+
+use host::clocks::{solve_pll, Config, Error, Limits, Pll, Source, F401, F411, HSI, LIMITS};
+use host::stm32f40x::{
+    address::{FLASH_BASE, PWR_BASE, RCC_BASE},
+    flash::acr,
+    pwr::{self, cr as pwr_cr},
+    rcc::{apb1enr, cfgr, cr, pllcfgr},
+    FLASH, PWR, RCC,
+};
+use host::trace::{Dir, Recorder};
+
+const MHZ: u32 = 1_000_000;
+
+// checks a PLL setting against the constraints of RM0368 6.3.2
+fn assert_valid(pll: &Pll, input: u32, sysclk: u32, limits: &Limits) {
+    let vco_in = input / pll.m;
+    assert_eq!(input % pll.m, 0, "{:?}", pll);
+    assert!((2..=63).contains(&pll.m), "{:?}", pll);
+    assert!((MHZ..=2 * MHZ).contains(&vco_in), "{:?}", pll);
+    assert!((limits.pll_n_min..=432).contains(&pll.n), "{:?}", pll);
+    assert!(
+        (limits.vco_min..=432 * MHZ).contains(&pll.vco(input)),
+        "{:?}",
+        pll
+    );
+    assert!([2, 4, 6, 8].contains(&pll.p), "{:?}", pll);
+    assert!((2..=15).contains(&pll.q), "{:?}", pll);
+    assert!(pll.pll48clk(input) <= 48 * MHZ, "{:?}", pll);
+    assert_eq!(pll.sysclk(input), sysclk, "{:?}", pll);
+}
+
+#[test]
+fn hsi_84mhz_f401() {
+    let setup = Config::hsi().sysclk(84 * MHZ).solve(&F401).unwrap();
+    // VCO input 1.6 MHz, as 2 MHz would need PLLN = 168 < 192
+    assert_eq!(
+        setup.pll,
+        Some(Pll {
+            m: 10,
+            n: 210,
+            p: 4,
+            q: 7
+        })
+    );
+    assert_eq!(setup.latency, 2);
+    assert_eq!((setup.ppre1, setup.ppre2), (2, 1));
+
+    let clocks = setup.clocks;
+    assert_eq!(clocks.sysclk(), 84 * MHZ);
+    assert_eq!(clocks.hclk(), 84 * MHZ);
+    assert_eq!(clocks.pclk1(), 42 * MHZ);
+    assert_eq!(clocks.pclk2(), 84 * MHZ);
+    assert_eq!(clocks.timclk1(), 84 * MHZ);
+    assert_eq!(clocks.timclk2(), 84 * MHZ);
+    assert_eq!(clocks.pll48clk(), Some(48 * MHZ));
+}
+
+#[test]
+fn hse_84mhz_f401() {
+    let setup = Config::hse(8 * MHZ)
+        .bypass()
+        .sysclk(84 * MHZ)
+        .solve(&F401)
+        .unwrap();
+    assert_eq!(
+        setup.source,
+        Source::Hse {
+            freq: 8 * MHZ,
+            bypass: true
+        }
+    );
+    assert_eq!(
+        setup.pll,
+        Some(Pll {
+            m: 5,
+            n: 210,
+            p: 4,
+            q: 7
+        })
+    );
+    assert_eq!(setup.clocks.pll48clk(), Some(48 * MHZ));
+}
+
+#[test]
+fn f411() {
+    // no VCO gives both 100 MHz and 48 MHz, the highest VCO input wins
+    let setup = Config::hsi().sysclk(100 * MHZ).solve(&F411).unwrap();
+    let pll = setup.pll.unwrap();
+    assert_eq!(
+        pll,
+        Pll {
+            m: 8,
+            n: 100,
+            p: 2,
+            q: 5
+        }
+    );
+    assert_valid(&pll, HSI, 100 * MHZ, &F411);
+    assert_eq!(setup.latency, 3);
+    assert_eq!(setup.clocks.pclk1(), 50 * MHZ);
+    assert_eq!(setup.clocks.pclk2(), 100 * MHZ);
+    assert_eq!(setup.clocks.pll48clk(), Some(40 * MHZ));
+
+    // the F411 allows PLLN < 192, so a 2 MHz VCO input for 84 MHz
+    let pll = solve_pll(8 * MHZ, 84 * MHZ, &F411).unwrap();
+    assert_eq!(
+        pll,
+        Pll {
+            m: 4,
+            n: 168,
+            p: 4,
+            q: 7
+        }
+    );
+    assert_eq!(pll.pll48clk(8 * MHZ), 48 * MHZ);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(
+        Config::hsi().sysclk(100 * MHZ).solve(&F401),
+        Err(Error::TooFast)
+    );
+    assert_eq!(
+        Config::hsi().sysclk(101 * MHZ).solve(&F411),
+        Err(Error::TooFast)
+    );
+    assert_eq!(Config::hse(3 * MHZ).solve(&F401), Err(Error::HseOutOfRange));
+    assert_eq!(
+        Config::hse(27 * MHZ).sysclk(84 * MHZ).solve(&F401),
+        Err(Error::HseOutOfRange)
+    );
+    assert_eq!(
+        Config::hsi().sysclk(83_333_333).solve(&F401),
+        Err(Error::NoSolution)
+    );
+    // below the VCO range of the F401, even at PLLP = 8
+    assert_eq!(
+        Config::hsi().sysclk(20 * MHZ).solve(&F401),
+        Err(Error::NoSolution)
+    );
+    assert!(Config::hsi().sysclk(20 * MHZ).solve(&F411).is_ok());
+}
+
+#[test]
+fn no_pll() {
+    let setup = Config::hsi().solve(&F401).unwrap();
+    assert_eq!(setup.pll, None);
+    assert_eq!(setup.latency, 0);
+    assert_eq!(setup.clocks.sysclk(), HSI);
+    assert_eq!(setup.clocks.pll48clk(), None);
+
+    let setup = Config::hse(25 * MHZ).solve(&F401).unwrap();
+    assert_eq!(setup.pll, None);
+    assert_eq!(setup.clocks.sysclk(), 25 * MHZ);
+}
+
+#[test]
+fn pll_bounds() {
+    for limits in [&F401, &F411].iter() {
+        for &input in [HSI, 8 * MHZ, 25 * MHZ].iter() {
+            let mut solved = 0;
+            for sysclk in (1..=limits.sysclk_max / MHZ).map(|f| f * MHZ) {
+                if let Some(pll) = solve_pll(input, sysclk, limits) {
+                    assert_valid(&pll, input, sysclk, limits);
+                    solved += 1;
+                }
+            }
+            assert!(solved > 40, "{} solved from {}", solved, input);
+        }
+    }
+}
+
+#[test]
+fn usb_clock() {
+    // 48 MHz exactly whenever some VCO allows it
+    for &sysclk in [48 * MHZ, 72 * MHZ, 84 * MHZ].iter() {
+        let pll = solve_pll(HSI, sysclk, &F401).unwrap();
+        assert_eq!(pll.pll48clk(HSI), 48 * MHZ, "{} Hz", sysclk);
+    }
+    // never above
+    let pll = solve_pll(HSI, 50 * MHZ, &F411).unwrap();
+    assert!(pll.pll48clk(HSI) < 48 * MHZ);
+}
+
+#[test]
+fn flash_wait_states() {
+    let latency = |sysclk, limits| Config::hsi().sysclk(sysclk).solve(limits).unwrap().latency;
+    assert_eq!(latency(24 * MHZ, &F401), 0);
+    assert_eq!(latency(30 * MHZ, &F401), 0);
+    assert_eq!(latency(32 * MHZ, &F401), 1);
+    assert_eq!(latency(60 * MHZ, &F401), 1);
+    assert_eq!(latency(64 * MHZ, &F401), 2);
+    assert_eq!(latency(84 * MHZ, &F401), 2);
+
+    assert_eq!(latency(30 * MHZ, &F411), 0);
+    assert_eq!(latency(64 * MHZ, &F411), 1);
+    assert_eq!(latency(72 * MHZ, &F411), 2);
+    assert_eq!(latency(90 * MHZ, &F411), 2);
+    assert_eq!(latency(96 * MHZ, &F411), 3);
+    assert_eq!(latency(100 * MHZ, &F411), 3);
+}
+
+#[test]
+fn apply() {
+    let rec: Recorder<256> = Recorder::new();
+    let (rcc, flash, pwr) = (RCC::mock(&rec), FLASH::mock(&rec), PWR::mock(&rec));
+
+    let clocks = Config::hsi()
+        .sysclk(84 * MHZ)
+        .freeze(&rcc, &flash, &pwr)
+        .unwrap();
+    assert_eq!(clocks.sysclk(), 84 * MHZ);
+
+    // as solved for the `LIMITS` of the selected MCU
+    let pll = solve_pll(HSI, 84 * MHZ, LIMITS).unwrap();
+    assert_eq!(pllcfgr::PLLM.read(&rcc.PLLCFGR), pll.m);
+    assert_eq!(pllcfgr::PLLN.read(&rcc.PLLCFGR), pll.n);
+    assert_eq!(pllcfgr::PLLP.read(&rcc.PLLCFGR), pll.p / 2 - 1);
+    assert_eq!(pllcfgr::PLLQ.read(&rcc.PLLCFGR), pll.q);
+    assert_eq!(pllcfgr::PLLSRC.read(&rcc.PLLCFGR), 0);
+    assert_eq!(cr::PLLRDY.read(&rcc.CR), 1);
+    assert_eq!(cfgr::SWS.read(&rcc.CFGR), 0b10);
+    assert_eq!(cfgr::PPRE1.read(&rcc.CFGR), 0b100);
+    assert_eq!(cfgr::PPRE2.read(&rcc.CFGR), 0b000);
+    assert_eq!(acr::LATENCY.read(&flash.ACR), 2);
+
+    // wait states raised before, PLL configured while off, then switched to
+    let writes: Vec<_> = rec
+        .events()
+        .filter(|e| e.dir == Dir::Write)
+        .map(|e| (e.addr, e.value))
+        .collect();
+    let position =
+        |pred: &dyn Fn(u32, u32) -> bool| writes.iter().position(|&(a, v)| pred(a, v)).unwrap();
+    let latency = position(&|a, v| a == FLASH_BASE && v & 0xF == 2);
+    let pllcfgr = position(&|a, _| a == RCC_BASE + 0x04);
+    let pllon = position(&|a, v| a == RCC_BASE && v & (1 << 24) != 0);
+    let switch = position(&|a, v| a == RCC_BASE + 0x08 && v & 0b11 == 0b10);
+    assert!(latency < switch);
+    assert!(pllcfgr < pllon && pllon < switch);
+
+    // back to the HSI, the wait states lowered after the switch
+    rec.clear();
+    Config::hsi().freeze(&rcc, &flash, &pwr).unwrap();
+    assert_eq!(cfgr::SWS.read(&rcc.CFGR), 0b00);
+    assert_eq!(acr::LATENCY.read(&flash.ACR), 0);
+    let switch = rec.position(|e| e.is_write_to(RCC_BASE + 0x08) && e.value & 0b11 == 0);
+    let latency = rec.position(|e| e.is_write_to(FLASH_BASE) && e.value & 0xF == 0);
+    assert!(switch.unwrap() < latency.unwrap());
+}
+
+const CFGR: u32 = RCC_BASE + 0x08;
+
+// PPRE1 of the CFGR writes, up to the switch to `sws` and after
+fn ppre1_around_switch(rec: &Recorder<256>, sws: u32) -> (Vec<u32>, Vec<u32>) {
+    let writes: Vec<_> = rec
+        .events()
+        .filter(|e| e.is_write_to(CFGR))
+        .map(|e| e.value)
+        .collect();
+    let switch = writes.iter().position(|v| v & 0b11 == sws).unwrap();
+    let ppre1 = |v: &u32| v >> 10 & 0b111;
+    (
+        writes[..=switch].iter().map(ppre1).collect(),
+        writes[switch + 1..].iter().map(ppre1).collect(),
+    )
+}
+
+#[test]
+fn prescalers_around_switch() {
+    let rec: Recorder<256> = Recorder::new();
+    let (rcc, flash, pwr) = (RCC::mock(&rec), FLASH::mock(&rec), PWR::mock(&rec));
+
+    // up, the APB1 divided by 2 before the PLL drives SYSCLK
+    let setup = Config::hsi().sysclk(84 * MHZ).solve(&F401).unwrap();
+    setup.apply(&rcc, &flash, &pwr);
+    let (before, after) = ppre1_around_switch(&rec, 0b10);
+    assert!(before.iter().all(|&bits| bits == 0b100), "{:?}", before);
+    assert!(after.iter().all(|&bits| bits == 0b100), "{:?}", after);
+
+    // down, the APB1 divided until SYSCLK is the HSI, then not divided
+    rec.clear();
+    let setup = Config::hsi().solve(&F401).unwrap();
+    setup.apply(&rcc, &flash, &pwr);
+    let (before, after) = ppre1_around_switch(&rec, 0b00);
+    assert!(before.iter().all(|&bits| bits == 0b100), "{:?}", before);
+    assert_eq!(after.last(), Some(&0b000));
+    assert_eq!(cfgr::PPRE1.read(&rcc.CFGR), 0b000);
+    assert_eq!(cfgr::PPRE2.read(&rcc.CFGR), 0b000);
+    assert_eq!(cfgr::HPRE.read(&rcc.CFGR), 0);
+}
+
+#[test]
+fn regulator_scale() {
+    let rec: Recorder<256> = Recorder::new();
+    let (rcc, flash, pwr) = (RCC::mock(&rec), FLASH::mock(&rec), PWR::mock(&rec));
+
+    assert_eq!(
+        Config::hsi().sysclk(84 * MHZ).solve(&F411).unwrap().vos,
+        pwr::SCALE2
+    );
+    let setup = Config::hsi().sysclk(100 * MHZ).solve(&F411).unwrap();
+    assert_eq!(setup.vos, pwr::SCALE1);
+    setup.apply(&rcc, &flash, &pwr);
+    assert_eq!(apb1enr::PWREN.read(&rcc.APB1ENR), 1);
+    assert_eq!(pwr_cr::VOS.read(&pwr.CR), pwr::SCALE1);
+
+    // the PWR clocked before the write, which is made while the PLL is off,
+    // and all before the switch to 100 MHz
+    let pwren = rec.position(|e| e.is_write_to(RCC_BASE + 0x40) && e.value & (1 << 28) != 0);
+    let vos = rec.position(|e| e.is_write_to(PWR_BASE) && e.value >> 14 & 0b11 == 0b11);
+    let pllon = rec.position(|e| e.is_write_to(RCC_BASE) && e.value & (1 << 24) != 0);
+    let switch = rec.position(|e| e.is_write_to(CFGR) && e.value & 0b11 == 0b10);
+    assert!(pwren.unwrap() < vos.unwrap());
+    assert!(vos.unwrap() < pllon.unwrap());
+    assert!(pllon.unwrap() < switch.unwrap());
+
+    // back to scale 2 with the PLL reconfigured
+    Config::hsi()
+        .sysclk(84 * MHZ)
+        .solve(&F411)
+        .unwrap()
+        .apply(&rcc, &flash, &pwr);
+    assert_eq!(pwr_cr::VOS.read(&pwr.CR), pwr::SCALE2);
+}
+
+#[test]
+fn hse_bypass() {
+    let rec: Recorder<256> = Recorder::new();
+    let (rcc, flash, pwr) = (RCC::mock(&rec), FLASH::mock(&rec), PWR::mock(&rec));
+
+    let bypass = Config::hse(8 * MHZ).bypass().sysclk(84 * MHZ);
+    bypass.solve(&F401).unwrap().apply(&rcc, &flash, &pwr);
+    assert_eq!(cr::HSEBYP.read(&rcc.CR), 1);
+    assert_eq!(cfgr::SWS.read(&rcc.CFGR), 0b10);
+
+    // the same again, the HSE left running
+    rec.clear();
+    bypass.solve(&F401).unwrap().apply(&rcc, &flash, &pwr);
+    assert_eq!(
+        rec.position(|e| e.is_write_to(RCC_BASE) && e.value & (1 << 16) == 0),
+        None
+    );
+
+    // to the crystal: off the PLL, the HSE off, then HSEBYP cleared
+    rec.clear();
+    let crystal = Config::hse(8 * MHZ).sysclk(84 * MHZ);
+    crystal.solve(&F401).unwrap().apply(&rcc, &flash, &pwr);
+    assert_eq!(cr::HSEBYP.read(&rcc.CR), 0);
+    assert_eq!(cr::HSEON.read(&rcc.CR), 1);
+    assert_eq!(cfgr::SWS.read(&rcc.CFGR), 0b10);
+
+    let hsi = rec.position(|e| e.is_write_to(CFGR) && e.value & 0b11 == 0b00);
+    let hse_off = rec.position(|e| e.is_write_to(RCC_BASE) && e.value & (1 << 16) == 0);
+    let cleared = rec.position(|e| e.is_write_to(RCC_BASE) && e.value & (1 << 18) == 0);
+    assert!(hsi.unwrap() < hse_off.unwrap());
+    assert!(hse_off.unwrap() < cleared.unwrap());
+
+    // HSE without the PLL, to bypass
+    Config::hse(8 * MHZ)
+        .solve(&F401)
+        .unwrap()
+        .apply(&rcc, &flash, &pwr);
+    assert_eq!(cfgr::SWS.read(&rcc.CFGR), 0b01);
+    Config::hse(8 * MHZ)
+        .bypass()
+        .solve(&F401)
+        .unwrap()
+        .apply(&rcc, &flash, &pwr);
+    assert_eq!(cr::HSEBYP.read(&rcc.CR), 1);
+    assert_eq!(cfgr::SWS.read(&rcc.CFGR), 0b01);
+}
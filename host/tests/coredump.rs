@@ -0,0 +1,199 @@
+//! Core dump tests, with a synthetic dump of `examples/crash.rs`
+
+use std::convert::TryInto;
+use std::process::Command;
+
+use host::coredump::{Dump, Error, MAGIC, PC, PORT, SP, VERSION};
+use host::elf::Elf;
+use host::fault::{cfsr, hfsr};
+use host::frame::crc16;
+
+const RAM: u32 = 0x2000_0000;
+
+fn regs() -> [u32; 17] {
+    let mut regs = [0; 17];
+    regs[0] = 0x2FFF_FFFF;
+    regs[SP] = RAM + 0x7BE0;
+    regs[14] = 0x0800_0405;
+    regs[PC] = 0x0800_0412;
+    regs[16] = 0x6100_0000;
+    regs
+}
+
+fn stack() -> Vec<u8> {
+    (0..64u8).collect()
+}
+
+// the stream of `src/coredump.rs`, a dump of the top of the stack
+fn dump() -> Vec<u8> {
+    let mut body = vec![VERSION];
+    for reg in regs().iter() {
+        body.extend_from_slice(&reg.to_le_bytes());
+    }
+    let fault = [
+        cfsr::PRECISERR | cfsr::BFARVALID,
+        hfsr::FORCED,
+        0x2FFF_FFFF,
+        0x2FFF_FFFF,
+        0,
+    ];
+    for reg in fault.iter() {
+        body.extend_from_slice(&reg.to_le_bytes());
+    }
+    body.push(1);
+    body.extend_from_slice(&(RAM + 0x7BC0).to_le_bytes());
+    body.extend_from_slice(&64u32.to_le_bytes());
+    body.extend_from_slice(&stack());
+
+    let mut stream = b"booting\r\n".to_vec();
+    stream.extend_from_slice(MAGIC);
+    stream.extend_from_slice(&body);
+    stream.extend_from_slice(&crc16(&body).to_le_bytes());
+    stream
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+#[test]
+fn parse() {
+    let dump = Dump::parse(&dump()).unwrap();
+    assert_eq!(dump.regs, regs());
+    assert_eq!(dump.fault.bfar(), Some(0x2FFF_FFFF));
+    assert_eq!(dump.regions.len(), 1);
+    assert_eq!(dump.regions[0].address, RAM + 0x7BC0);
+    assert_eq!(dump.regions[0].bytes, stack());
+    assert_eq!(dump.word(RAM + 0x7BC4), Some(0x0706_0504));
+    assert_eq!(dump.word(RAM + 0x7BFE), None);
+
+    let text = dump.to_string();
+    assert!(text.starts_with("HardFault, escalated from a configurable fault\n"));
+    assert!(text.contains("sp=0x20007be0 lr=0x08000405 pc=0x08000412\nxpsr=0x61000000\n"));
+    assert!(text.ends_with("region 0x20007bc0..0x20007c00 (64 bytes)\n"));
+}
+
+#[test]
+fn errors() {
+    assert_eq!(Dump::parse(b"no dump here"), Err(Error::NoMagic));
+
+    let stream = dump();
+    assert_eq!(
+        Dump::parse(&stream[..stream.len() - 1]),
+        Err(Error::Truncated)
+    );
+    assert_eq!(Dump::parse(&stream[..40]), Err(Error::Truncated));
+
+    // a byte lost from the memory
+    let mut lost = stream.clone();
+    lost.remove(stream.len() - 10);
+    assert_eq!(Dump::parse(&lost), Err(Error::Truncated));
+
+    let mut changed = stream.clone();
+    changed[stream.len() - 10] ^= 1;
+    assert_eq!(Dump::parse(&changed), Err(Error::Crc));
+
+    let mut version = stream;
+    version[9 + 4] = 9;
+    assert_eq!(Dump::parse(&version), Err(Error::Version(9)));
+}
+
+#[test]
+fn elf() {
+    let core = Dump::parse(&dump()).unwrap().to_elf();
+    // a 32-bit little endian ARM core
+    assert!(Elf::parse(core.clone()).is_ok());
+    assert_eq!(u16_at(&core, 16), 4);
+    assert_eq!(u16_at(&core, 18), 40);
+    assert_eq!(u32_at(&core, 28), 52);
+    assert_eq!(u16_at(&core, 42), 32);
+    assert_eq!(u16_at(&core, 44), 2);
+
+    // the note, an `NT_PRSTATUS` of "CORE"
+    let (note, size) = (52 + 2 * 32, 12 + 8 + 148);
+    assert_eq!(u32_at(&core, 52), 4);
+    assert_eq!(u32_at(&core, 56), note as u32);
+    assert_eq!(u32_at(&core, 68), size as u32);
+    assert_eq!(
+        &core[note..note + 12],
+        [5, 0, 0, 0, 148, 0, 0, 0, 1, 0, 0, 0]
+    );
+    assert_eq!(&core[note + 12..note + 17], b"CORE\0");
+    let desc = note + 20;
+    assert_eq!(u16_at(&core, desc + 12), 11);
+    for (i, reg) in regs().iter().enumerate() {
+        assert_eq!(u32_at(&core, desc + 72 + 4 * i), *reg);
+    }
+
+    // the memory, loaded at its address
+    let load = 52 + 32;
+    assert_eq!(u32_at(&core, load), 1);
+    assert_eq!(u32_at(&core, load + 4), (note + size) as u32);
+    assert_eq!(u32_at(&core, load + 8), RAM + 0x7BC0);
+    assert_eq!(u32_at(&core, load + 16), 64);
+    assert_eq!(&core[note + size..], &stack()[..]);
+}
+
+#[test]
+fn cli() {
+    let dir = std::env::temp_dir().join(format!("coredump-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let coredump = env!("CARGO_BIN_EXE_coredump");
+
+    // over the serial port
+    let (input, core) = (dir.join("serial.bin"), dir.join("core"));
+    std::fs::write(&input, dump()).unwrap();
+    let output = Command::new(coredump)
+        .arg("-o")
+        .arg(&core)
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("pc=0x08000412"));
+    let expected = Dump::parse(&dump()).unwrap().to_elf();
+    assert_eq!(std::fs::read(&core).unwrap(), expected);
+
+    // over ITM, in 4 byte writes to the port (and 1 byte ones at the end)
+    let mut itm = vec![];
+    let stream = dump();
+    let mut chunks = stream.chunks_exact(4);
+    for chunk in &mut chunks {
+        itm.push(PORT << 3 | 3);
+        itm.extend_from_slice(chunk);
+    }
+    for &byte in chunks.remainder() {
+        itm.extend_from_slice(&[PORT << 3 | 1, byte]);
+    }
+    let input = dir.join("itm.bin");
+    std::fs::write(&input, itm).unwrap();
+    let status = Command::new(coredump)
+        .arg("-i")
+        .arg("-o")
+        .arg(&core)
+        .arg(&input)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read(&core).unwrap(), expected);
+
+    // no dump
+    let input = dir.join("none.bin");
+    std::fs::write(&input, "booting\r\n").unwrap();
+    let output = Command::new(coredump)
+        .arg("-o")
+        .arg(dir.join("none"))
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(output.stderr, b"coredump: no core dump found\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
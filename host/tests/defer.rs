@@ -0,0 +1,239 @@
+//! Deferred logging tests, records built by the shared `defer` module
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use host::defer::{tag, Arg, Record, MAX_FRAME, MAX_RECORD};
+use host::elf::{self, Elf};
+use host::log::{format, Error, Frames, Strings, Value};
+
+// interned strings, as laid out by the linker in `.log`
+const LOG: &[u8] = b"I app: started\0W app::serial: overrun {} at {:#010x}\0D app: {:?} {:>6.2}\0";
+const STARTED: u16 = 0;
+const OVERRUN: u16 = 15;
+const DEBUG: u16 = 53;
+
+// a 32-bit little endian ELF, with `.log` at address 0 and `.text`
+fn image(log: &[u8]) -> Vec<u8> {
+    let names = b"\0.log\0.shstrtab\0.text\0";
+    let text = [0u8; 8];
+    let log_at = 52;
+    let text_at = log_at + log.len();
+    let names_at = text_at + text.len();
+    let headers_at = names_at + names.len();
+
+    let mut bytes = vec![0x7f, b'E', b'L', b'F', 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    for half in &[2u16, 40] {
+        bytes.extend(&half.to_le_bytes());
+    }
+    for word in &[1u32, 0, 0, headers_at as u32, 0x0500_0400] {
+        bytes.extend(&word.to_le_bytes());
+    }
+    for half in &[52u16, 0, 0, 40, 4, 2] {
+        bytes.extend(&half.to_le_bytes());
+    }
+    bytes.extend(log);
+    bytes.extend(&text);
+    bytes.extend(&names[..]);
+
+    // name, type, flags, addr, offset, size
+    let sections = [
+        [0, 0, 0, 0, 0, 0],
+        [1, 1, 0, 0, log_at, log.len()],
+        [6, 3, 0, 0, names_at, names.len()],
+        [16, 1, 6, 0x0800_0000, text_at, text.len()],
+    ];
+    for section in &sections {
+        for &word in section.iter().chain(&[0, 0, 0, 0]) {
+            bytes.extend(&(word as u32).to_le_bytes());
+        }
+    }
+    bytes
+}
+
+fn record(index: u16, args: &[&dyn Arg]) -> Record {
+    let mut record = Record::new(index);
+    for arg in args {
+        arg.encode(&mut record);
+    }
+    record
+}
+
+fn frame(record: &Record) -> Vec<u8> {
+    let mut frame = [0; MAX_FRAME];
+    let n = record.frame(&mut frame);
+    frame[..n].to_vec()
+}
+
+#[test]
+fn elf_sections() {
+    let elf = Elf::parse(image(LOG)).unwrap();
+    let names: Vec<_> = elf.sections().iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["", ".log", ".shstrtab", ".text"]);
+
+    let text = elf.section(".text").unwrap();
+    assert_eq!((text.addr, text.size), (0x0800_0000, 8));
+    assert_eq!(elf.data(elf.section(".log").unwrap()), LOG);
+
+    assert_eq!(
+        Elf::parse(b"#!/bin/sh".to_vec()).unwrap_err(),
+        elf::Error::NotElf
+    );
+    let mut elf64 = image(LOG);
+    elf64[4] = 2;
+    assert_eq!(Elf::parse(elf64).unwrap_err(), elf::Error::Unsupported);
+    let mut short = image(LOG);
+    short.truncate(100);
+    assert_eq!(Elf::parse(short).unwrap_err(), elf::Error::Truncated);
+}
+
+#[test]
+fn round_trip() {
+    let strings = Strings::from_elf(&Elf::parse(image(LOG)).unwrap()).unwrap();
+    assert_eq!(strings.get(STARTED), Some("I app: started"));
+    assert_eq!(strings.get(DEBUG), Some("D app: {:?} {:>6.2}"));
+
+    let mut stream = frame(&record(STARTED, &[]));
+    stream.extend(frame(&record(OVERRUN, &[&3u32, &0x2000_1000u32])));
+    stream.extend(frame(&record(DEBUG, &[&"idle", &-1.5f32])));
+
+    let mut frames = Frames::new();
+    let (head, tail) = stream.split_at(10);
+    let mut records = frames.push(head);
+    records.extend(frames.push(tail));
+
+    let lines: Vec<String> = records
+        .into_iter()
+        .map(|r| strings.line(&r.unwrap()).unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "I app: started",
+            "W app::serial: overrun 3 at 0x20001000",
+            "D app: \"idle\"  -1.50",
+        ]
+    );
+
+    assert_eq!(strings.line(&[1, 2]), Err(Error::Index(0x0201)));
+    assert_eq!(strings.line(&[0]), Err(Error::Frame));
+    assert_eq!(Frames::new().push(&[0x05, 1, 0]), vec![Err(Error::Frame)]);
+}
+
+#[test]
+fn arguments() {
+    let bytes = [1u8, 2, 3];
+    let record = record(
+        0,
+        &[
+            &-2i8,
+            &-300i16,
+            &u64::MAX,
+            &true,
+            &'å',
+            &"ok",
+            &bytes,
+            &7usize,
+        ],
+    );
+    assert_eq!(
+        Value::decode_all(&record.as_bytes()[2..]).unwrap(),
+        vec![
+            Value::Signed(-2, 1),
+            Value::Signed(-300, 2),
+            Value::Unsigned(u64::MAX),
+            Value::Bool(true),
+            Value::Char('å'),
+            Value::Str("ok".to_string()),
+            Value::Bytes(vec![1, 2, 3]),
+            Value::Unsigned(7),
+        ]
+    );
+    assert_eq!(Value::decode_all(&[tag::U32, 1, 2]), Err(Error::Arg));
+    assert_eq!(Value::decode_all(&[0x42]), Err(Error::Arg));
+}
+
+#[test]
+fn truncated() {
+    let long = "x".repeat(60);
+    let record = record(0, &[&1u8, &long.as_str(), &2u8, &3u8]);
+    assert!(record.as_bytes().len() <= MAX_RECORD);
+    assert_eq!(record.as_bytes()[2..], [tag::U8, 1, tag::TRUNCATED]);
+
+    let values = Value::decode_all(&record.as_bytes()[2..]).unwrap();
+    assert_eq!(format("{} {} {} {}", &values).unwrap(), "1 … … …");
+}
+
+#[test]
+fn formatting() {
+    let u = |v| [Value::Unsigned(v)];
+    let i = |v, size| [Value::Signed(v, size)];
+
+    // as `format!` would
+    assert_eq!(format("{} {{}}", &u(42)).unwrap(), format!("{} {{}}", 42));
+    assert_eq!(format("{:#x}", &u(255)).unwrap(), format!("{:#x}", 255));
+    assert_eq!(
+        format("{:08X}", &u(0xBEEF)).unwrap(),
+        format!("{:08X}", 0xBEEF)
+    );
+    assert_eq!(format("{:#010b}", &u(5)).unwrap(), format!("{:#010b}", 5));
+    assert_eq!(format("{:x}", &i(-1, 1)).unwrap(), format!("{:x}", -1i8));
+    assert_eq!(
+        format("{:+05}", &i(-42, 4)).unwrap(),
+        format!("{:+05}", -42)
+    );
+    assert_eq!(format("{:+}", &i(42, 4)).unwrap(), format!("{:+}", 42));
+    assert_eq!(format("[{:>5}]", &u(7)).unwrap(), format!("[{:>5}]", 7));
+    assert_eq!(format("[{:*^7}]", &u(7)).unwrap(), format!("[{:*^7}]", 7));
+    assert_eq!(
+        format("{:.3} {:e}", &[Value::F32(1.23456), Value::F32(1500.0)]).unwrap(),
+        format!("{:.3} {:e}", 1.23456f32, 1500.0f32)
+    );
+    assert_eq!(
+        format(
+            "[{:6}] [{:<4}]",
+            &[Value::Str("ab".into()), Value::Bool(true)]
+        )
+        .unwrap(),
+        format!("[{:6}] [{:<4}]", "ab", true)
+    );
+    assert_eq!(
+        format("{:x?}", &[Value::Bytes(vec![10, 255])]).unwrap(),
+        format!("{:x?}", [10u8, 255])
+    );
+
+    assert_eq!(format("{} {}", &u(1)), Err(Error::Format));
+    assert_eq!(format("{0}", &u(1)), Err(Error::Format));
+    assert_eq!(format("{:q}", &u(1)), Err(Error::Format));
+    assert_eq!(format("}", &[]), Err(Error::Format));
+}
+
+#[test]
+fn cli_decodes_uart() {
+    let path = std::env::temp_dir().join(format!("itm-defer-{}.elf", std::process::id()));
+    std::fs::write(&path, image(LOG)).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_itm"))
+        .arg("-e")
+        .arg(&path)
+        .args(["-u", "-n", "-l", "warn", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+        stdin.write_all(&frame(&record(STARTED, &[]))).unwrap();
+        stdin
+            .write_all(&frame(&record(OVERRUN, &[&1u32, &0xFFu32])))
+            .unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "WARN  app::serial overrun 1 at 0x000000ff\n"
+    );
+}
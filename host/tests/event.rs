@@ -0,0 +1,159 @@
+//! Task trace event tests, with synthetic traces of `examples/rtfm_itm_spawn.rs`
+
+use std::process::Command;
+
+use host::event::{Error, Event, Kind, Trace, PORT};
+
+// an ITM instrumentation packet on the event port
+fn write(stream: &mut Vec<u8>, payload: &[u8]) {
+    let size = match payload.len() {
+        1 => 1,
+        2 => 2,
+        _ => 3,
+    };
+    stream.push(PORT << 3 | size);
+    stream.extend_from_slice(payload);
+}
+
+fn event(stream: &mut Vec<u8>, kind: u16, id: u16, cycles: u32, name: &str) {
+    write(stream, &(kind << 12 | id).to_le_bytes());
+    write(stream, &cycles.to_le_bytes());
+    if kind < 2 {
+        for &b in name.as_bytes().iter().chain(&[0]) {
+            write(stream, &[b]);
+        }
+    }
+}
+
+// `init` spawns `task1`, `idle` spawns it with `itm` locked and then again
+fn spawn() -> Vec<u8> {
+    let mut s = vec![];
+    event(&mut s, 0, 0, 10, "init");
+    event(&mut s, 0, 1, 20, "idle");
+    event(&mut s, 0, 2, 30, "task1");
+    event(&mut s, 1, 0, 40, "itm");
+    event(&mut s, 2, 0, 100, ""); // init
+    event(&mut s, 3, 0, 200, "");
+    event(&mut s, 2, 2, 210, ""); // task1 from init
+    event(&mut s, 3, 2, 300, "");
+    event(&mut s, 2, 1, 310, ""); // idle
+    event(&mut s, 4, 0, 320, "");
+    event(&mut s, 5, 0, 400, "");
+    event(&mut s, 2, 2, 410, ""); // task1, after the unlock
+    event(&mut s, 3, 2, 480, "");
+    event(&mut s, 2, 2, 490, ""); // task1 from idle
+    event(&mut s, 3, 2, 560, "");
+    s
+}
+
+fn trace(stream: &[u8]) -> Trace {
+    let mut trace = Trace::new();
+    for packet in host::itm::Decoder::new().decode(stream) {
+        if let Ok(host::itm::Packet::Instrumentation {
+            port: PORT,
+            payload,
+        }) = packet
+        {
+            trace.push(&payload).unwrap();
+        }
+    }
+    trace
+}
+
+#[test]
+fn decode() {
+    let trace = trace(&spawn());
+    assert_eq!(trace.events.len(), 11);
+    assert_eq!(
+        trace.events[5],
+        Event {
+            time: 320,
+            kind: Kind::Lock,
+            id: 0,
+        }
+    );
+    assert_eq!(trace.task(2), "task1");
+    assert_eq!(trace.task(7), "task7");
+    assert_eq!(trace.resource(0), "itm");
+
+    // the cycle counter wraps
+    let mut s = vec![];
+    event(&mut s, 2, 0, 0xFFFF_FF00, "");
+    event(&mut s, 3, 0, 0x10, "");
+    let trace = self::trace(&s);
+    assert_eq!(trace.events[1].time, 0x1_0000_0010);
+
+    let mut trace = Trace::new();
+    assert_eq!(trace.push(&[0; 4]), Err(Error::Unexpected(4)));
+    assert_eq!(trace.push(&[0, 0x70]), Err(Error::Kind(7)));
+    assert_eq!(trace.push(&[0x00, 0x20]), Ok(()));
+    assert_eq!(trace.push(&[0]), Err(Error::Unexpected(1)));
+}
+
+#[test]
+fn vcd() {
+    // 1 GHz, a cycle per ns
+    let vcd = trace(&spawn()).vcd(1_000_000_000);
+    assert!(vcd.starts_with("$timescale 1 ns $end\n"));
+    for var in [
+        "$var wire 12 ! running $end",
+        "$var wire 1 \" init $end",
+        "$var wire 1 # idle $end",
+        "$var wire 1 $ task1 $end",
+        "$var wire 1 % itm $end",
+    ] {
+        assert!(vcd.contains(var), "{}", vcd);
+    }
+    // `task1` preempts `idle`, then `idle` runs again
+    assert!(vcd.contains("#410\n1$\nb10 !\n#480\n0$\nb1 !\n"), "{}", vcd);
+    assert!(vcd.contains("#320\n1%\n#400\n0%\n"), "{}", vcd);
+}
+
+#[test]
+fn chrome() {
+    let json = trace(&spawn()).chrome(16_000_000);
+    assert!(json.starts_with("{\"traceEvents\":[\n"));
+    assert!(json.ends_with("\n]}\n"));
+    assert!(json.contains(
+        "{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":2,\"args\":{\"name\":\"task1\"}}"
+    ));
+    // 410 cycles at 16 MHz
+    assert!(json.contains(
+        "{\"name\":\"task1\",\"cat\":\"task\",\"ph\":\"B\",\"ts\":25.625,\"pid\":0,\"tid\":2}"
+    ));
+    // the lock is on `idle`
+    assert!(json.contains(
+        "{\"name\":\"itm\",\"cat\":\"lock\",\"ph\":\"E\",\"ts\":25.000,\"pid\":0,\"tid\":1}"
+    ));
+    assert_eq!(json.matches("\"ph\":\"B\"").count(), 6);
+    assert_eq!(json.matches("\"ph\":\"E\"").count(), 5);
+}
+
+#[test]
+fn cli() {
+    let dir = std::env::temp_dir().join(format!("events-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("itm.bin");
+    let output = dir.join("spawn.json");
+    std::fs::write(&input, spawn()).unwrap();
+
+    let events = env!("CARGO_BIN_EXE_events");
+    let status = Command::new(events)
+        .arg("-o")
+        .arg(&output)
+        .arg(&input)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let json = std::fs::read_to_string(&output).unwrap();
+    assert!(json.starts_with("{\"traceEvents\""));
+
+    let vcd = Command::new(events)
+        .args(["-f", "vcd", "-c", "1000000000"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8(vcd.stdout).unwrap().contains("#560\n"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
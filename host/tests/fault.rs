@@ -0,0 +1,128 @@
+//! Fault status decoding tests, with register values of typical faults
+
+use host::fault::{cfsr, hfsr, Cause, Registers};
+
+// `crash.rs`, reading 0x2FFF_FFFF, escalated to a HardFault
+const CRASH: Registers = Registers {
+    cfsr: cfsr::PRECISERR | cfsr::BFARVALID,
+    hfsr: hfsr::FORCED,
+    mmfar: 0x2FFF_FFFF,
+    bfar: 0x2FFF_FFFF,
+    afsr: 0,
+};
+
+fn causes(regs: &Registers) -> Vec<Cause> {
+    regs.causes().collect()
+}
+
+#[test]
+fn precise_bus_fault() {
+    assert_eq!(
+        causes(&CRASH),
+        [Cause::Forced, Cause::PreciseBusError(Some(0x2FFF_FFFF))]
+    );
+    assert_eq!(
+        CRASH.to_string(),
+        "HardFault, escalated from a configurable fault\n  \
+         precise data bus error at 0x2fffffff\n\
+         CFSR=0x00008200 HFSR=0x40000000 MMFAR=0x2fffffff BFAR=0x2fffffff AFSR=0x00000000\n"
+    );
+    assert!(!CRASH.is_stack_overflow());
+
+    // the address is only valid with `BFARVALID`
+    let imprecise = Registers {
+        cfsr: cfsr::IMPRECISERR,
+        bfar: 0x1234,
+        ..Registers::default()
+    };
+    assert_eq!(imprecise.bfar(), None);
+    assert_eq!(causes(&imprecise), [Cause::ImpreciseBusError]);
+    assert!(imprecise.to_string().starts_with("BusFault\n  imprecise"));
+}
+
+#[test]
+fn usage_faults() {
+    let regs = Registers {
+        cfsr: cfsr::UNALIGNED | cfsr::DIVBYZERO | cfsr::INVSTATE,
+        hfsr: hfsr::FORCED,
+        ..Registers::default()
+    };
+    assert_eq!(
+        causes(&regs),
+        [
+            Cause::Forced,
+            Cause::InvalidState,
+            Cause::Unaligned,
+            Cause::DivideByZero
+        ]
+    );
+    let text = regs.to_string();
+    assert!(text.contains("\n  invalid EPSR state"), "{}", text);
+    assert!(
+        text.contains("\n  unaligned access\n  divide by zero\n"),
+        "{}",
+        text
+    );
+
+    // handled by the UsageFault handler, not escalated
+    let regs = Registers {
+        cfsr: cfsr::DIVBYZERO,
+        ..Registers::default()
+    };
+    assert!(regs
+        .to_string()
+        .starts_with("UsageFault\n  divide by zero\n"));
+}
+
+#[test]
+fn stack_overflow() {
+    let regs = Registers {
+        cfsr: cfsr::STKERR,
+        hfsr: hfsr::FORCED,
+        ..Registers::default()
+    };
+    assert!(regs.is_stack_overflow());
+    assert!(regs
+        .to_string()
+        .contains("\n  stack overflow on exception entry (bus fault stacking)\n"));
+
+    let regs = Registers {
+        cfsr: cfsr::MSTKERR | cfsr::DACCVIOL | cfsr::MMARVALID,
+        mmfar: 0x2000_0000,
+        ..Registers::default()
+    };
+    assert!(regs.is_stack_overflow());
+    assert_eq!(
+        causes(&regs),
+        [
+            Cause::DataAccessViolation(Some(0x2000_0000)),
+            Cause::MemManageStacking
+        ]
+    );
+    assert!(regs.to_string().starts_with("MemManage\n"));
+}
+
+#[test]
+fn hard_faults() {
+    let regs = Registers {
+        hfsr: hfsr::VECTTBL,
+        ..Registers::default()
+    };
+    assert_eq!(
+        regs.to_string().lines().next(),
+        Some("HardFault, bus fault reading the vector table")
+    );
+
+    // escalated, but the configurable status was already cleared
+    let regs = Registers {
+        hfsr: hfsr::FORCED,
+        ..Registers::default()
+    };
+    assert!(regs
+        .to_string()
+        .contains("(configurable fault status cleared)"));
+
+    assert!(Registers::default()
+        .to_string()
+        .starts_with("no fault recorded\n"));
+}
@@ -0,0 +1,141 @@
+//! Register field tests, on plain memory (`Cell<u32>`) and `VolatileCell`
+
+use std::cell::Cell;
+
+use host::field::{Access, Field, Reg, Value};
+use host::stm32f40x::VolatileCell;
+
+// a register of our own
+struct R;
+
+const LOW: Field<R, 0, 1> = Field::new();
+const HIGH: Field<R, 31, 1> = Field::new();
+const NIBBLE: Field<R, 0, 4> = Field::new();
+const TOP: Field<R, 29, 3> = Field::new();
+const MIDDLE: Field<R, 8, 8> = Field::new();
+
+fn reg(value: u32) -> Reg<R, Cell<u32>> {
+    Reg::new(Cell::new(value))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Speed {
+    Low = 0b000,
+    High = 0b101,
+}
+
+impl Value for Speed {
+    const WIDTH: u8 = 3;
+
+    fn bits(self) -> u32 {
+        self as u32
+    }
+
+    fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            0b000 => Some(Speed::Low),
+            0b101 => Some(Speed::High),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn masks() {
+    assert_eq!(Field::<R, 0, 1>::MASK, 0x0000_0001);
+    assert_eq!(Field::<R, 31, 1>::MASK, 0x8000_0000);
+    assert_eq!(Field::<R, 0, 4>::MASK, 0x0000_000F);
+    assert_eq!(Field::<R, 29, 3>::MASK, 0xE000_0000);
+    assert_eq!(Field::<R, 0, 32>::MASK, 0xFFFF_FFFF);
+}
+
+#[test]
+fn bits() {
+    assert_eq!(LOW.bits(1), 0x0000_0001);
+    assert_eq!(HIGH.bits(1), 0x8000_0000);
+    assert_eq!(NIBBLE.bits(0xA), 0x0000_000A);
+    assert_eq!(TOP.bits(0b101), 0xA000_0000);
+    assert_eq!(MIDDLE.bits(0xFF), 0x0000_FF00);
+    assert_eq!(TOP.val(Speed::High), 0xA000_0000);
+}
+
+#[test]
+fn read() {
+    let r = reg(0x8000_00F5);
+    assert_eq!(LOW.read(&r), 1);
+    assert_eq!(HIGH.read(&r), 1);
+    assert_eq!(NIBBLE.read(&r), 0x5);
+    assert_eq!(TOP.read(&r), 0b100);
+    assert_eq!(MIDDLE.read(&r), 0);
+}
+
+#[test]
+fn modify_single_bits() {
+    // all ones, clearing a bit leaves the others set
+    let r = reg(u32::MAX);
+    LOW.modify(&r, 0);
+    assert_eq!(r.read(), 0xFFFF_FFFE);
+    HIGH.modify(&r, 0);
+    assert_eq!(r.read(), 0x7FFF_FFFE);
+
+    // all zeros, setting a bit leaves the others clear
+    let r = reg(0);
+    HIGH.modify(&r, 1);
+    assert_eq!(r.read(), 0x8000_0000);
+    LOW.modify(&r, 1);
+    assert_eq!(r.read(), 0x8000_0001);
+}
+
+#[test]
+fn modify_multi_bits() {
+    let r = reg(u32::MAX);
+    NIBBLE.modify(&r, 0b0110);
+    assert_eq!(r.read(), 0xFFFF_FFF6);
+    TOP.modify(&r, 0b010);
+    assert_eq!(r.read(), 0x5FFF_FFF6);
+    MIDDLE.modify(&r, 0);
+    assert_eq!(r.read(), 0x5FFF_00F6);
+
+    let r = reg(0);
+    TOP.modify(&r, 0b111);
+    MIDDLE.modify(&r, 0x81);
+    assert_eq!(r.read(), 0xE000_8100);
+}
+
+#[test]
+fn set_and_get() {
+    let r = reg(0x1FFF_FFFF);
+    assert_eq!(TOP.get::<Speed, _>(&r), Some(Speed::Low));
+    TOP.set(&r, Speed::High);
+    assert_eq!(r.read(), 0xBFFF_FFFF);
+    assert_eq!(TOP.get(&r), Some(Speed::High));
+
+    // reserved patterns
+    TOP.modify(&r, 0b111);
+    assert_eq!(TOP.get::<Speed, _>(&r), None);
+}
+
+#[test]
+fn volatile_cell() {
+    let r: Reg<R, VolatileCell<u32>> = Reg::new(VolatileCell::new(0x0000_FF00));
+    HIGH.modify(&r, 1);
+    NIBBLE.modify(&r, 0x3);
+    assert_eq!(r.read(), 0x8000_FF03);
+    assert_eq!(MIDDLE.read(&r), 0xFF);
+    TOP.set(&r, Speed::High);
+    assert_eq!(Access::read(r.cell()), 0xA000_FF03);
+}
+
+// debug builds catch a value wider than the field, release builds mask it
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "value does not fit field"))]
+fn value_masked_to_width() {
+    assert_eq!(NIBBLE.bits(0x1F), 0xF);
+    assert_eq!(HIGH.bits(0b10), 0);
+
+    let r = reg(0);
+    NIBBLE.modify(&r, 0xFF);
+    assert_eq!(r.read(), 0xF);
+    TOP.modify(&r, 0xF);
+    assert_eq!(r.read(), 0xE000_000F);
+}
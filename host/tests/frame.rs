@@ -0,0 +1,223 @@
+//! Round trip and corruption tests of the framing, run with `cargo test`
+
+use std::collections::VecDeque;
+
+use host::frame::{cobs, crc16, Decoder, Error, Kind, Link, Packet, Sink, MAX_FRAME, MAX_PAYLOAD};
+
+fn frame(packet: &Packet) -> Vec<u8> {
+    let mut buf = [0; MAX_FRAME];
+    let n = packet.encode(&mut buf).unwrap();
+    buf[..n].to_vec()
+}
+
+// a decoded packet, as (kind, seq, payload)
+type Owned = (Kind, u8, Vec<u8>);
+
+// all results of feeding `bytes`
+fn decode(bytes: &[u8]) -> Vec<Result<Owned, Error>> {
+    let mut decoder = Decoder::new();
+    let mut results = vec![];
+    for &byte in bytes {
+        if let Some(r) = decoder.feed(byte) {
+            results.push(r.map(|p| (p.kind, p.seq, p.payload.to_vec())));
+        }
+    }
+    results
+}
+
+#[test]
+fn crc_check_value() {
+    assert_eq!(crc16(b"123456789"), 0x29B1);
+}
+
+#[test]
+fn cobs_round_trip() {
+    let inputs: Vec<Vec<u8>> = vec![
+        vec![],
+        vec![0],
+        vec![0, 0],
+        vec![1, 2, 0, 3],
+        (1..=253).collect(),
+        (1..=254).collect(),
+        (1..=255).collect(),
+        (0..=255).cycle().take(600).collect(),
+        vec![0; 300],
+    ];
+
+    for src in inputs {
+        let mut enc = vec![0; cobs::max_encoded_len(src.len())];
+        let n = cobs::encode(&src, &mut enc);
+        assert!(!enc[..n].contains(&0), "zero in encoding of {:?}", src);
+
+        let mut dec = vec![0; src.len()];
+        let m = cobs::decode(&enc[..n], &mut dec).unwrap();
+        assert_eq!(&dec[..m], &src[..]);
+    }
+}
+
+#[test]
+fn packet_round_trip() {
+    for n in [0, 1, 2, 127, MAX_PAYLOAD].iter() {
+        let payload: Vec<u8> = (0..*n as u8).collect();
+        let packet = Packet {
+            kind: Kind::Data,
+            seq: 42,
+            payload: &payload,
+        };
+        let bytes = frame(&packet);
+        assert_eq!(bytes.last(), Some(&0));
+        assert_eq!(decode(&bytes), vec![Ok((Kind::Data, 42, payload.clone()))]);
+    }
+}
+
+#[test]
+fn too_long() {
+    let payload = [1; MAX_PAYLOAD + 1];
+    let packet = Packet {
+        kind: Kind::Data,
+        seq: 0,
+        payload: &payload,
+    };
+    assert_eq!(packet.encode(&mut [0; MAX_FRAME]), Err(Error::TooLong));
+}
+
+#[test]
+fn single_bit_errors_are_detected() {
+    let packet = Packet {
+        kind: Kind::Data,
+        seq: 7,
+        payload: b"hello\0world",
+    };
+    let good = frame(&packet);
+
+    for i in 0..good.len() * 8 {
+        let mut bad = good.clone();
+        bad[i / 8] ^= 1 << (i % 8);
+        // a flipped delimiter merges with the next frame, which resyncs it
+        bad.push(0);
+        assert!(
+            decode(&bad).iter().all(|r| r.is_err()),
+            "bit {} not detected",
+            i
+        );
+    }
+}
+
+#[test]
+fn resync_after_garbage() {
+    let packet = Packet {
+        kind: Kind::Ack,
+        seq: 1,
+        payload: &[],
+    };
+    // line noise, ended by a (possibly corrupted) delimiter
+    let mut bytes = vec![0x55, 0xAA, 0x13, 0x00];
+    bytes.extend(frame(&packet));
+    assert_eq!(
+        decode(&bytes),
+        vec![Err(Error::Cobs), Ok((Kind::Ack, 1, vec![]))]
+    );
+}
+
+// frames in flight from one end to the other
+#[derive(Default)]
+struct Wire {
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl Sink for Wire {
+    fn send(&mut self, frame: &[u8]) {
+        self.frames.push_back(frame.to_vec());
+    }
+}
+
+// delivers the frames on `wire` to `link`, damaging those picked by `damage`
+fn transfer(
+    wire: &mut Wire,
+    link: &mut Link,
+    back: &mut Wire,
+    now: u32,
+    damage: &mut dyn FnMut() -> bool,
+    out: &mut Vec<Vec<u8>>,
+) {
+    while let Some(mut frame) = wire.frames.pop_front() {
+        if damage() {
+            frame[0] ^= 0x10;
+        }
+        for byte in frame {
+            if let Some(payload) = link.receive(byte, now, back) {
+                out.push(payload.to_vec());
+            }
+        }
+    }
+}
+
+#[test]
+fn lossy_link_delivers_in_order_once() {
+    let (mut a, mut b) = (Link::new(), Link::new());
+    let (mut a_to_b, mut b_to_a) = (Wire::default(), Wire::default());
+    let mut received = vec![];
+    let mut ignored = vec![];
+
+    // damage every 3rd frame a -> b, and every 4th b -> a (the `Ack`s)
+    let (mut i, mut j) = (0, 0);
+    let mut every_3rd = || {
+        i += 1;
+        i % 3 == 0
+    };
+    let mut every_4th = || {
+        j += 1;
+        j % 4 == 0
+    };
+
+    let messages: Vec<Vec<u8>> = (0..50u8).map(|k| vec![k; k as usize]).collect();
+    let mut now = 0;
+    for message in &messages {
+        a.send(message, now, &mut a_to_b).unwrap();
+        while !a.is_idle() {
+            transfer(
+                &mut a_to_b,
+                &mut b,
+                &mut b_to_a,
+                now,
+                &mut every_3rd,
+                &mut received,
+            );
+            transfer(
+                &mut b_to_a,
+                &mut a,
+                &mut a_to_b,
+                now,
+                &mut every_4th,
+                &mut ignored,
+            );
+            now += 10;
+            a.poll(now, &mut a_to_b).unwrap();
+        }
+    }
+
+    assert_eq!(received, messages);
+    assert!(ignored.is_empty());
+    assert_eq!(b.stats().delivered, 50);
+    assert!(a.stats().retransmitted > 0 && b.stats().errors > 0);
+}
+
+#[test]
+fn gives_up_without_peer() {
+    let mut a = Link::new();
+    let mut wire = Wire::default();
+    a.send(b"anyone?", 0, &mut wire).unwrap();
+    assert_eq!(a.send(b"again", 0, &mut wire), Err(Error::Busy));
+
+    let mut now = 0;
+    let result = loop {
+        now += 10;
+        if let Err(e) = a.poll(now, &mut wire) {
+            break e;
+        }
+    };
+    assert_eq!(result, Error::Timeout);
+    assert_eq!(wire.frames.len(), 6);
+    assert!(a.is_idle());
+    assert_eq!(a.stats().failed, 1);
+}
@@ -0,0 +1,228 @@
+//! ITM decoder tests, against byte streams as found in `/tmp/itm.fifo`
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use host::itm::{Clock, Decoder, Error, ExceptionAction, Packet, TimestampQuality};
+
+// `iprintln!(stim[0], "Hello, world!")`, word writes and a half word tail
+const HELLO: &[u8] = &[
+    0x03, b'H', b'e', b'l', b'l', //
+    0x03, b'o', b',', b' ', b'w', //
+    0x03, b'o', b'r', b'l', b'd', //
+    0x02, b'!', b'\n',
+];
+
+// sync, then ports 1 and 2, with timestamps and exception trace enabled
+const MIXED: &[u8] = &[
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x80, // sync
+    0x09, b'w', // port 1, one byte
+    0x0E, 0x0F, 0x10, // SysTick (15) entered
+    0xC0, 0x81, 0x01, // local timestamp, 129 cycles
+    0x12, b'e', b'!', // port 2, two bytes
+    0x0E, 0x0F, 0x20, // SysTick exited
+    0x0E, 0x00, 0x30, // return to thread mode
+    0x30, // local timestamp, 3 cycles
+    0x70, // overflow
+    0x17, 0x94, 0x01, 0x00, 0x08, // PC sample 0x0800_0194
+    0x15, 0x00, // PC sample, sleeping
+    0x94, 0xDE, 0x99, 0xAA, 0x21, // global timestamp 1, wrapped
+    0xB4, 0x05, // global timestamp 2
+    0x9F, 0x78, 0x56, 0x34, 0x12, // comparator 1 wrote 0x1234_5678
+    0x5E, 0x00, 0x20, // comparator 1 address 0x2000
+    0x08, // extension, stimulus page 0
+];
+
+fn instrumentation(port: u8, payload: &[u8]) -> Packet {
+    Packet::Instrumentation {
+        port,
+        payload: payload.to_vec(),
+    }
+}
+
+#[test]
+fn hello() {
+    let packets = Decoder::new().decode(HELLO);
+    let text: Vec<u8> = packets
+        .into_iter()
+        .flat_map(|p| match p {
+            Ok(Packet::Instrumentation { port: 0, payload }) => payload,
+            p => panic!("unexpected {:?}", p),
+        })
+        .collect();
+    assert_eq!(text, b"Hello, world!\n");
+}
+
+#[test]
+fn mixed() {
+    let packets: Vec<Packet> = Decoder::new()
+        .decode(MIXED)
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(
+        packets,
+        vec![
+            Packet::Sync,
+            instrumentation(1, b"w"),
+            Packet::ExceptionTrace {
+                number: 15,
+                action: ExceptionAction::Entered
+            },
+            Packet::LocalTimestamp {
+                delta: 129,
+                quality: TimestampQuality::Sync
+            },
+            instrumentation(2, b"e!"),
+            Packet::ExceptionTrace {
+                number: 15,
+                action: ExceptionAction::Exited
+            },
+            Packet::ExceptionTrace {
+                number: 0,
+                action: ExceptionAction::Returned
+            },
+            Packet::LocalTimestamp {
+                delta: 3,
+                quality: TimestampQuality::Sync
+            },
+            Packet::Overflow,
+            Packet::PcSample {
+                pc: Some(0x0800_0194)
+            },
+            Packet::PcSample { pc: None },
+            Packet::GlobalTimestamp1 {
+                value: 0x002A_8CDE,
+                bits: 26,
+                wrap: true,
+                clock_change: false
+            },
+            Packet::GlobalTimestamp2 { value: 5 },
+            Packet::DataTraceValue {
+                comparator: 1,
+                write: true,
+                value: 0x1234_5678,
+                size: 4
+            },
+            Packet::DataTraceAddress {
+                comparator: 1,
+                address: 0x2000
+            },
+            Packet::Extension {
+                sh: false,
+                value: 0
+            },
+        ]
+    );
+}
+
+#[test]
+fn split_anywhere() {
+    let whole = Decoder::new().decode(MIXED);
+    for at in 0..MIXED.len() {
+        let mut decoder = Decoder::new();
+        let mut packets = decoder.decode(&MIXED[..at]);
+        packets.extend(decoder.decode(&MIXED[at..]));
+        assert_eq!(packets, whole, "split at {}", at);
+    }
+}
+
+#[test]
+fn errors() {
+    let mut decoder = Decoder::new();
+    // too few zeros, a reserved header, a runaway timestamp
+    assert_eq!(decoder.decode(&[0, 0, 0x80]), vec![Err(Error::BadSync)]);
+    assert_eq!(decoder.decode(&[0x04]), vec![Err(Error::Reserved(0x04))]);
+    assert_eq!(
+        decoder.decode(&[0xC0, 0x80, 0x80, 0x80, 0x80]),
+        vec![Err(Error::Malformed(0xC0))]
+    );
+    // and back in step
+    assert_eq!(
+        decoder.decode(&[0x01, b'x']),
+        vec![Ok(instrumentation(0, b"x"))]
+    );
+}
+
+#[test]
+fn cli_demultiplexes_ports() {
+    let dir = std::env::temp_dir().join(format!("itm-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (one, two) = (dir.join("one"), dir.join("two"));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_itm"))
+        .arg("-p")
+        .arg("0=-")
+        .arg("-p")
+        .arg(format!("1={}", one.display()))
+        .arg("-p")
+        .arg(format!("2={}", two.display()))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+        stdin.write_all(HELLO).unwrap();
+        stdin.write_all(MIXED).unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello, world!\n");
+    assert_eq!(std::fs::read(&one).unwrap(), b"w");
+    assert_eq!(std::fs::read(&two).unwrap(), b"e!");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn clock() {
+    let mut clock = Clock::new(16_000_000, 4);
+    let timestamp = |delta| Packet::LocalTimestamp {
+        delta,
+        quality: TimestampQuality::Sync,
+    };
+
+    assert!(!clock.feed(&instrumentation(0, b"x")));
+    assert!(clock.feed(&timestamp(4_000_000)));
+    assert!(clock.feed(&timestamp(1)));
+    assert_eq!(clock.cycles(), 16_000_004);
+    assert_eq!(clock.now(), std::time::Duration::new(1, 250));
+    assert!(clock.is_exact());
+
+    clock.feed(&Packet::Overflow);
+    assert!(!clock.is_exact());
+}
+
+#[test]
+fn cli_stamps_lines() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_itm"))
+        .args(["-t", "16000000", "-p", "0=-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+        stdin
+            .write_all(&[
+                0x03, b'h', b'i', b'\n', b'o', // port 0
+                0xC0, 0x80, 0x7D, // 16 000 cycles, 1 ms
+                0x02, b'k', b'\n', // port 0
+                0xC0, 0x80, 0xC8, 0xD0, 0x07, // 16 000 000 cycles, 1 s
+                0x70, // overflow
+                0x01, b'x', 0x01, b'\n', // port 0
+                0x30,  // 3 cycles
+            ])
+            .unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[   0.001000] hi\n[   0.001000] ok\n[~  1.001000] x\n"
+    );
+}
@@ -0,0 +1,85 @@
+//! Log record tests, as written by `src/log.rs`
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use host::log::{Level, Lines, Record};
+
+#[test]
+fn parse() {
+    let record = Record::parse("W app::serial: overrun: 3").unwrap();
+    assert_eq!(record.level, Level::Warn);
+    assert_eq!(record.module, "app::serial");
+    assert_eq!(record.message, "overrun: 3");
+
+    // plain `iprintln!` output
+    assert_eq!(Record::parse("Hello, world!"), None);
+    assert_eq!(Record::parse("I am here: now"), None);
+    assert_eq!(Record::parse("X app: unknown level"), None);
+    assert_eq!(Record::parse(""), None);
+}
+
+#[test]
+fn render() {
+    let record = Record::parse("E app: failed").unwrap();
+    assert_eq!(record.render(false), "ERROR app failed");
+    assert_eq!(
+        Record::parse("I app: ok").unwrap().render(false),
+        "INFO  app ok"
+    );
+    assert_eq!(
+        record.render(true),
+        "\x1b[1;31mERROR\x1b[0m \x1b[2mapp\x1b[0m failed"
+    );
+}
+
+#[test]
+fn levels() {
+    assert!(Level::Error < Level::Warn && Level::Debug < Level::Trace);
+    assert_eq!("warn".parse(), Ok(Level::Warn));
+    assert_eq!("TRACE".parse(), Ok(Level::Trace));
+    assert!("verbose".parse::<Level>().is_err());
+}
+
+#[test]
+fn lines() {
+    let mut lines = Lines::new();
+    assert!(lines.push(b"I ap").is_empty());
+    assert_eq!(
+        lines.push(b"p: a\r\nI app: b\nI"),
+        vec!["I app: a", "I app: b"]
+    );
+    assert_eq!(lines.push(b" app: c\n"), vec!["I app: c"]);
+}
+
+// ITM instrumentation packets, one byte each, for `text` on `port`
+fn stim(port: u8, text: &str) -> Vec<u8> {
+    text.bytes().flat_map(|b| vec![port << 3 | 1, b]).collect()
+}
+
+#[test]
+fn cli_filters_by_level() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_itm"))
+        .args(["-l", "warn", "-n", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+        stdin.write_all(&stim(0, "I app: started\n")).unwrap();
+        stdin
+            .write_all(&stim(1, "W app::serial: overrun\n"))
+            .unwrap();
+        stdin.write_all(&stim(2, "D app: idle\n")).unwrap();
+        stdin.write_all(&stim(0, "Hello, world!\n")).unwrap();
+        stdin.write_all(&stim(1, "E app: failed\n")).unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "WARN  app::serial overrun\nHello, world!\nERROR app failed\n"
+    );
+}
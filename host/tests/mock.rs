@@ -0,0 +1,88 @@
+//! Driver code against the in-memory register file, see `src/mock.rs`
+
+use host::led;
+use host::stm32f40x::{
+    address::{GPIOA_BASE, GPIOA_BSRR, GPIOA_MODER, RCC_AHB1ENR, RCC_BASE},
+    gpio::{moder, Mode},
+    GPIOA, RCC,
+};
+use host::trace::{check, Dir, Recorder, RULES};
+
+#[test]
+fn reset_values() {
+    let rec: Recorder<8> = Recorder::new();
+    let (rcc, gpioa) = (RCC::mock(&rec), GPIOA::mock(&rec));
+
+    assert_eq!(rcc.AHB1ENR.peek(), 0);
+    assert_eq!(rcc.CR.peek(), 0x0000_0083);
+    assert_eq!(gpioa.MODER.peek(), 0xA800_0000);
+    assert_eq!(rcc.AHB1ENR.addr(), RCC_AHB1ENR);
+    assert_eq!(gpioa.AFR[1].addr(), GPIOA_BASE + 0x24);
+    assert_eq!(rcc.CSR.addr(), RCC_BASE + 0x74);
+    // peeking is not an access
+    assert!(rec.is_empty());
+}
+
+#[test]
+fn led_init() {
+    let rec: Recorder<64> = Recorder::new();
+    let (rcc, gpioa) = (RCC::mock(&rec), GPIOA::mock(&rec));
+
+    led::init(&rcc, &gpioa);
+
+    // GPIOA powered, PA5 output, the debug pins (PA13-15) left alone
+    assert_eq!(rcc.AHB1ENR.peek(), 0x0000_0001);
+    assert_eq!(gpioa.MODER.peek(), 0xA800_0400);
+
+    // the port is powered before it is touched
+    let enabled = rec.position(|e| e.is_write_to(RCC_AHB1ENR) && e.value & 1 != 0);
+    let first = rec.position(|e| e.addr & !0x3FF == GPIOA_BASE);
+    assert_eq!(enabled, Some(1));
+    assert!(enabled < first);
+    assert_eq!(check(RULES, rec.events()), None);
+
+    // read-modify-write of both registers, nothing else
+    let accesses: Vec<_> = rec.events().map(|e| (e.addr, e.dir)).collect();
+    assert_eq!(
+        accesses,
+        [
+            (RCC_AHB1ENR, Dir::Read),
+            (RCC_AHB1ENR, Dir::Write),
+            (GPIOA_MODER, Dir::Read),
+            (GPIOA_MODER, Dir::Write),
+        ]
+    );
+    assert_eq!(moder::MODER5.get(&gpioa.MODER), Some(Mode::Output));
+}
+
+#[test]
+fn led_on_off() {
+    let rec: Recorder<64> = Recorder::new();
+    let (rcc, gpioa) = (RCC::mock(&rec), GPIOA::mock(&rec));
+
+    led::init(&rcc, &gpioa);
+    rec.clear();
+    led::on(&gpioa);
+    led::off(&gpioa);
+
+    let writes: Vec<_> = rec.events().map(|e| (e.addr, e.value, e.dir)).collect();
+    assert_eq!(
+        writes,
+        [
+            (GPIOA_BSRR, 1 << 5, Dir::Write),
+            (GPIOA_BSRR, 1 << (16 + 5), Dir::Write),
+        ]
+    );
+}
+
+#[test]
+fn led_init_without_clock() {
+    let rec: Recorder<64> = Recorder::new();
+    let gpioa = GPIOA::mock(&rec);
+
+    // configuring the pin alone is caught
+    moder::MODER5.set(&gpioa.MODER, Mode::Output);
+    let violation = check(RULES, rec.events()).unwrap();
+    assert_eq!(violation.index, 0);
+    assert_eq!(violation.event.addr, GPIOA_MODER);
+}
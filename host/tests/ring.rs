@@ -0,0 +1,109 @@
+//! Ring buffer tests, single threaded and with the ends on two threads
+
+use std::thread;
+
+use host::ring::Queue;
+
+#[test]
+fn empty() {
+    let mut q: Queue<u8, 4> = Queue::new();
+    assert_eq!(q.capacity(), 4);
+    assert!(q.is_empty());
+
+    let (p, mut c) = q.split();
+    assert!(p.is_empty() && !p.is_full());
+    assert_eq!(c.peek(), None);
+    assert_eq!(c.dequeue(), None);
+    assert_eq!(c.len(), 0);
+}
+
+#[test]
+fn full() {
+    let mut q: Queue<u8, 4> = Queue::new();
+    let (mut p, mut c) = q.split();
+    for i in 0..4 {
+        assert_eq!(p.enqueue(i), Ok(()));
+    }
+    assert!(p.is_full());
+    assert_eq!(p.len(), 4);
+    // given back, the queue unchanged
+    assert_eq!(p.enqueue(4), Err(4));
+    assert_eq!(c.len(), 4);
+
+    assert_eq!(c.dequeue(), Some(0));
+    assert!(!p.is_full());
+    assert_eq!(p.enqueue(4), Ok(()));
+    assert_eq!(
+        (0..4).map(|_| c.dequeue()).collect::<Vec<_>>(),
+        [Some(1), Some(2), Some(3), Some(4)]
+    );
+    assert!(c.is_empty());
+}
+
+#[test]
+fn wrap_around() {
+    // the indices run modulo 2 * N, many times over
+    let mut q: Queue<u32, 3> = Queue::new();
+    let (mut p, mut c) = q.split();
+    let mut next = 0;
+    for round in 0..100 {
+        let n = round % 4;
+        for i in 0..n {
+            assert_eq!(
+                p.enqueue(next + i),
+                if i < 3 { Ok(()) } else { Err(next + i) }
+            );
+        }
+        let stored = n.min(3);
+        assert_eq!(c.len() as u32, stored);
+        for i in 0..stored {
+            assert_eq!(c.dequeue(), Some(next + i));
+        }
+        assert_eq!(c.dequeue(), None);
+        next += stored;
+    }
+}
+
+#[test]
+fn peek() {
+    let mut q: Queue<char, 2> = Queue::new();
+    let (mut p, mut c) = q.split();
+    p.enqueue('a').unwrap();
+    p.enqueue('b').unwrap();
+    assert_eq!(c.peek(), Some('a'));
+    assert_eq!(c.peek(), Some('a'));
+    assert_eq!(c.len(), 2);
+    assert_eq!(c.dequeue(), Some('a'));
+    assert_eq!(c.peek(), Some('b'));
+    assert_eq!(c.dequeue(), Some('b'));
+    assert_eq!(c.peek(), None);
+}
+
+#[test]
+fn split_threads() {
+    const COUNT: u32 = 100_000;
+    let q: &'static mut Queue<u32, 8> = Box::leak(Box::new(Queue::new()));
+    let (mut p, mut c) = q.split();
+
+    let producer = thread::spawn(move || {
+        for i in 0..COUNT {
+            while p.enqueue(i).is_err() {
+                thread::yield_now();
+            }
+        }
+    });
+
+    // in order, none lost or repeated
+    let mut expected = 0;
+    while expected < COUNT {
+        match c.dequeue() {
+            Some(value) => {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+            None => thread::yield_now(),
+        }
+    }
+    producer.join().unwrap();
+    assert!(c.is_empty());
+}
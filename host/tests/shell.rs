@@ -0,0 +1,285 @@
+//! Shell tests, feeding terminal input and checking the echo and output
+
+use std::collections::HashMap;
+
+use host::clocks::{self, Clocks};
+use host::shell::{self, Args, Error, Shell, Target, MAX_ARGS};
+use host::time::{Duration, U32Ext as _};
+
+const UP: &[u8] = b"\x1b[A";
+const DOWN: &[u8] = b"\x1b[B";
+const ERASE: &str = "\x08 \x08";
+const LINE: usize = 40;
+
+#[derive(Default)]
+struct Mock {
+    led: Option<bool>,
+    memory: HashMap<u32, u32>,
+    resets: usize,
+}
+
+impl Target for Mock {
+    fn led(&mut self, on: bool) {
+        self.led = Some(on);
+    }
+
+    fn peek(&mut self, address: u32) -> u32 {
+        self.memory.get(&address).copied().unwrap_or(0)
+    }
+
+    fn poke(&mut self, address: u32, value: u32) {
+        self.memory.insert(address, value);
+    }
+
+    fn clocks(&self) -> Clocks {
+        clocks::RESET
+    }
+
+    fn reset(&mut self) {
+        self.resets += 1;
+    }
+
+    fn uptime(&self) -> Duration {
+        12_345.ms()
+    }
+}
+
+// feeds `input`, returning what the shell echoed and printed
+fn feed(shell: &mut Shell<LINE, 2>, target: &mut Mock, input: &[u8]) -> String {
+    let mut out = String::new();
+    for &byte in input {
+        shell.feed(byte, target, &mut out).unwrap();
+    }
+    out
+}
+
+fn shell() -> (Shell<LINE, 2>, Mock) {
+    (Shell::new(shell::COMMANDS), Mock::default())
+}
+
+#[test]
+fn line_endings() {
+    let (mut shell, mut target) = shell();
+    assert_eq!(feed(&mut shell, &mut target, b"led on\r"), "led on\r\n> ");
+    assert_eq!(target.led, Some(true));
+    assert_eq!(feed(&mut shell, &mut target, b"led off\n"), "led off\r\n> ");
+    assert_eq!(target.led, Some(false));
+
+    // the LF of CR LF is not another (empty) line
+    assert_eq!(feed(&mut shell, &mut target, b"reset\r\n"), "reset\r\n> ");
+    assert_eq!(target.resets, 1);
+    // but LF LF and CR CR are two
+    assert_eq!(feed(&mut shell, &mut target, b"\n\n"), "\r\n> \r\n> ");
+    assert_eq!(feed(&mut shell, &mut target, b"\r\r"), "\r\n> \r\n> ");
+    assert_eq!(target.resets, 1);
+}
+
+#[test]
+fn backspace() {
+    let (mut shell, mut target) = shell();
+    // nothing to erase at column 0, nothing echoed
+    assert_eq!(feed(&mut shell, &mut target, b"\x08\x7f"), "");
+    assert_eq!(shell.line(), "");
+
+    let out = feed(&mut shell, &mut target, b"lex\x7fd\x08\x08ed");
+    assert_eq!(out, format!("lex{}d{}{}ed", ERASE, ERASE, ERASE));
+    assert_eq!(shell.line(), "led");
+    assert_eq!(
+        feed(&mut shell, &mut target, b"\x08\x08\x08\x08"),
+        ERASE.repeat(3)
+    );
+    assert_eq!(shell.line(), "");
+}
+
+#[test]
+fn ctrl_c() {
+    let (mut shell, mut target) = shell();
+    assert_eq!(feed(&mut shell, &mut target, b"reset\x03"), "reset^C\r\n> ");
+    assert_eq!(shell.line(), "");
+    assert_eq!(target.resets, 0);
+}
+
+#[test]
+fn history() {
+    let (mut shell, mut target) = shell();
+    // nothing to go back to
+    assert_eq!(feed(&mut shell, &mut target, UP), "\x07");
+
+    feed(&mut shell, &mut target, b"led on\r");
+    feed(&mut shell, &mut target, b"uptime\r");
+    // empty lines and repeats are not remembered
+    feed(&mut shell, &mut target, b"\r  \ruptime\r");
+
+    assert_eq!(feed(&mut shell, &mut target, UP), "uptime");
+    assert_eq!(
+        feed(&mut shell, &mut target, UP),
+        format!("{}led on", ERASE.repeat(6))
+    );
+    assert_eq!(shell.line(), "led on");
+    // the oldest reached
+    assert_eq!(feed(&mut shell, &mut target, UP), "\x07");
+    assert_eq!(
+        feed(&mut shell, &mut target, DOWN),
+        format!("{}uptime", ERASE.repeat(6))
+    );
+    // back to the (empty) line being edited, and no further
+    assert_eq!(feed(&mut shell, &mut target, DOWN), ERASE.repeat(6));
+    assert_eq!(feed(&mut shell, &mut target, DOWN), "\x07");
+
+    // an entry recalled, edited and run
+    feed(&mut shell, &mut target, UP);
+    feed(&mut shell, &mut target, UP);
+    let out = feed(&mut shell, &mut target, b"\x7fff\r");
+    assert!(out.ends_with("f\r\n> "), "{:?}", out);
+    assert_eq!(target.led, Some(false));
+
+    // `H` = 2 lines kept, "led on" dropped
+    feed(&mut shell, &mut target, UP);
+    feed(&mut shell, &mut target, UP);
+    assert_eq!(shell.line(), "uptime");
+    assert_eq!(feed(&mut shell, &mut target, UP), "\x07");
+
+    // other escape sequences are ignored
+    feed(&mut shell, &mut target, b"\x03");
+    assert_eq!(feed(&mut shell, &mut target, b"\x1b[C\x1b[D"), "");
+    assert_eq!(shell.line(), "");
+}
+
+#[test]
+fn overflow() {
+    let (mut shell, mut target) = shell();
+    let line = format!("poke 0x2000_0000 {}", "1".repeat(LINE - 17));
+    let out = feed(&mut shell, &mut target, format!("{}23", line).as_bytes());
+    // `LINE` bytes taken, the rest rung
+    assert_eq!(out, format!("{}\x07\x07", line));
+    assert_eq!(shell.line(), line);
+    // as are control characters
+    assert_eq!(
+        feed(&mut shell, &mut target, b"\x7f\x01\t"),
+        format!("{}\x07\x07", ERASE)
+    );
+    assert_eq!(
+        feed(&mut shell, &mut target, b"\r"),
+        "\r\nerror: not a number\r\n> "
+    );
+    assert!(target.memory.is_empty());
+}
+
+#[test]
+fn errors() {
+    let (mut shell, mut target) = shell();
+    assert_eq!(
+        feed(&mut shell, &mut target, b"blink\r"),
+        "blink\r\nerror: unknown command, try `help`\r\n> "
+    );
+    assert_eq!(
+        feed(&mut shell, &mut target, b"led\r"),
+        "led\r\nusage: led on|off\r\n> "
+    );
+    assert_eq!(
+        feed(&mut shell, &mut target, b"led dim\r"),
+        "led dim\r\nusage: led on|off\r\n> "
+    );
+    assert_eq!(
+        feed(&mut shell, &mut target, b"reset now\r"),
+        "reset now\r\nusage: reset\r\n> "
+    );
+    assert_eq!(target.led, None);
+    assert_eq!(target.resets, 0);
+}
+
+#[test]
+fn peek() {
+    let (mut shell, mut target) = shell();
+    target.memory.insert(0x4002_3830, 0x0010_0001);
+    for input in [
+        "peek 0x4002_3830",
+        "peek 1073887280",
+        "peek 0b1000000000000100011100000110000",
+    ]
+    .iter()
+    {
+        let out = feed(&mut shell, &mut target, format!("{}\r", input).as_bytes());
+        let expected = format!("{}\r\n0x40023830: 0x00100001\r\n> ", input);
+        assert_eq!(out, expected);
+    }
+
+    let mut out = String::new();
+    let mut run = |line: &str| {
+        out.clear();
+        shell::dispatch(shell::COMMANDS, line, &mut target, &mut out).map(|()| out.clone())
+    };
+    assert_eq!(
+        run("peek 0x20000000"),
+        Ok("0x20000000: 0x00000000\r\n".into())
+    );
+    assert_eq!(run("peek 0x4002_3832"), Err(Error::Unaligned));
+    assert_eq!(run("peek 6"), Err(Error::Unaligned));
+    assert_eq!(run("peek 0x"), Err(Error::Number));
+    assert_eq!(run("peek 0x1_0000_0000"), Err(Error::Number));
+    assert_eq!(run("peek 12ab"), Err(Error::Number));
+    assert_eq!(run("peek -4"), Err(Error::Number));
+    assert_eq!(run("peek"), Err(Error::Usage));
+    assert_eq!(run("peek 0 4"), Err(Error::Usage));
+}
+
+#[test]
+fn errors_reported() {
+    let (mut shell, mut target) = shell();
+    assert_eq!(
+        feed(&mut shell, &mut target, b"peek 0x2\r"),
+        "peek 0x2\r\nerror: address not word aligned\r\n> "
+    );
+    assert_eq!(
+        feed(&mut shell, &mut target, b"peek zero\r"),
+        "peek zero\r\nerror: not a number\r\n> "
+    );
+    assert_eq!(
+        feed(&mut shell, &mut target, b"a b c d e f g h i\r"),
+        "a b c d e f g h i\r\nerror: too many arguments\r\n> "
+    );
+}
+
+#[test]
+fn poke() {
+    let (mut shell, mut target) = shell();
+    assert_eq!(
+        feed(&mut shell, &mut target, b"poke 0x20000004 0b101\r"),
+        "poke 0x20000004 0b101\r\n> "
+    );
+    assert_eq!(target.memory.get(&0x2000_0004), Some(&5));
+    assert_eq!(
+        feed(&mut shell, &mut target, b"poke 0x20000004\r"),
+        "poke 0x20000004\r\nusage: poke <address> <value>\r\n> "
+    );
+}
+
+#[test]
+fn commands() {
+    let (mut shell, mut target) = shell();
+    assert_eq!(
+        feed(&mut shell, &mut target, b"uptime\r"),
+        "uptime\r\n12.345 s\r\n> "
+    );
+    let out = feed(&mut shell, &mut target, b"clocks\r");
+    assert_eq!(
+        out,
+        "clocks\r\nsysclk 16000000 Hz\r\nhclk   16000000 Hz\r\npclk1  16000000 Hz\r\npclk2  16000000 Hz\r\n> "
+    );
+    let out = feed(&mut shell, &mut target, b"help\r");
+    assert_eq!(out.matches("\r\n").count(), 1 + shell::COMMANDS.len());
+    assert!(out.contains("peek <address>           read a word\r\n"));
+}
+
+#[test]
+fn args() {
+    let args = Args::parse("  poke\t0x0  1 ").unwrap();
+    assert_eq!(args.as_slice(), ["poke", "0x0", "1"]);
+    assert_eq!(args.get(3), None);
+    assert!(Args::parse("").unwrap().is_empty());
+    assert_eq!(Args::parse(&"a ".repeat(MAX_ARGS)).unwrap().len(), MAX_ARGS);
+    assert_eq!(
+        Args::parse(&"a ".repeat(MAX_ARGS + 1)),
+        Err(Error::TooManyArguments)
+    );
+}
@@ -0,0 +1,189 @@
+//! Response time analysis tests, and the TOML subset it reads
+
+use std::process::Command;
+
+use host::srp::{table, Error, Lock, System, Task};
+use host::toml::{self, Value};
+
+const SYSTEM: &str = r#"
+# three tasks sharing `r`
+[[task]]
+name = "a"
+priority = 3
+period = 10
+wcet = 2
+
+[[task]]
+name = "b"
+priority = 2
+period = 20
+wcet = 4
+
+[[task]]
+name = "c"   # the background work
+priority = 1
+period = 0x32
+wcet = 10
+
+[[lock]]
+task = "b"
+resource = "r"
+ceiling = 3
+wcet = 1
+
+[[lock]]
+task = "c"
+resource = "r"
+ceiling = 3
+wcet = 3
+"#;
+
+// as written by `examples/rtfm_wcet.rs`, timestamped by `itm -t`
+const REPORT: &str = "\
+[   2.100000] wcet task sample: priority=2 period=16000 n=2100 max=210 avg=190
+[   2.100010] wcet task blink: priority=1 period=8000000 n=4 max=1843 avg=1790
+[   2.100020] wcet lock blink.count: ceiling=2 n=4 max=112 avg=108
+";
+
+#[test]
+fn toml() {
+    let tables =
+        toml::parse("x = -1_000\n[t]\ns = \"a # \\\"b\\\"\" # c\n[[u]]\n[[u]]\nb = true\n")
+            .unwrap();
+    assert_eq!(tables.len(), 4);
+    assert_eq!(tables[0].get("x"), Some(&Value::Integer(-1000)));
+    assert_eq!(tables[1].name, "t");
+    assert_eq!(
+        tables[1].get("s"),
+        Some(&Value::String("a # \"b\"".to_string()))
+    );
+    assert!(tables[2].array && tables[2].pairs.is_empty());
+    assert_eq!(tables[3].get("b"), Some(&Value::Boolean(true)));
+
+    for (text, line) in [
+        ("[t]\n[t]", 2),
+        ("a = 1\na = 2", 2),
+        ("\n\na = 1__0", 3),
+        ("a = \"b", 1),
+        ("a", 1),
+        ("[a b]", 1),
+        ("a = [1, 2]", 1),
+    ] {
+        assert_eq!(toml::parse(text).unwrap_err().line, line, "{}", text);
+    }
+}
+
+#[test]
+fn analyse() {
+    let system = System::from_toml(SYSTEM).unwrap();
+    assert_eq!(system.tasks[2].period, Some(50));
+
+    let responses = system.analyse().unwrap();
+    let times: Vec<_> = responses
+        .iter()
+        .map(|r| (r.name.as_str(), r.blocking, r.response))
+        .collect();
+    assert_eq!(
+        times,
+        [("a", 3, Some(5)), ("b", 3, Some(9)), ("c", 0, Some(18))]
+    );
+
+    // with a tighter deadline `c` misses it
+    let mut tight = System::from_toml(SYSTEM).unwrap();
+    tight.add_task(Task {
+        name: "c".to_string(),
+        priority: None,
+        period: None,
+        deadline: Some(15),
+        wcet: None,
+    });
+    let responses = tight.analyse().unwrap();
+    assert!(!responses[2].meets_deadline());
+    let table = table(&responses);
+    assert!(table.contains("MISSED"), "{}", table);
+    assert!(table.ends_with("utilization 60.0%, 1 of 3 deadlines missed\n"));
+}
+
+#[test]
+fn invalid() {
+    let invalid = |text: &str| match System::from_toml(text).and_then(|s| s.analyse()) {
+        Err(Error::Invalid(e)) => e,
+        other => panic!("{:?}", other),
+    };
+    assert!(invalid("[[task]]\nname = \"a\"\npriority = 1\nwcet = 1").contains("no period"));
+    assert!(invalid("[[task]]\nname = \"a\"\npriority = 256").contains("out of range"));
+    assert!(
+        invalid("[[task]]\nname = \"a\"\nperiod = 1\npriority = 1\nwcet = 1\nwecet = 1")
+            .contains("unknown key")
+    );
+    assert!(
+        invalid("[[lock]]\ntask = \"a\"\nresource = \"r\"\nceiling = 1\nwcet = 1")
+            .contains("unknown task")
+    );
+    assert!(invalid("[tasks]").contains("unknown table"));
+    assert!(matches!(
+        System::from_toml("[[task]]\nname = 1 2"),
+        Err(Error::Toml(_))
+    ));
+}
+
+#[test]
+fn report() {
+    let system = System::from_report(REPORT);
+    assert_eq!(system.tasks.len(), 2);
+    assert_eq!(
+        system.locks,
+        [Lock {
+            task: "blink".to_string(),
+            resource: "count".to_string(),
+            ceiling: 2,
+            wcet: 112,
+        }]
+    );
+
+    let responses = system.analyse().unwrap();
+    // `sample` is blocked by the lock of `blink`
+    assert_eq!(responses[0].blocking, 112);
+    assert_eq!(responses[0].response, Some(210 + 112));
+    assert_eq!(responses[1].blocking, 0);
+    // preempted by `sample` once per 16000 cycles
+    assert_eq!(responses[1].response, Some(1843 + 210));
+
+    // a description tightens the deadline of the measured `blink`
+    let mut merged = System::from_toml("[[task]]\nname = \"blink\"\ndeadline = 2_000").unwrap();
+    merged.merge(system);
+    assert_eq!(merged.tasks[0].deadline, Some(2000));
+    assert_eq!(merged.tasks[0].wcet, Some(1843));
+    assert_eq!(merged.analyse().unwrap()[0].response, None);
+}
+
+#[test]
+fn cli() {
+    let dir = std::env::temp_dir().join(format!("srp-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let system = dir.join("system.toml");
+    let report = dir.join("wcet.txt");
+    std::fs::write(&system, SYSTEM).unwrap();
+    std::fs::write(&report, REPORT).unwrap();
+
+    let srp = env!("CARGO_BIN_EXE_srp");
+    let output = Command::new(srp).arg(&system).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.lines().nth(1).unwrap().starts_with("a "),
+        "{}",
+        stdout
+    );
+    assert!(stdout.ends_with("all deadlines met\n"), "{}", stdout);
+
+    // both systems at once, `sample` then preempts `b`, `c`, ...
+    let output = Command::new(srp)
+        .arg(&system)
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,95 @@
+//! Time unit tests, converting to cycles at the reset and PLL clocks
+
+use host::clocks::{self, Clocks, Config, F401, F411};
+use host::time::{Duration, Error, Hertz, U32Ext as _, SYST_RELOAD_MAX};
+
+fn clocks(sysclk: u32) -> Clocks {
+    let limits = if sysclk > F401.sysclk_max {
+        &F411
+    } else {
+        &F401
+    };
+    Config::hsi().sysclk(sysclk).solve(limits).unwrap().clocks
+}
+
+#[test]
+fn units() {
+    assert_eq!(1.s(), Duration::from_millis(1_000));
+    assert_eq!(1.ms(), Duration::from_micros(1_000));
+    assert_eq!(1_500.us().as_millis(), 1);
+    assert_eq!(u32::MAX.s().as_micros(), u32::MAX as u64 * 1_000_000);
+    assert_eq!(115_200.hz(), Hertz(115_200));
+    assert_eq!(48.khz(), Hertz(48_000));
+    assert_eq!(84.mhz(), Hertz(84_000_000));
+    assert_eq!(4_294.mhz(), Hertz(4_294_000_000));
+}
+
+#[test]
+#[should_panic(expected = "frequency overflow")]
+fn khz_overflow() {
+    let _ = 4_294_968.khz();
+}
+
+#[test]
+#[should_panic(expected = "frequency overflow")]
+fn mhz_overflow() {
+    let _ = 4_295.mhz();
+}
+
+#[test]
+fn cycles() {
+    let reset = &clocks::RESET;
+    assert_eq!(1.s().cycles(reset), Ok(16_000_000));
+    assert_eq!(1.us().cycles(reset), Ok(16));
+    assert_eq!(0.us().cycles(reset), Ok(0));
+
+    let fast = clocks(84_000_000);
+    assert_eq!(1.s().cycles(&fast), Ok(84_000_000));
+    assert_eq!(1_500.ms().cycles(&fast), Ok(126_000_000));
+    assert_eq!(1_000_001.us().cycles(&fast), Ok(84_000_084));
+
+    // rounded down
+    let odd = clocks(100_000_000);
+    assert_eq!(odd.sysclk(), 100_000_000);
+    assert_eq!(7.us().cycles(&odd), Ok(700));
+}
+
+#[test]
+fn cycles_overflow() {
+    let fast = clocks(84_000_000);
+    // the largest that fits, u32::MAX / 84 MHz = 51.13 s
+    assert_eq!(51.s().cycles(&fast), Ok(4_284_000_000));
+    assert_eq!(51_130.ms().cycles(&fast), Ok(4_294_920_000));
+    assert_eq!(52.s().cycles(&fast), Err(Error::Overflow));
+
+    // beyond the range of `micros * sysclk` in a u64
+    assert_eq!(300_000.s().cycles(&fast), Err(Error::Overflow));
+    assert_eq!(u32::MAX.s().cycles(&fast), Err(Error::Overflow));
+    assert_eq!(u32::MAX.ms().cycles(&fast), Err(Error::Overflow));
+}
+
+#[test]
+fn frequency_cycles() {
+    let reset = &clocks::RESET;
+    assert_eq!(1.khz().cycles(reset), Ok(16_000));
+    assert_eq!(16.mhz().cycles(reset), Ok(1));
+    assert_eq!(17.mhz().cycles(reset), Err(Error::Zero));
+    assert_eq!(0.hz().cycles(reset), Err(Error::Zero));
+}
+
+#[test]
+fn systick_reload() {
+    let reset = &clocks::RESET;
+    assert_eq!(1.s().systick_reload(reset), Ok(15_999_999));
+    assert_eq!(1.ms().systick_reload(reset), Ok(15_999));
+    assert_eq!(0.ms().systick_reload(reset), Err(Error::Zero));
+
+    // 2^24 cycles at most, 1.048576 s at 16 MHz
+    assert_eq!(1_048_576.us().systick_reload(reset), Ok(SYST_RELOAD_MAX));
+    assert_eq!(1_048_577.us().systick_reload(reset), Err(Error::Overflow));
+    assert_eq!(
+        1.s().systick_reload(&clocks(84_000_000)),
+        Err(Error::Overflow)
+    );
+    assert_eq!(300_000.s().systick_reload(reset), Err(Error::Overflow));
+}
@@ -0,0 +1,142 @@
+//! Register access recorder and clock-enable rule tests
+
+use host::stm32f40x::address::{AHB1PERIPH_BASE, GPIOA_BSRR, GPIOA_MODER, RCC_AHB1ENR};
+use host::trace::{check, Checker, Dir, Event, Record, Recorder, Rule, RULES};
+
+fn write(addr: u32, value: u32) -> Event {
+    Event {
+        addr,
+        value,
+        dir: Dir::Write,
+    }
+}
+
+fn read(addr: u32, value: u32) -> Event {
+    Event {
+        addr,
+        value,
+        dir: Dir::Read,
+    }
+}
+
+#[test]
+fn recorder() {
+    let rec: Recorder<4> = Recorder::new();
+    assert!(rec.is_empty());
+    assert_eq!(rec.events().count(), 0);
+
+    for value in 0..3 {
+        rec.record(write(GPIOA_BSRR, value));
+    }
+    assert_eq!(rec.len(), 3);
+    assert_eq!(rec.dropped(), 0);
+    assert_eq!(
+        rec.position(|e| e.is_write_to(GPIOA_BSRR) && e.value == 2),
+        Some(2)
+    );
+    assert_eq!(rec.position(|e| e.is_write_to(GPIOA_MODER)), None);
+}
+
+#[test]
+fn recorder_wraps() {
+    let rec: Recorder<4> = Recorder::new();
+    for value in 0..10 {
+        rec.record(write(GPIOA_BSRR, value));
+    }
+    // the last 4, oldest first
+    assert_eq!(rec.len(), 4);
+    assert_eq!(rec.dropped(), 6);
+    let values: Vec<u32> = rec.events().map(|e| e.value).collect();
+    assert_eq!(values, [6, 7, 8, 9]);
+    assert_eq!(rec.position(|e| e.value == 8), Some(2));
+
+    rec.clear();
+    assert!(rec.is_empty());
+    assert_eq!(rec.dropped(), 0);
+    rec.record(read(GPIOA_MODER, 0));
+    assert_eq!(rec.events().collect::<Vec<_>>(), [read(GPIOA_MODER, 0)]);
+}
+
+#[test]
+fn moder_before_gpioaen() {
+    let events = [
+        read(GPIOA_MODER, 0xA800_0000),
+        write(GPIOA_MODER, 0xA800_0400),
+        write(RCC_AHB1ENR, 1),
+    ];
+    let violation = check(RULES, events.iter().copied()).unwrap();
+    assert_eq!(violation.index, 0);
+    assert_eq!(violation.rule.name, "GPIOA");
+
+    // a write only
+    let violation = check(RULES, events[1..].iter().copied()).unwrap();
+    assert_eq!(violation.index, 0);
+    assert_eq!(violation.event, write(GPIOA_MODER, 0xA800_0400));
+}
+
+#[test]
+fn gpioaen_before_moder() {
+    let events = [
+        read(RCC_AHB1ENR, 0),
+        write(RCC_AHB1ENR, 1),
+        read(GPIOA_MODER, 0xA800_0000),
+        write(GPIOA_MODER, 0xA800_0400),
+    ];
+    assert_eq!(check(RULES, events.iter().copied()), None);
+}
+
+#[test]
+fn clock_disabled_again() {
+    let events = [
+        write(RCC_AHB1ENR, 1),
+        write(GPIOA_MODER, 0xA800_0400),
+        // GPIOB on, GPIOA off
+        write(RCC_AHB1ENR, 0b10),
+        write(GPIOA_BSRR, 1 << 5),
+    ];
+    let violation = check(RULES, events.iter().copied()).unwrap();
+    assert_eq!(violation.index, 3);
+
+    // online, the violation of one access does not stop the checker
+    let mut checker = Checker::new(RULES);
+    let results: Vec<bool> = events.iter().map(|&e| checker.check(e).is_ok()).collect();
+    assert_eq!(results, [true, true, true, false]);
+    assert!(checker.check(write(AHB1PERIPH_BASE + 0x400, 0)).is_ok());
+}
+
+#[test]
+fn outside_of_rules() {
+    // RCC itself, and the APB1 peripherals, are not covered
+    let events = [write(RCC_AHB1ENR, 0), write(0x4000_4400, 0)];
+    assert_eq!(check(RULES, events.iter().copied()), None);
+
+    let gpioa = RULES[0];
+    assert!(gpioa.covers(GPIOA_MODER));
+    assert!(gpioa.covers(AHB1PERIPH_BASE + 0x3FF));
+    assert!(!gpioa.covers(AHB1PERIPH_BASE + 0x400));
+    assert!(!gpioa.covers(AHB1PERIPH_BASE - 1));
+}
+
+#[test]
+fn many_enable_registers() {
+    // six peripherals, each enabled by a register of its own
+    let rule = |i: u32| Rule {
+        name: "P",
+        base: 0x1000 * (i + 1),
+        size: 0x100,
+        enable: 0x100 + 4 * i,
+        bit: i as u8,
+    };
+    let rules = [rule(0), rule(1), rule(2), rule(3), rule(4), rule(5)];
+
+    let mut events = vec![];
+    for (i, r) in rules.iter().enumerate() {
+        events.push(write(r.enable, 1 << i));
+        events.push(write(r.base, 0));
+    }
+    assert_eq!(check(&rules, events.iter().copied()), None);
+
+    // the last one left disabled
+    events.remove(10);
+    assert_eq!(check(&rules, events.iter().copied()).unwrap().index, 10);
+}
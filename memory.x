@@ -2,10 +2,36 @@ MEMORY
 {
   /* NOTE K = KiBi = 1024 bytes */
   FLASH : ORIGIN = 0x08000000, LENGTH = 64K 
-  RAM : ORIGIN = 0x20000000, LENGTH = 32K
+  RAM : ORIGIN = 0x20000000, LENGTH = 31K
+  /* crash records and reboot counters (`src/crash.rs`, `src/reboot.rs`), */
+  /* kept over resets */
+  NOINIT : ORIGIN = 0x20007C00, LENGTH = 1K
 }
 
 /* This is where the call stack will be allocated. */
 /* The stack is of the full descending type. */
 /* NOTE Do NOT modify `_stack_start` unless you know what you are doing */
-_stack_start = ORIGIN(RAM) + LENGTH(RAM);
\ No newline at end of file
+_stack_start = ORIGIN(RAM) + LENGTH(RAM);
+
+/* Interned log strings (`log-deferred`, see `src/defer.rs`), kept in the */
+/* ELF for the host decoder but not loaded to FLASH */
+SECTIONS
+{
+  .log 0 (INFO) :
+  {
+    *(.log .log.*);
+  }
+}
+
+/* Not initialized at boot, so what a crash handler wrote is still there */
+/* after the reset */
+SECTIONS
+{
+  .noinit (NOLOAD) : ALIGN(4)
+  {
+    *(.noinit .noinit.*);
+  } > NOINIT
+}
+
+/* string indices are 16 bits */
+ASSERT(SIZEOF(.log) <= 0x10000, "too many interned log strings");
@@ -0,0 +1,136 @@
+//! Backtraces, walking the frame pointer chain
+//!
+//! `panic_itm` reports where a panic happened, not how the program got
+//! there. With frame pointers (`-C force-frame-pointers=yes`, see
+//! `.cargo/config`), every function starts by pushing `r7` and `lr` and
+//! points `r7` at the pair, so the stack holds a chain of frame records:
+//!
+//! ``` text
+//! r7 -> | caller r7 | return address | ... | caller r7 | return address | ...
+//! ```
+//!
+//! `Backtrace::capture` follows it from its caller up, collecting the return
+//! addresses, at most `DEPTH`. An exception entry (`lr` an `EXC_RETURN`) is
+//! followed through the exception frame, stacked by the hardware above the
+//! record of the handler, taking its `pc`: the backtrace of a `HardFault`
+//! continues with the faulting instruction and its callers.
+//!
+//! ``` ignore
+//! #[panic_handler]
+//! fn panic(info: &PanicInfo) -> ! {
+//!     let stim = unsafe { &mut (*ITM::ptr()).stim[0] };
+//!     iprintln!(stim, "{}", info);
+//!     iprintln!(stim, "{}", Backtrace::capture());
+//!     loop {}
+//! }
+//! ```
+//!
+//! ``` text
+//! panicked at 'index out of bounds: the len is 4 but the index is 8', examples/backtrace.rs:65:5
+//! backtrace: 0x08000a1b 0x080004f7 0x08000529 0x08000541 0x08000561 0x0800058b
+//! ```
+//!
+//! Only addresses are sent, `backtrace` (in `host/`) symbolises them against
+//! the ELF. Return addresses have the Thumb bit set; the `pc` of an exception
+//! frame has it clear, telling the two apart.
+//!
+//! The walk stops at the first record outside of the stack, or not above
+//! the previous one, and at an exception taken from the process stack (PSP).
+//! A fault in the prologue of a function, before its record is pushed, loses
+//! its caller.
+
+use core::arch::asm;
+use core::fmt;
+use core::ptr;
+
+use crate::stm32f40x::address::SRAM_BASE;
+
+/// Return addresses kept.
+pub const DEPTH: usize = 16;
+
+// the `lr` of an exception handler, bit 2 set if returning to the PSP
+const EXC_RETURN: u32 = 0xFFFF_FFE0;
+const EXC_RETURN_PSP: u32 = 1 << 2;
+// the stacked `pc`, in the exception frame
+const FRAME_PC: u32 = 0x18;
+
+extern "C" {
+    // the top of the stack, from `memory.x`
+    static _stack_start: u32;
+}
+
+/// The return addresses of a call chain, innermost first.
+#[derive(Clone, Copy)]
+pub struct Backtrace {
+    addresses: [u32; DEPTH],
+    len: usize,
+}
+
+impl Backtrace {
+    /// The backtrace of the caller.
+    #[inline(never)]
+    pub fn capture() -> Self {
+        let fp: u32;
+        // NOTE(unsafe) reads `r7`, the frame pointer
+        unsafe { asm!("mov {}, r7", out(reg) fp, options(nomem, nostack, preserves_flags)) };
+        // NOTE(unsafe) only reads within the stack
+        unsafe { Self::walk(fp) }
+    }
+
+    /// The backtrace from the frame record at `fp`.
+    ///
+    /// # Safety
+    ///
+    /// Reads the stack from `fp` up, compiled with frame pointers.
+    pub unsafe fn walk(mut fp: u32) -> Self {
+        let top = &_stack_start as *const u32 as u32;
+        let read = |address: u32| ptr::read_volatile(address as *const u32);
+        let mut bt = Backtrace {
+            addresses: [0; DEPTH],
+            len: 0,
+        };
+
+        while bt.len < DEPTH {
+            if fp < SRAM_BASE || fp % 4 != 0 || fp > top - 8 {
+                break;
+            }
+            let (next, lr) = (read(fp), read(fp + 4));
+            if lr & EXC_RETURN == EXC_RETURN {
+                if lr & EXC_RETURN_PSP != 0 {
+                    break;
+                }
+                // the exception frame, just above the record of the handler
+                let frame = fp + 8;
+                if frame + FRAME_PC >= top {
+                    break;
+                }
+                bt.addresses[bt.len] = read(frame + FRAME_PC);
+            } else {
+                bt.addresses[bt.len] = lr;
+            }
+            bt.len += 1;
+
+            // callers are above, a record at or below is not one
+            if next <= fp {
+                break;
+            }
+            fp = next;
+        }
+        bt
+    }
+
+    pub fn addresses(&self) -> &[u32] {
+        &self.addresses[..self.len]
+    }
+}
+
+impl fmt::Display for Backtrace {
+    /// `backtrace:` and the addresses, on one line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("backtrace:")?;
+        for address in self.addresses() {
+            write!(f, " {:#010x}", address)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+//! Cycle counting micro-benchmarks
+//!
+//! Instead of reading `DWT::get_cycle_count()` around the code by hand (as in
+//! `examples/bare2.rs`), register named closures and let a `Runner` warm
+//! them up, time `N` runs each, subtract the cost of the measurement itself
+//! and report the min, median and max cycles:
+//!
+//! ``` ignore
+//! let b = [1u8; 64];
+//! let (mut a, mut c) = ([0u8; 64], [0u8; 64]);
+//!
+//! let runner: Runner<32> = Runner::new(DWT::get_cycle_count);
+//! runner.run(
+//!     &mut [
+//!         Bench::new("index", &mut || for i in 0..64 { a[i] = b[i] }),
+//!         Bench::new("copy_from_slice", &mut || c.copy_from_slice(&b)),
+//!     ],
+//!     &mut Itm(&mut itm.stim[0]),
+//! )?;
+//! ```
+//!
+//! Each result is one line, which `bench` (in `host/`) reads to compare two
+//! runs, e.g., dev vs release:
+//!
+//! ``` text
+//! bench copy_from_slice: n=32 min=93 median=93 max=101 overhead=12
+//! ```
+//!
+//! Samples are taken with interrupts disabled, so the closures must not
+//! wait for an interrupt. The DWT cycle counter must be enabled (see
+//! `DWT::enable_cycle_counter` or `swo::Config::apply`).
+
+use core::fmt::{self, Write};
+
+use cortex_m::{interrupt, itm, peripheral::itm::Stim};
+
+/// Cycle counts of `iterations` runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub iterations: u32,
+    pub min: u32,
+    pub median: u32,
+    pub max: u32,
+}
+
+impl Stats {
+    /// The statistics of `samples` (sorting them), all zero if empty.
+    pub fn from_samples(samples: &mut [u32]) -> Self {
+        samples.sort_unstable();
+        let n = samples.len();
+        if n == 0 {
+            return Stats {
+                iterations: 0,
+                min: 0,
+                median: 0,
+                max: 0,
+            };
+        }
+        let median = if n % 2 == 1 {
+            samples[n / 2]
+        } else {
+            ((samples[n / 2 - 1] as u64 + samples[n / 2] as u64) / 2) as u32
+        };
+        Stats {
+            iterations: n as u32,
+            min: samples[0],
+            median,
+            max: samples[n - 1],
+        }
+    }
+}
+
+/// A named benchmark.
+pub struct Bench<'a> {
+    pub name: &'static str,
+    f: &'a mut dyn FnMut(),
+}
+
+impl<'a> Bench<'a> {
+    pub fn new(name: &'static str, f: &'a mut dyn FnMut()) -> Self {
+        Bench { name, f }
+    }
+}
+
+/// Runs benchmarks `N` times each.
+pub struct Runner<const N: usize> {
+    cycles: fn() -> u32,
+    warmup: u32,
+    overhead: u32,
+}
+
+// one sample, not inlined so that every closure is timed the same way
+#[inline(never)]
+fn sample(cycles: fn() -> u32, f: &mut dyn FnMut()) -> u32 {
+    interrupt::free(|_| {
+        let start = cycles();
+        f();
+        cycles().wrapping_sub(start)
+    })
+}
+
+impl<const N: usize> Runner<N> {
+    /// A runner reading the cycle counter by `cycles`, measuring its own
+    /// overhead as the fastest run of an empty closure.
+    pub fn new(cycles: fn() -> u32) -> Self {
+        let mut runner = Runner {
+            cycles,
+            warmup: 1,
+            overhead: 0,
+        };
+        runner.overhead = runner.measure(&mut || {}).min;
+        runner
+    }
+
+    /// Runs before timing (default 1), to fill caches and the flash
+    /// prefetch buffer.
+    pub fn warmup(mut self, runs: u32) -> Self {
+        self.warmup = runs;
+        self
+    }
+
+    /// Cycles of the measurement itself, subtracted from every sample.
+    pub fn overhead(&self) -> u32 {
+        self.overhead
+    }
+
+    /// Times `f`, `N` times after the warm-up.
+    pub fn measure(&self, f: &mut dyn FnMut()) -> Stats {
+        for _ in 0..self.warmup {
+            f();
+        }
+        let mut samples = [0; N];
+        for s in samples.iter_mut() {
+            *s = sample(self.cycles, f).saturating_sub(self.overhead);
+        }
+        Stats::from_samples(&mut samples)
+    }
+
+    /// Times all `benches`, writing a line per benchmark to `out`.
+    pub fn run(&self, benches: &mut [Bench], out: &mut dyn Write) -> fmt::Result {
+        for bench in benches {
+            let stats = self.measure(&mut *bench.f);
+            writeln!(
+                out,
+                "bench {}: n={} min={} median={} max={} overhead={}",
+                bench.name, stats.iterations, stats.min, stats.median, stats.max, self.overhead
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes to an ITM stimulus port, e.g., the reports of `Runner::run`.
+///
+/// (`hio::hstdout()` does the same over semihosting.)
+pub struct Itm<'a>(pub &'a mut Stim);
+
+impl Write for Itm<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        itm::write_str(self.0, s);
+        Ok(())
+    }
+}
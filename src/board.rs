@@ -0,0 +1,162 @@
+//! Board support for the Nucleo-F401RE and Nucleo-F411RE
+//!
+//! The boards share the same wiring (UM1724):
+//! - LD2, the green user LED on PA5 (high = on)
+//! - B1, the blue user button on PC13 (pulled up externally, low = pressed)
+//! - USART2 on PA2 (TX) / PA3 (RX), wired to the ST-LINK virtual COM port
+//!   (`/dev/ttyACM0` on a Linux host)
+//!
+//! The F401 is the default (the `f401` feature), build with
+//! `--no-default-features --features f411` for the F411.
+//!
+//! ``` ignore
+//! let mut board = Board::take().unwrap();
+//! board.led.on();
+//! ```
+//!
+//! Clocks are left at the reset configuration (16 MHz HSI), matching the
+//! `tpiu config` in `openocd.gdb`.
+
+use cortex_m::{
+    interrupt,
+    peripheral::{CorePeripherals, DCB, DWT, ITM, NVIC, SCB, SYST},
+};
+use stm32f4xx_hal::{
+    gpio::{
+        gpioa::{PA2, PA3, PA5},
+        gpioc::PC13,
+        Alternate, Floating, Input, Output, PushPull, AF7,
+    },
+    prelude::*,
+    rcc::Clocks,
+    serial::{config::Config, Serial},
+    stm32::{self, GPIOA, GPIOC, RCC, USART2},
+};
+
+#[cfg(not(feature = "f411"))]
+pub const NAME: &str = "NUCLEO-F401RE";
+#[cfg(feature = "f411")]
+pub const NAME: &str = "NUCLEO-F411RE";
+
+/// Baud rate of the virtual COM port (8N1).
+pub const VCP_BAUDRATE: u32 = 115_200;
+
+/// LD2 (green), on PA5.
+pub struct Led {
+    pin: PA5<Output<PushPull>>,
+}
+
+impl Led {
+    pub fn on(&mut self) {
+        let _ = self.pin.set_high();
+    }
+
+    pub fn off(&mut self) {
+        let _ = self.pin.set_low();
+    }
+
+    pub fn toggle(&mut self) {
+        let _ = self.pin.toggle();
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.pin.is_set_high().unwrap_or(false)
+    }
+}
+
+/// B1 (blue), on PC13.
+pub struct UserButton {
+    pin: PC13<Input<Floating>>,
+}
+
+impl UserButton {
+    pub fn is_pressed(&self) -> bool {
+        self.pin.is_low().unwrap_or(false)
+    }
+}
+
+/// USART2, connected to the ST-LINK virtual COM port.
+pub type Vcp = Serial<USART2, (PA2<Alternate<AF7>>, PA3<Alternate<AF7>>)>;
+
+pub struct Board {
+    pub led: Led,
+    pub button: UserButton,
+    pub vcp: Vcp,
+    pub clocks: Clocks,
+    pub itm: ITM,
+    // the remaining core peripherals
+    pub dcb: DCB,
+    pub dwt: DWT,
+    pub nvic: NVIC,
+    pub scb: SCB,
+    pub syst: SYST,
+}
+
+impl Board {
+    /// Takes the core and device peripherals, `None` if either is already
+    /// taken.
+    ///
+    /// The two are taken one after the other, if only one of them succeeds
+    /// the half taken is lost (there is no giving it back); use `new` to
+    /// build the board from peripherals taken otherwise.
+    pub fn take() -> Option<Self> {
+        let taken = interrupt::free(|_| (CorePeripherals::take(), stm32::Peripherals::take()));
+        match taken {
+            (Some(core), Some(device)) => Some(Self::new(core, device)),
+            _ => None,
+        }
+    }
+
+    /// Sets up the board from the given peripherals (e.g., from RTFM `init`).
+    pub fn new(core: CorePeripherals, device: stm32::Peripherals) -> Self {
+        let (led, button, vcp, clocks) =
+            setup(device.RCC, device.GPIOA, device.GPIOC, device.USART2);
+
+        Board {
+            led,
+            button,
+            vcp,
+            clocks,
+            itm: core.ITM,
+            dcb: core.DCB,
+            dwt: core.DWT,
+            nvic: core.NVIC,
+            scb: core.SCB,
+            syst: core.SYST,
+        }
+    }
+}
+
+/// Sets up the LED, button and virtual COM port, leaving all other
+/// peripherals to the application.
+pub fn setup(
+    rcc: RCC,
+    gpioa: GPIOA,
+    gpioc: GPIOC,
+    usart2: USART2,
+) -> (Led, UserButton, Vcp, Clocks) {
+    let clocks = rcc.constrain().cfgr.freeze();
+
+    let gpioa = gpioa.split();
+    let gpioc = gpioc.split();
+
+    let led = Led {
+        pin: gpioa.pa5.into_push_pull_output(),
+    };
+    let button = UserButton {
+        pin: gpioc.pc13.into_floating_input(),
+    };
+
+    let tx = gpioa.pa2.into_alternate_af7();
+    let rx = gpioa.pa3.into_alternate_af7();
+    let vcp = Serial::usart2(
+        usart2,
+        (tx, rx),
+        Config::default().baudrate(VCP_BAUDRATE.bps()),
+        clocks,
+    )
+    // cannot fail for 8N1 at 115200
+    .unwrap();
+
+    (led, button, vcp, clocks)
+}
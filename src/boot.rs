@@ -0,0 +1,154 @@
+//! Why the program starts
+//!
+//! The reset flags of `RCC_CSR` tell what reset the MCU (the power, the
+//! reset pin, the program, a watchdog, ...), the crash record (see
+//! `crash.rs`) tells whether the program crashed before. `BootReason`
+//! combines the two, to be logged once at start:
+//!
+//! ``` ignore
+//! #[entry]
+//! fn main() -> ! {
+//!     let record = crash::take();
+//!     let csr = boot::take_flags(unsafe { &*RCC::get() });
+//!     let reason = BootReason::new(csr, record.as_ref().map(|r| r.kind().into()));
+//!     info!("{}", reason);
+//!     ...
+//! }
+//! ```
+//!
+//! ``` text
+//! I app: panic, then software reset
+//! ```
+//!
+//! (`reboot::boot` does so as well.) The flags stay set until cleared by
+//! `RMVF`, so `take_flags` clears them, or the next start would see them
+//! again. A reset sets several flags at once: on the STM32F4 any reset is
+//! output on the reset pin (setting `PINRSTF`) and a power on also sets
+//! `BORRSTF`. `Reset::decode` picks the one that caused the others.
+//!
+//! The module is shared with the host library in `host/`, where
+//! `take_flags` runs against `RCC::mock`.
+//!
+//! see RM0368 6.1 (reset) and 6.3.18 (RCC_CSR)
+
+use core::fmt;
+
+use crate::field::Access;
+use crate::stm32f40x::{rcc::csr, RCC};
+
+/// What reset the MCU.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reset {
+    /// Power on (POR), or power down (PDR).
+    PowerOn,
+    /// The supply dropped below the brownout threshold (BOR).
+    Brownout,
+    /// The independent watchdog expired.
+    IndependentWatchdog,
+    /// The window watchdog expired, or was refreshed too early.
+    WindowWatchdog,
+    /// Entering Standby or Stop mode, if the option bytes say so.
+    LowPower,
+    /// By the program, `SCB::sys_reset` (e.g., `reboot::reset`).
+    Software,
+    /// The reset pin (the B2 button, or the debugger).
+    Pin,
+    /// No flag set, e.g., cleared and started again without a reset.
+    Unknown,
+}
+
+// the causes, each before those it also flags
+const RESETS: [(u32, Reset); 7] = [
+    (csr::PORRSTF.bits(1), Reset::PowerOn),
+    (csr::BORRSTF.bits(1), Reset::Brownout),
+    (csr::IWDGRSTF.bits(1), Reset::IndependentWatchdog),
+    (csr::WWDGRSTF.bits(1), Reset::WindowWatchdog),
+    (csr::LPWRRSTF.bits(1), Reset::LowPower),
+    (csr::SFTRSTF.bits(1), Reset::Software),
+    (csr::PINRSTF.bits(1), Reset::Pin),
+];
+
+impl Reset {
+    /// The reset flagged in `RCC_CSR`.
+    pub fn decode(csr: u32) -> Self {
+        RESETS
+            .iter()
+            .find(|&&(mask, _)| csr & mask != 0)
+            .map_or(Reset::Unknown, |&(_, reset)| reset)
+    }
+}
+
+impl fmt::Display for Reset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Reset::PowerOn => "power on reset",
+            Reset::Brownout => "brownout reset",
+            Reset::IndependentWatchdog => "independent watchdog reset",
+            Reset::WindowWatchdog => "window watchdog reset",
+            Reset::LowPower => "low-power reset",
+            Reset::Software => "software reset",
+            Reset::Pin => "reset pin",
+            Reset::Unknown => "no reset flag",
+        })
+    }
+}
+
+/// What crashed, as recorded by `crash.rs`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Crash {
+    Fault,
+    Panic,
+}
+
+/// Why the program starts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BootReason {
+    /// A reset, the last run did not crash (or did not record it).
+    Reset(Reset),
+    /// A crash, then the reset (by `reboot::reset`, a watchdog, the button,
+    /// ...).
+    Crash(Crash, Reset),
+}
+
+impl BootReason {
+    /// From the value of `RCC_CSR` and the kind of the crash record, if any.
+    ///
+    /// A power on loses the RAM, a crash record that passes its CRC
+    /// nevertheless is not taken as the cause.
+    pub fn new(csr: u32, crash: Option<Crash>) -> Self {
+        let reset = Reset::decode(csr);
+        match crash {
+            Some(crash) if reset != Reset::PowerOn => BootReason::Crash(crash, reset),
+            _ => BootReason::Reset(reset),
+        }
+    }
+
+    /// The reset, whether after a crash or not.
+    pub fn reset(&self) -> Reset {
+        match *self {
+            BootReason::Reset(reset) | BootReason::Crash(_, reset) => reset,
+        }
+    }
+
+    /// Whether the last run ended by a crash.
+    pub fn is_crash(&self) -> bool {
+        matches!(self, BootReason::Crash(..))
+    }
+}
+
+impl fmt::Display for BootReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BootReason::Reset(reset) => write!(f, "{}", reset),
+            BootReason::Crash(Crash::Fault, reset) => write!(f, "fault, then {}", reset),
+            BootReason::Crash(Crash::Panic, reset) => write!(f, "panic, then {}", reset),
+        }
+    }
+}
+
+/// Reads `RCC_CSR` and clears its reset flags, once at start.
+pub fn take_flags<A: Access>(rcc: &RCC<A>) -> u32 {
+    let value = rcc.CSR.read();
+    csr::RMVF.modify(&rcc.CSR, 1);
+    value
+}
@@ -0,0 +1,506 @@
+//! Clock tree configuration
+//!
+//! After reset the MCU runs from the 16 MHz internal RC oscillator (HSI),
+//! which is what the examples assume (`RESET`, and the `tpiu config` in
+//! `openocd.gdb`). A `Config` selects the source (HSI or HSE), solves the PLL
+//! factors for the requested SYSCLK and picks the bus prescalers and flash
+//! wait states. `freeze` programs the `RCC`, `FLASH` and `PWR` (the regulator
+//! scale) and returns the resulting (frozen) `Clocks`.
+//!
+//! ``` ignore
+//! let rcc = unsafe { &*RCC::get() };
+//! let flash = unsafe { &*FLASH::get() };
+//! let pwr = unsafe { &*PWR::get() };
+//! let clocks = Config::hsi().sysclk(84_000_000).freeze(rcc, flash, pwr).unwrap();
+//! ```
+//!
+//! The solver (`Config::solve`) is pure computation, it can be run on the host
+//! against both the F401 and F411 `Limits`.
+//!
+//! see RM0368 6.2 (clock tree), 6.3 (RCC registers) and 3.5.1 (wait states),
+//! RM0383 5.1.3 (F411 regulator scale)
+
+use crate::field::Access;
+use crate::stm32f40x::{
+    flash::acr,
+    pwr,
+    rcc::{apb1enr, cfgr, cr, pllcfgr},
+    FLASH, PWR, RCC,
+};
+
+/// Frequency of the internal RC oscillator.
+pub const HSI: u32 = 16_000_000;
+
+/// The clocks after reset, those of the examples that keep them.
+pub const RESET: Clocks = Clocks::reset();
+
+// PLL constraints common to the F401 and F411
+const VCO_IN_MIN: u32 = 1_000_000;
+const VCO_IN_MAX: u32 = 2_000_000;
+const VCO_MAX: u32 = 432_000_000;
+const PLLN_MAX: u32 = 432;
+const PLL48CLK: u32 = 48_000_000;
+// highest SYSCLK in regulator scale 2, the reset default
+const SCALE2_MAX: u32 = 84_000_000;
+
+/// Device specific limits, at 2.7 - 3.6 V supply.
+#[derive(Debug, PartialEq)]
+pub struct Limits {
+    pub sysclk_max: u32,
+    pub pclk1_max: u32,
+    pub pclk2_max: u32,
+    pub pll_n_min: u32,
+    pub vco_min: u32,
+    /// Max HCLK for 0, 1, 2, ... flash wait states.
+    pub flash_ws: &'static [u32],
+}
+
+/// STM32F401, RM0368.
+#[rustfmt::skip]
+pub const F401: Limits = Limits {
+    sysclk_max: 84_000_000,
+    pclk1_max:  42_000_000,
+    pclk2_max:  84_000_000,
+    pll_n_min:  192,
+    vco_min:    192_000_000,
+    flash_ws:   &[30_000_000, 60_000_000, 84_000_000],
+};
+
+/// STM32F411, RM0383.
+#[rustfmt::skip]
+pub const F411: Limits = Limits {
+    sysclk_max: 100_000_000,
+    pclk1_max:  50_000_000,
+    pclk2_max:  100_000_000,
+    pll_n_min:  50,
+    vco_min:    100_000_000,
+    flash_ws:   &[30_000_000, 64_000_000, 90_000_000, 100_000_000],
+};
+
+/// Limits of the device we build for (see the `f411` feature).
+#[cfg(not(feature = "f411"))]
+pub const LIMITS: &Limits = &F401;
+#[cfg(feature = "f411")]
+pub const LIMITS: &Limits = &F411;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// HSE must be in the 4 - 26 MHz range.
+    HseOutOfRange,
+    /// Requested SYSCLK exceeds the device limit.
+    TooFast,
+    /// No PLL setting gives exactly the requested SYSCLK.
+    NoSolution,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Hsi,
+    /// External crystal, or external clock if `bypass` (e.g., the 8 MHz MCO
+    /// output of the ST-LINK on the Nucleo boards).
+    Hse { freq: u32, bypass: bool },
+}
+
+impl Source {
+    pub fn freq(self) -> u32 {
+        match self {
+            Source::Hsi => HSI,
+            Source::Hse { freq, .. } => freq,
+        }
+    }
+}
+
+/// PLL factors, VCO = input / m * n, SYSCLK = VCO / p, PLL48CLK = VCO / q.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pll {
+    pub m: u32,
+    pub n: u32,
+    pub p: u32,
+    pub q: u32,
+}
+
+impl Pll {
+    pub fn vco(&self, input: u32) -> u32 {
+        input / self.m * self.n
+    }
+
+    pub fn sysclk(&self, input: u32) -> u32 {
+        self.vco(input) / self.p
+    }
+
+    pub fn pll48clk(&self, input: u32) -> u32 {
+        self.vco(input) / self.q
+    }
+}
+
+/// Finds PLL factors giving exactly `sysclk` from `input` (RM0368 6.3.2).
+///
+/// Among the valid settings, one giving exactly 48 MHz on PLL48CLK (for USB)
+/// is preferred, then the highest VCO input frequency (less PLL jitter).
+pub fn solve_pll(input: u32, sysclk: u32, limits: &Limits) -> Option<Pll> {
+    let mut best: Option<((bool, u32), Pll)> = None;
+
+    for m in 2..=63 {
+        if !input.is_multiple_of(m) {
+            continue;
+        }
+        let vco_in = input / m;
+        if !(VCO_IN_MIN..=VCO_IN_MAX).contains(&vco_in) {
+            continue;
+        }
+
+        for &p in &[2, 4, 6, 8] {
+            let vco = match sysclk.checked_mul(p) {
+                Some(vco) if vco >= limits.vco_min && vco <= VCO_MAX => vco,
+                _ => continue,
+            };
+            if vco % vco_in != 0 {
+                continue;
+            }
+            let n = vco / vco_in;
+            if n < limits.pll_n_min || n > PLLN_MAX {
+                continue;
+            }
+
+            // PLL48CLK must not exceed 48 MHz
+            let q = vco.div_ceil(PLL48CLK).max(2);
+            if q > 15 {
+                continue;
+            }
+
+            let score = (vco % PLL48CLK == 0, vco_in);
+            if best.is_none_or(|(s, _)| score > s) {
+                best = Some((score, Pll { m, n, p, q }));
+            }
+        }
+    }
+
+    best.map(|(_, pll)| pll)
+}
+
+// smallest APB prescaler keeping the bus clock within `max`
+fn apb_divider(hclk: u32, max: u32) -> u32 {
+    [1, 2, 4, 8, 16]
+        .iter()
+        .cloned()
+        .find(|d| hclk / d <= max)
+        .unwrap_or(16)
+}
+
+// PPRE encoding, RM0368 6.3.3
+fn ppre_bits(divider: u32) -> u32 {
+    match divider {
+        1 => 0b000,
+        2 => 0b100,
+        4 => 0b101,
+        8 => 0b110,
+        _ => 0b111,
+    }
+}
+
+// the larger of two dividers, as PPRE bits (0b0xx is not divided, then the
+// larger the value the larger the divider)
+fn ppre_max(a: u32, b: u32) -> u32 {
+    let div = |bits: u32| if bits & 0b100 == 0 { 0 } else { bits };
+    if div(a) >= div(b) {
+        a
+    } else {
+        b
+    }
+}
+
+/// Frozen clock configuration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clocks {
+    sysclk: u32,
+    hclk: u32,
+    pclk1: u32,
+    pclk2: u32,
+    ppre1: u8,
+    ppre2: u8,
+    pll48clk: Option<u32>,
+}
+
+impl Clocks {
+    /// The clocks after reset (16 MHz HSI, no prescaling).
+    pub const fn reset() -> Self {
+        Clocks {
+            sysclk: HSI,
+            hclk: HSI,
+            pclk1: HSI,
+            pclk2: HSI,
+            ppre1: 1,
+            ppre2: 1,
+            pll48clk: None,
+        }
+    }
+
+    /// System clock (also the core clock, driving SysTick and the DWT).
+    pub fn sysclk(&self) -> u32 {
+        self.sysclk
+    }
+
+    /// AHB clock.
+    pub fn hclk(&self) -> u32 {
+        self.hclk
+    }
+
+    /// APB1 clock (e.g., USART2).
+    pub fn pclk1(&self) -> u32 {
+        self.pclk1
+    }
+
+    /// APB2 clock (e.g., USART1).
+    pub fn pclk2(&self) -> u32 {
+        self.pclk2
+    }
+
+    /// APB1 timer clock, doubled when APB1 is prescaled.
+    pub fn timclk1(&self) -> u32 {
+        self.pclk1 * if self.ppre1 == 1 { 1 } else { 2 }
+    }
+
+    /// APB2 timer clock, doubled when APB2 is prescaled.
+    pub fn timclk2(&self) -> u32 {
+        self.pclk2 * if self.ppre2 == 1 { 1 } else { 2 }
+    }
+
+    /// PLL48CLK, if the PLL is used.
+    pub fn pll48clk(&self) -> Option<u32> {
+        self.pll48clk
+    }
+}
+
+/// A solved configuration, ready to be programmed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Setup {
+    pub source: Source,
+    pub pll: Option<Pll>,
+    /// Flash wait states.
+    pub latency: u32,
+    /// Regulator scale, `pwr::SCALE1` above 84 MHz (F411 only).
+    pub vos: u32,
+    pub ppre1: u32,
+    pub ppre2: u32,
+    pub clocks: Clocks,
+}
+
+/// Requested clock configuration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    source: Source,
+    sysclk: Option<u32>,
+}
+
+impl Config {
+    /// Use the internal RC oscillator.
+    pub fn hsi() -> Self {
+        Config {
+            source: Source::Hsi,
+            sysclk: None,
+        }
+    }
+
+    /// Use an external crystal of frequency `freq`.
+    pub fn hse(freq: u32) -> Self {
+        Config {
+            source: Source::Hse {
+                freq,
+                bypass: false,
+            },
+            sysclk: None,
+        }
+    }
+
+    /// Use an external clock signal instead of a crystal (HSE only).
+    pub fn bypass(mut self) -> Self {
+        if let Source::Hse { ref mut bypass, .. } = self.source {
+            *bypass = true;
+        }
+        self
+    }
+
+    /// Requested SYSCLK, defaults to the source frequency (no PLL).
+    pub fn sysclk(mut self, freq: u32) -> Self {
+        self.sysclk = Some(freq);
+        self
+    }
+
+    /// Solves the configuration for the given device `limits`.
+    ///
+    /// AHB runs at SYSCLK, the APB buses as fast as allowed.
+    pub fn solve(&self, limits: &Limits) -> Result<Setup, Error> {
+        let input = self.source.freq();
+        if let Source::Hse { freq, .. } = self.source {
+            if !(4_000_000..=26_000_000).contains(&freq) {
+                return Err(Error::HseOutOfRange);
+            }
+        }
+
+        let sysclk = self.sysclk.unwrap_or(input);
+        if sysclk > limits.sysclk_max {
+            return Err(Error::TooFast);
+        }
+
+        let pll = if sysclk == input {
+            None
+        } else {
+            Some(solve_pll(input, sysclk, limits).ok_or(Error::NoSolution)?)
+        };
+
+        let hclk = sysclk;
+        let ppre1 = apb_divider(hclk, limits.pclk1_max);
+        let ppre2 = apb_divider(hclk, limits.pclk2_max);
+        let latency = limits
+            .flash_ws
+            .iter()
+            .position(|&max| hclk <= max)
+            .ok_or(Error::TooFast)? as u32;
+
+        Ok(Setup {
+            source: self.source,
+            pll,
+            latency,
+            vos: if sysclk > SCALE2_MAX {
+                pwr::SCALE1
+            } else {
+                pwr::SCALE2
+            },
+            ppre1,
+            ppre2,
+            clocks: Clocks {
+                sysclk,
+                hclk,
+                pclk1: hclk / ppre1,
+                pclk2: hclk / ppre2,
+                ppre1: ppre1 as u8,
+                ppre2: ppre2 as u8,
+                pll48clk: pll.map(|pll| pll.pll48clk(input)),
+            },
+        })
+    }
+
+    /// Solves (for `LIMITS`) and programs the clock tree.
+    pub fn freeze<A: Access>(
+        self,
+        rcc: &RCC<A>,
+        flash: &FLASH<A>,
+        pwr: &PWR<A>,
+    ) -> Result<Clocks, Error> {
+        let setup = self.solve(LIMITS)?;
+        setup.apply(rcc, flash, pwr);
+        Ok(setup.clocks)
+    }
+}
+
+impl Setup {
+    /// Programs the clock tree, RM0368 6.3.
+    ///
+    /// Whatever the clock tree before, no bus runs faster than allowed on the
+    /// way: the wait states, prescalers and regulator scale are raised before
+    /// the switch of SYSCLK, and lowered after.
+    pub fn apply<A: Access>(&self, rcc: &RCC<A>, flash: &FLASH<A>, pwr: &PWR<A>) {
+        // start the source oscillator
+        match self.source {
+            Source::Hsi => {
+                cr::HSION.modify(&rcc.CR, 1);
+                while cr::HSIRDY.read(&rcc.CR) == 0 {}
+            }
+            Source::Hse { bypass, .. } => {
+                // HSEBYP can only be written while the HSE is off (RM0368
+                // 6.3.1), so off SYSCLK and the PLL it may drive first
+                if cr::HSEON.read(&rcc.CR) == 1 && cr::HSEBYP.read(&rcc.CR) != bypass as u32 {
+                    switch_to_hsi(rcc);
+                    cr::PLLON.modify(&rcc.CR, 0);
+                    while cr::PLLRDY.read(&rcc.CR) != 0 {}
+                    cr::HSEON.modify(&rcc.CR, 0);
+                    while cr::HSERDY.read(&rcc.CR) != 0 {}
+                }
+                if cr::HSEON.read(&rcc.CR) == 0 {
+                    cr::HSEBYP.modify(&rcc.CR, bypass as u32);
+                }
+                cr::HSEON.modify(&rcc.CR, 1);
+                while cr::HSERDY.read(&rcc.CR) == 0 {}
+            }
+        }
+
+        // wait states must be increased *before* raising the clock (RM0368 3.5.1)
+        let latency = acr::LATENCY.read(&flash.ACR);
+        if self.latency > latency {
+            set_latency(flash, self.latency);
+        }
+
+        // until the switch, the APB prescalers must suit both the old and the
+        // new SYSCLK (the AHB stays as it is, not divided is set after)
+        let (ppre1, ppre2) = (ppre_bits(self.ppre1), ppre_bits(self.ppre2));
+        cfgr::PPRE1.modify(&rcc.CFGR, ppre_max(cfgr::PPRE1.read(&rcc.CFGR), ppre1));
+        cfgr::PPRE2.modify(&rcc.CFGR, ppre_max(cfgr::PPRE2.read(&rcc.CFGR), ppre2));
+
+        let sw = match self.pll {
+            Some(pll) => {
+                // the PLL cannot be stopped while it drives SYSCLK
+                if cfgr::SWS.read(&rcc.CFGR) == 0b10 {
+                    switch_to_hsi(rcc);
+                }
+
+                // the PLL must be off while configured
+                cr::PLLON.modify(&rcc.CR, 0);
+                while cr::PLLRDY.read(&rcc.CR) != 0 {}
+
+                // the regulator scale, written while the PLL is off and in
+                // effect once it is on (RM0383 5.1.3)
+                apb1enr::PWREN.modify(&rcc.APB1ENR, 1);
+                pwr::cr::VOS.modify(&pwr.CR, self.vos);
+
+                let hse = match self.source {
+                    Source::Hse { .. } => 1,
+                    Source::Hsi => 0,
+                };
+                pllcfgr::PLLSRC.modify(&rcc.PLLCFGR, hse);
+                pllcfgr::PLLM.modify(&rcc.PLLCFGR, pll.m);
+                pllcfgr::PLLN.modify(&rcc.PLLCFGR, pll.n);
+                pllcfgr::PLLP.modify(&rcc.PLLCFGR, pll.p / 2 - 1);
+                pllcfgr::PLLQ.modify(&rcc.PLLCFGR, pll.q);
+
+                cr::PLLON.modify(&rcc.CR, 1);
+                while cr::PLLRDY.read(&rcc.CR) == 0 {}
+                0b10
+            }
+            None => match self.source {
+                Source::Hsi => 0b00,
+                Source::Hse { .. } => 0b01,
+            },
+        };
+
+        // switch, and wait until the switch is done
+        cfgr::SW.modify(&rcc.CFGR, sw);
+        while cfgr::SWS.read(&rcc.CFGR) != sw {}
+
+        // AHB not divided, and the APB prescalers of the new SYSCLK only
+        cfgr::HPRE.modify(&rcc.CFGR, 0);
+        cfgr::PPRE1.modify(&rcc.CFGR, ppre1);
+        cfgr::PPRE2.modify(&rcc.CFGR, ppre2);
+
+        // wait states can be decreased *after* lowering the clock
+        if self.latency < latency {
+            set_latency(flash, self.latency);
+        }
+    }
+}
+
+// SYSCLK from the HSI, as after reset
+fn switch_to_hsi<A: Access>(rcc: &RCC<A>) {
+    cr::HSION.modify(&rcc.CR, 1);
+    while cr::HSIRDY.read(&rcc.CR) == 0 {}
+    cfgr::SW.modify(&rcc.CFGR, 0b00);
+    while cfgr::SWS.read(&rcc.CFGR) != 0b00 {}
+}
+
+fn set_latency<A: Access>(flash: &FLASH<A>, latency: u32) {
+    acr::PRFTEN.modify(&flash.ACR, 1);
+    acr::ICEN.modify(&flash.ACR, 1);
+    acr::DCEN.modify(&flash.ACR, 1);
+    acr::LATENCY.modify(&flash.ACR, latency);
+    // the new setting is effective when read back (RM0368 3.5.1)
+    while acr::LATENCY.read(&flash.ACR) != latency {}
+}
@@ -0,0 +1,198 @@
+//! Core dumps, streamed from a fault handler
+//!
+//! A crash record (see `crash.rs`) holds the top of the stack; a core dump
+//! holds all of RAM and the registers, so a debugger can show the state of
+//! the program at the fault: backtrace, locals and statics. The `HardFault`
+//! handler streams it over USART2 or ITM port `PORT`:
+//!
+//! ``` ignore
+//! #[exception]
+//! fn HardFault(ef: &ExceptionFrame) -> ! {
+//!     coredump::write(ef, &[coredump::RAM], &mut coredump::Itm);
+//!     loop {}
+//! }
+//! ```
+//!
+//! and `coredump` (in `host/`) turns the capture into an ELF core file, to
+//! be opened along with the firmware:
+//!
+//! ``` console
+//! > arm-none-eabi-gdb target/thumbv7em-none-eabihf/debug/examples/crash core
+//! ```
+//!
+//! The stream (little endian) starts with a magic, so it can be found among
+//! other output, and ends with the CRC-16 of `frame.rs` over all after the
+//! magic:
+//!
+//! ``` text
+//! dump   = "CORE" | version (1) | regs (17 x 4) | fault (5 x 4) | count (1) | region* | crc (2)
+//! regs   = r0-r12, sp, lr, pc, xpsr
+//! fault  = cfsr, hfsr, mmfar, bfar, afsr
+//! region = address (4) | length (4) | bytes
+//! ```
+//!
+//! The exception entry does not save r4-r11, and the handler may have used
+//! them before the dump, so they are sent as 0. The sp is that before the
+//! exception frame, which the FPU state (s0-s15 and FPSCR) extends if the
+//! code used the FPU. EXC_RETURN (the `lr` on entry) would tell, but the
+//! handler cannot read it reliably once its prologue (or a call) has run, so
+//! the FPU context registers tell instead: an extended frame points `FPCAR`
+//! at its FPU state and, with lazy stacking (`LSPEN`, the reset default),
+//! sets `LSPACT` until that state is saved, which the handler does not do
+//! before the dump (see ARMv7-M ARM B3.2.21 and B3.2.22).
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use cortex_m::{interrupt, peripheral::ITM};
+use cortex_m_rt::ExceptionFrame;
+
+use crate::fault;
+use crate::frame::{crc16_update, Sink};
+use crate::stm32f40x::{
+    address::SRAM_BASE,
+    usart::{cr1, cr3, sr},
+    USART,
+};
+
+pub const MAGIC: &[u8; 4] = b"CORE";
+pub const VERSION: u8 = 1;
+/// The ITM stimulus port of `Itm`.
+pub const PORT: usize = 4;
+/// All of RAM (`RAM` and `NOINIT` in `memory.x`), address and length.
+pub const RAM: (u32, u32) = (SRAM_BASE, 32 * 1024);
+
+// xPSR bit 9, the frame was aligned to 8 bytes by a padding word
+const STKALIGN: u32 = 1 << 9;
+// Floating-point Context Control Register: lazy stacking pending, and
+// enabled; and the Floating-point Context Address Register
+const FPCCR: u32 = 0xE000_EF34;
+const LSPACT: u32 = 1 << 0;
+const LSPEN: u32 = 1 << 30;
+const FPCAR: u32 = 0xE000_EF38;
+// the basic frame, and the FPU state extending it
+const FRAME_SIZE: u32 = 0x20;
+const FPU_FRAME_SIZE: u32 = 0x48;
+
+/// USART2, as configured before the fault (e.g., by `board::setup`), polled.
+pub struct Usart2;
+
+impl Sink for Usart2 {
+    fn send(&mut self, bytes: &[u8]) {
+        // NOTE(unsafe) the fault handler takes over the port, DMA included
+        let usart = unsafe { &*USART::usart2() };
+        cr3::DMAT.modify(&usart.CR3, 0);
+        cr1::TE.modify(&usart.CR1, 1);
+        for &byte in bytes {
+            while sr::TXE.read(&usart.SR) == 0 {}
+            usart.DR.write(byte as u32);
+        }
+        while sr::TC.read(&usart.SR) == 0 {}
+    }
+}
+
+/// ITM stimulus port `PORT`, dropped if the ITM or the port is disabled.
+pub struct Itm;
+
+impl Sink for Itm {
+    fn send(&mut self, bytes: &[u8]) {
+        // NOTE(unsafe) the fault handler takes over the ITM
+        let itm = unsafe { &mut *ITM::ptr() };
+        // writing to a disabled port would wait forever for the FIFO
+        if itm.tcr.read() & 1 == 0 || itm.ter[0].read() & (1 << PORT) == 0 {
+            return;
+        }
+        cortex_m::itm::write_all(&mut itm.stim[PORT], bytes);
+    }
+}
+
+// sends while keeping the CRC
+struct Crc<'a> {
+    out: &'a mut dyn Sink,
+    crc: u16,
+}
+
+impl Crc<'_> {
+    fn send(&mut self, bytes: &[u8]) {
+        self.crc = crc16_update(self.crc, bytes);
+        self.out.send(bytes);
+    }
+
+    fn word(&mut self, word: u32) {
+        self.send(&word.to_le_bytes());
+    }
+}
+
+// whether the frame at `frame` is extended by the FPU state, `FPCAR` alone
+// may be left by an earlier (returned) frame at the same address
+fn is_extended(frame: u32) -> bool {
+    // NOTE(unsafe) reading the FPU context registers is side effect free
+    let (fpccr, fpcar) = unsafe {
+        (
+            core::ptr::read_volatile(FPCCR as *const u32),
+            core::ptr::read_volatile(FPCAR as *const u32),
+        )
+    };
+    fpcar == frame + FRAME_SIZE && (fpccr & LSPEN == 0 || fpccr & LSPACT != 0)
+}
+
+/// The registers at the fault, r4-r11 unknown (0), from the exception frame
+/// (and whether the FPU state extends it).
+pub fn registers(ef: &ExceptionFrame) -> [u32; 17] {
+    let frame = ef as *const _ as u32;
+    let mut sp = frame + FRAME_SIZE;
+    if is_extended(frame) {
+        sp += FPU_FRAME_SIZE;
+    }
+    if ef.xpsr & STKALIGN != 0 {
+        sp += 4;
+    }
+    let mut regs = [0; 17];
+    regs[..4].copy_from_slice(&[ef.r0, ef.r1, ef.r2, ef.r3]);
+    regs[12] = ef.r12;
+    regs[13] = sp;
+    regs[14] = ef.lr;
+    regs[15] = ef.pc;
+    regs[16] = ef.xpsr;
+    regs
+}
+
+/// Streams a core dump of `regions` (address and length, at most 255) to
+/// `out`, with interrupts disabled.
+pub fn write(ef: &ExceptionFrame, regions: &[(u32, u32)], out: &mut dyn Sink) {
+    interrupt::free(|_| {
+        out.send(MAGIC);
+        let mut out = Crc { out, crc: 0xFFFF };
+        out.send(&[VERSION]);
+        for &reg in registers(ef).iter() {
+            out.word(reg);
+        }
+        // NOTE(unsafe) reading the fault status is side effect free
+        let f = unsafe { fault::Registers::read() };
+        for &reg in [f.cfsr, f.hfsr, f.mmfar, f.bfar, f.afsr].iter() {
+            out.word(reg);
+        }
+
+        let regions = &regions[..regions.len().min(255)];
+        out.send(&[regions.len() as u8]);
+        for &(address, length) in regions {
+            out.word(address);
+            out.word(length);
+            compiler_fence(Ordering::SeqCst);
+            // a chunk at a time, the bytes read one by one
+            let mut chunk = [0u8; 64];
+            let mut at = address;
+            while at < address + length {
+                let n = (address + length - at).min(chunk.len() as u32) as usize;
+                for (i, b) in chunk[..n].iter_mut().enumerate() {
+                    // NOTE(unsafe) the caller gives readable regions
+                    *b = unsafe { core::ptr::read_volatile((at as usize + i) as *const u8) };
+                }
+                out.send(&chunk[..n]);
+                at += n as u32;
+            }
+        }
+
+        let crc = out.crc;
+        out.out.send(&crc.to_le_bytes());
+    });
+}
@@ -0,0 +1,278 @@
+//! Crash records that survive a reset
+//!
+//! With `panic_halt` (or a plain `HardFault` handler) the evidence of a crash
+//! is gone once the board is reset. Instead, the handlers write a `Record`
+//! to the `.noinit` section, which `memory.x` places in a RAM region of its
+//! own that the runtime does not initialize, and the next boot reads it back:
+//!
+//! ``` ignore
+//! #[exception]
+//! fn HardFault(ef: &ExceptionFrame) -> ! {
+//!     crash::record_fault(ef);
+//!     loop {}
+//! }
+//!
+//! #[panic_handler]
+//! fn panic(info: &PanicInfo) -> ! {
+//!     crash::record_panic(info);
+//!     loop {}
+//! }
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     if let Some(record) = crash::take() {
+//!         write!(tx, "{}", record).ok();
+//!     }
+//!     ...
+//! }
+//! ```
+//!
+//! A record holds the exception frame and the fault status registers (see
+//! `fault.rs`), the first `STACK_WORDS` words of the stack and the panic
+//! message (truncated to `MESSAGE_LEN` bytes). It is only taken as valid if
+//! its magic and CRC (the CRC-16 of `frame.rs`) match, so the random RAM
+//! contents after power on are not mistaken for a crash.
+
+use core::fmt::{self, Write};
+use core::mem::{size_of, MaybeUninit};
+use core::panic::PanicInfo;
+use core::{ptr, slice, str};
+
+use cortex_m::register::msp;
+use cortex_m_rt::ExceptionFrame;
+
+use crate::boot::Crash;
+use crate::fault;
+use crate::frame::crc16;
+use crate::stm32f40x::address::SRAM_BASE;
+
+/// Marks a written record.
+pub const MAGIC: u32 = 0xDEAD_C0DE;
+/// Words of stack kept.
+pub const STACK_WORDS: usize = 32;
+/// Bytes of panic message kept.
+pub const MESSAGE_LEN: usize = 128;
+
+/// What crashed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// A `HardFault` (or a configurable fault handled alike).
+    Fault = 1,
+    Panic = 2,
+}
+
+impl From<Kind> for Crash {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::Fault => Crash::Fault,
+            Kind::Panic => Crash::Panic,
+        }
+    }
+}
+
+/// A crash, as kept in `.noinit`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Record {
+    magic: u32,
+    // of the bytes after `crc`
+    crc: u32,
+    kind: u32,
+    // r0, r1, r2, r3, r12, lr, pc, xpsr
+    frame: [u32; 8],
+    // cfsr, hfsr, mmfar, bfar, afsr
+    fault: [u32; 5],
+    sp: u32,
+    stack_len: u32,
+    stack: [u32; STACK_WORDS],
+    message_len: u32,
+    message: [u8; MESSAGE_LEN],
+}
+
+#[link_section = ".noinit.crash"]
+static mut RECORD: MaybeUninit<Record> = MaybeUninit::uninit();
+
+extern "C" {
+    // the top of the stack, from `memory.x`
+    static _stack_start: u32;
+}
+
+impl Record {
+    // all but `magic` and `crc`
+    fn body(&self) -> &[u8] {
+        // NOTE(unsafe) `repr(C)` without padding, all words and bytes
+        let bytes =
+            unsafe { slice::from_raw_parts(self as *const _ as *const u8, size_of::<Self>()) };
+        &bytes[8..]
+    }
+
+    fn is_valid(&self) -> bool {
+        self.magic == MAGIC
+            && self.crc == crc16(self.body()) as u32
+            && (self.kind == Kind::Fault as u32 || self.kind == Kind::Panic as u32)
+            && self.stack_len as usize <= STACK_WORDS
+            && self.message_len as usize <= MESSAGE_LEN
+    }
+
+    pub fn kind(&self) -> Kind {
+        if self.kind == Kind::Panic as u32 {
+            Kind::Panic
+        } else {
+            Kind::Fault
+        }
+    }
+
+    /// The exception frame (r0-r3, r12, lr, pc and xpsr), zero for a panic.
+    pub fn frame(&self) -> &[u32; 8] {
+        &self.frame
+    }
+
+    pub fn fault(&self) -> fault::Registers {
+        let [cfsr, hfsr, mmfar, bfar, afsr] = self.fault;
+        fault::Registers {
+            cfsr,
+            hfsr,
+            mmfar,
+            bfar,
+            afsr,
+        }
+    }
+
+    /// The stack pointer when the record was written.
+    pub fn sp(&self) -> u32 {
+        self.sp
+    }
+
+    /// The stack, from `sp` up.
+    pub fn stack(&self) -> &[u32] {
+        &self.stack[..self.stack_len as usize]
+    }
+
+    /// The panic message, empty for a fault.
+    pub fn message(&self) -> &str {
+        let bytes = &self.message[..self.message_len as usize];
+        match str::from_utf8(bytes) {
+            Ok(s) => s,
+            // cut in the middle of a character
+            Err(e) => str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or(""),
+        }
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind() {
+            Kind::Fault => {
+                let [r0, r1, r2, r3, r12, lr, pc, xpsr] = self.frame;
+                write!(f, "crash: {}", self.fault())?;
+                writeln!(
+                    f,
+                    "r0={:#010x} r1={:#010x} r2={:#010x} r3={:#010x}",
+                    r0, r1, r2, r3
+                )?;
+                writeln!(
+                    f,
+                    "r12={:#010x} lr={:#010x} pc={:#010x} xpsr={:#010x}",
+                    r12, lr, pc, xpsr
+                )?;
+            }
+            Kind::Panic => writeln!(f, "crash: {}", self.message())?,
+        }
+        write!(f, "stack at {:#010x}:", self.sp)?;
+        for (i, word) in self.stack().iter().enumerate() {
+            if i % 4 == 0 {
+                f.write_str("\n ")?;
+            }
+            write!(f, " {:#010x}", word)?;
+        }
+        f.write_str("\n")
+    }
+}
+
+// the record, to be filled in place (a crash may be short of stack)
+unsafe fn record() -> &'static mut Record {
+    &mut *(*ptr::addr_of_mut!(RECORD)).as_mut_ptr()
+}
+
+// copies the stack from `sp` up, if `sp` is in RAM
+unsafe fn snapshot(r: &mut Record, sp: u32) {
+    let top = &_stack_start as *const u32 as u32;
+    r.sp = sp;
+    r.stack_len = 0;
+    if sp < SRAM_BASE || sp >= top || sp % 4 != 0 {
+        return;
+    }
+    let n = (((top - sp) / 4) as usize).min(STACK_WORDS);
+    for i in 0..n {
+        r.stack[i] = ptr::read_volatile((sp as *const u32).add(i));
+    }
+    r.stack_len = n as u32;
+}
+
+fn seal(r: &mut Record) {
+    r.crc = crc16(r.body()) as u32;
+    r.magic = MAGIC;
+}
+
+/// Records a fault, from its exception frame and the fault status registers.
+pub fn record_fault(ef: &ExceptionFrame) {
+    // NOTE(unsafe) only the crash handlers write the record, and they do not
+    // return to anything that could read it
+    unsafe {
+        let r = record();
+        r.magic = 0;
+        r.kind = Kind::Fault as u32;
+        r.frame = [ef.r0, ef.r1, ef.r2, ef.r3, ef.r12, ef.lr, ef.pc, ef.xpsr];
+        let regs = fault::Registers::read();
+        r.fault = [regs.cfsr, regs.hfsr, regs.mmfar, regs.bfar, regs.afsr];
+        snapshot(r, ef as *const _ as u32);
+        r.message_len = 0;
+        seal(r);
+    }
+}
+
+// fills the message buffer, dropping what does not fit
+struct Message<'a>(&'a mut Record);
+
+impl Write for Message<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = self.0.message_len as usize;
+        let n = s.len().min(MESSAGE_LEN - len);
+        self.0.message[len..len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.0.message_len += n as u32;
+        Ok(())
+    }
+}
+
+/// Records a panic, with its message and location.
+pub fn record_panic(info: &PanicInfo) {
+    // NOTE(unsafe) see `record_fault`
+    unsafe {
+        let r = record();
+        r.magic = 0;
+        r.kind = Kind::Panic as u32;
+        r.frame = [0; 8];
+        r.fault = [0; 5];
+        snapshot(r, msp::read());
+        r.message_len = 0;
+        let _ = write!(Message(r), "{}", info);
+        seal(r);
+    }
+}
+
+/// The record of the last crash if any, clearing it.
+pub fn take() -> Option<Record> {
+    // NOTE(unsafe) at boot, before any handler can write the record; the RAM
+    // is copied out as is (any bit pattern is a `Record`) and only the copy
+    // is validated
+    unsafe {
+        let slot = ptr::addr_of_mut!(RECORD).cast::<Record>();
+        let record = ptr::read_volatile(slot);
+        ptr::write_volatile(ptr::addr_of_mut!((*slot).magic), 0);
+        if record.is_valid() {
+            Some(record)
+        } else {
+            None
+        }
+    }
+}
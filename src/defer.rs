@@ -0,0 +1,205 @@
+//! Deferred formatting, the wire format
+//!
+//! With the `log-deferred` feature the log macros (see `log.rs`) do not
+//! format on the target. The format string of each call is interned into
+//! the `.log` section of the ELF, which is not loaded to flash (see
+//! `memory.x`), and only its index (the address in `.log`) and the raw
+//! arguments are sent:
+//!
+//! ``` text
+//! record = index (2, little endian) | arg*
+//! arg    = tag (1) | value (little endian, `str` as length (1) | bytes)
+//! frame  = cobs(record) | 0x00
+//! ```
+//!
+//! An interned string is `<level tag> <module>: <format string>\0`, so the
+//! host (`itm -e`, in `host/`) rebuilds the same lines as plain logging.
+//!
+//! Arguments that do not fit in `MAX_RECORD` are dropped, the record then
+//! ends with a `TRUNCATED` tag.
+//!
+//! The module is plain `core`, shared with the host library in `host/`.
+
+use crate::frame::cobs;
+
+/// Largest record, index and arguments included.
+pub const MAX_RECORD: usize = 64;
+/// Largest frame, delimiter included.
+pub const MAX_FRAME: usize = cobs::max_encoded_len(MAX_RECORD) + 1;
+
+/// The type of an argument.
+pub mod tag {
+    pub const U8: u8 = 1;
+    pub const U16: u8 = 2;
+    pub const U32: u8 = 3;
+    pub const U64: u8 = 4;
+    pub const I8: u8 = 5;
+    pub const I16: u8 = 6;
+    pub const I32: u8 = 7;
+    pub const I64: u8 = 8;
+    pub const BOOL: u8 = 9;
+    pub const CHAR: u8 = 10;
+    pub const F32: u8 = 11;
+    pub const STR: u8 = 12;
+    pub const BYTES: u8 = 13;
+    /// The remaining arguments did not fit.
+    pub const TRUNCATED: u8 = 0xFF;
+}
+
+/// Builds the interned form of `s`, `N` must be `s.len() + 3`.
+#[doc(hidden)]
+pub const fn intern<const N: usize>(tag: u8, s: &str) -> [u8; N] {
+    let s = s.as_bytes();
+    let mut bytes = [0; N];
+    bytes[0] = tag;
+    bytes[1] = b' ';
+    let mut i = 0;
+    while i < s.len() {
+        bytes[i + 2] = s[i];
+        i += 1;
+    }
+    bytes
+}
+
+/// A value that can be sent as a deferred argument.
+pub trait Arg {
+    fn encode(&self, record: &mut Record);
+}
+
+/// A record under construction.
+pub struct Record {
+    bytes: [u8; MAX_RECORD],
+    len: usize,
+    truncated: bool,
+}
+
+impl Record {
+    pub fn new(index: u16) -> Self {
+        let mut record = Record {
+            bytes: [0; MAX_RECORD],
+            len: 2,
+            truncated: false,
+        };
+        record.bytes[..2].copy_from_slice(&index.to_le_bytes());
+        record
+    }
+
+    /// Adds an argument, `value` being its encoded bytes.
+    pub fn push(&mut self, tag: u8, value: &[u8]) {
+        // keep room for the `TRUNCATED` tag
+        if self.truncated || self.len + 1 + value.len() >= MAX_RECORD {
+            if !self.truncated {
+                self.bytes[self.len] = tag::TRUNCATED;
+                self.len += 1;
+                self.truncated = true;
+            }
+            return;
+        }
+        self.bytes[self.len] = tag;
+        self.bytes[self.len + 1..self.len + 1 + value.len()].copy_from_slice(value);
+        self.len += 1 + value.len();
+    }
+
+    /// Adds a length prefixed argument (at most 255 bytes).
+    pub fn push_slice(&mut self, tag: u8, value: &[u8]) {
+        let value = &value[..value.len().min(255)];
+        let mut bytes = [0; MAX_RECORD];
+        if value.len() < MAX_RECORD {
+            bytes[0] = value.len() as u8;
+            bytes[1..=value.len()].copy_from_slice(value);
+            self.push(tag, &bytes[..=value.len()]);
+        } else {
+            // can never fit
+            self.push(tag, value);
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Encodes the record into `frame`, returning the length of the frame.
+    pub fn frame(&self, frame: &mut [u8; MAX_FRAME]) -> usize {
+        let n = cobs::encode(self.as_bytes(), frame);
+        frame[n] = 0;
+        n + 1
+    }
+}
+
+macro_rules! int {
+    ($($t:ty => $tag:ident,)*) => {
+        $(
+            impl Arg for $t {
+                fn encode(&self, record: &mut Record) {
+                    record.push(tag::$tag, &self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+int! {
+    u8 => U8,
+    u16 => U16,
+    u32 => U32,
+    u64 => U64,
+    i8 => I8,
+    i16 => I16,
+    i32 => I32,
+    i64 => I64,
+}
+
+// `usize` and `isize` are 32 bits on the target
+impl Arg for usize {
+    fn encode(&self, record: &mut Record) {
+        (*self as u32).encode(record)
+    }
+}
+
+impl Arg for isize {
+    fn encode(&self, record: &mut Record) {
+        (*self as i32).encode(record)
+    }
+}
+
+impl Arg for bool {
+    fn encode(&self, record: &mut Record) {
+        record.push(tag::BOOL, &[*self as u8]);
+    }
+}
+
+impl Arg for char {
+    fn encode(&self, record: &mut Record) {
+        record.push(tag::CHAR, &(*self as u32).to_le_bytes());
+    }
+}
+
+impl Arg for f32 {
+    fn encode(&self, record: &mut Record) {
+        record.push(tag::F32, &self.to_bits().to_le_bytes());
+    }
+}
+
+impl Arg for str {
+    fn encode(&self, record: &mut Record) {
+        record.push_slice(tag::STR, self.as_bytes());
+    }
+}
+
+impl Arg for [u8] {
+    fn encode(&self, record: &mut Record) {
+        record.push_slice(tag::BYTES, self);
+    }
+}
+
+impl<const N: usize> Arg for [u8; N] {
+    fn encode(&self, record: &mut Record) {
+        record.push_slice(tag::BYTES, self);
+    }
+}
+
+impl<T: Arg + ?Sized> Arg for &T {
+    fn encode(&self, record: &mut Record) {
+        (**self).encode(record)
+    }
+}
@@ -0,0 +1,341 @@
+//! DMA driven USART2, with frames delimited by the IDLE line
+//!
+//! `serial.rs` takes one interrupt per byte. Here DMA1 moves the bytes
+//! (RM0368 9.3.3, table 27: USART2_RX on stream 5, USART2_TX on stream 6,
+//! both on channel 4), and the CPU is only involved once per frame.
+//!
+//! Receiving runs the stream in circular double buffer mode (RM0368 9.3.9):
+//! the DMA fills one buffer while the other is handed to the application.
+//! A frame ends either when a buffer is full (transfer complete) or when the
+//! line goes idle for one character time (USART IDLE, RM0368 19.6.1), in
+//! which case the stream is stopped and the partly filled buffer is swapped
+//! out.
+//!
+//! Buffers move by value between the DMA and the application, so a buffer
+//! is never accessible while the DMA may write (or read) it:
+//! - `Rx::on_interrupt` returns a received `Frame`, which is given back with
+//!   `Rx::release`. Three buffers are needed, two loaded in the stream and
+//!   one spare to swap in, a frame is dropped if there is no spare.
+//! - `Tx::write` consumes the `Tx` and the buffer, `Transfer::wait` gives
+//!   both back once the last byte is handed to the USART.
+//!
+//! ``` ignore
+//! static mut BUFS: [[u8; 64]; 3] = [[0; 64]; 3];
+//!
+//! let [a, b, c] = unsafe { &mut BUFS };
+//! let (mut rx, tx) = dma::usart2(board.vcp, [a, b, c]);
+//!
+//! // in the USART2 and DMA1_STREAM5 interrupt handlers
+//! if let Some(frame) = rx.on_interrupt() {
+//!     // echo, then hand the buffer back
+//!     let (frame, tx) = tx.write(frame).wait();
+//!     rx.release(frame);
+//! }
+//! ```
+
+use core::ops::Deref;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::board::Vcp;
+use crate::stm32f40x::{
+    dma::{cr, hifcr, hisr, ndtr, Dir},
+    rcc::ahb1enr,
+    usart::{cr1, cr3, sr},
+    Stream, DMA, RCC, USART,
+};
+
+const CHANNEL: u32 = 4;
+const RX_STREAM: usize = 5;
+const TX_STREAM: usize = 6;
+
+/// Receive errors, counted since start.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Errors {
+    /// The DMA did not empty the data register in time.
+    pub overrun: u32,
+    /// Missing stop bit.
+    pub framing: u32,
+    /// Noise detected on the line.
+    pub noise: u32,
+    /// No spare buffer was released, the frame is discarded.
+    pub dropped: u32,
+}
+
+/// Takes over the (configured) virtual COM port, receiving into `bufs`.
+///
+/// Frames are at most `N` bytes (`N` < 65536), longer frames are split.
+pub fn usart2<const N: usize>(serial: Vcp, bufs: [&'static mut [u8; N]; 3]) -> (Rx<N>, Tx) {
+    assert!(N > 0 && N <= 0xFFFF);
+
+    // the HAL configured baud rate and pins, the registers are ours from now on
+    let (_usart, _pins) = serial.release();
+    let [m0, m1, spare] = bufs;
+
+    let (rcc, dma, usart) = unsafe { (&*RCC::get(), &*DMA::dma1(), &*USART::usart2()) };
+
+    ahb1enr::DMA1EN.modify(&rcc.AHB1ENR, 1);
+
+    let data = usart.DR.cell() as *const _ as u32;
+
+    // RX, circular double buffer mode, interrupt on each full buffer
+    let s = &dma.S[RX_STREAM];
+    stop(s);
+    s.PAR.write(data);
+    s.M0AR.write(m0.as_ptr() as u32);
+    s.M1AR.write(m1.as_ptr() as u32);
+    s.NDTR.write(ndtr::NDT.bits(N as u32));
+    s.CR.write(
+        cr::CHSEL.bits(CHANNEL)
+            | cr::DBM.bits(1)
+            | cr::CIRC.bits(1)
+            | cr::MINC.bits(1)
+            | cr::DIR.val(Dir::PeripheralToMemory)
+            | cr::TCIE.bits(1),
+    );
+    clear_rx_flags(dma);
+
+    // TX, a single buffer at a time, started by `Tx::write`
+    let s = &dma.S[TX_STREAM];
+    stop(s);
+    s.PAR.write(data);
+    s.CR.write(cr::CHSEL.bits(CHANNEL) | cr::MINC.bits(1) | cr::DIR.val(Dir::MemoryToPeripheral));
+
+    cr3::DMAR.modify(&usart.CR3, 1);
+    cr3::DMAT.modify(&usart.CR3, 1);
+    cr1::IDLEIE.modify(&usart.CR1, 1);
+
+    compiler_fence(Ordering::SeqCst);
+    cr::EN.modify(&dma.S[RX_STREAM].CR, 1);
+
+    (
+        Rx {
+            bufs: [m0, m1],
+            spare: Some(spare),
+            errors: Errors::default(),
+        },
+        Tx { _private: () },
+    )
+}
+
+// disables the stream, waiting for an ongoing transfer to finish (RM0368 9.3.17)
+fn stop(s: &Stream) {
+    cr::EN.modify(&s.CR, 0);
+    while cr::EN.read(&s.CR) != 0 {}
+}
+
+fn clear_rx_flags(dma: &DMA) {
+    dma.HIFCR.write(
+        hifcr::CFEIF5.bits(1)
+            | hifcr::CDMEIF5.bits(1)
+            | hifcr::CTEIF5.bits(1)
+            | hifcr::CHTIF5.bits(1)
+            | hifcr::CTCIF5.bits(1),
+    );
+}
+
+fn clear_tx_flags(dma: &DMA) {
+    dma.HIFCR.write(
+        hifcr::CFEIF6.bits(1)
+            | hifcr::CDMEIF6.bits(1)
+            | hifcr::CTEIF6.bits(1)
+            | hifcr::CHTIF6.bits(1)
+            | hifcr::CTCIF6.bits(1),
+    );
+}
+
+/// A received frame, owning its buffer until given back by `Rx::release`.
+pub struct Frame<const N: usize> {
+    buf: &'static mut [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Deref for Frame<N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// The receiving end, owning DMA1 stream 5.
+pub struct Rx<const N: usize> {
+    // the buffers loaded in M0AR and M1AR, owned by the DMA
+    bufs: [&'static mut [u8; N]; 2],
+    spare: Option<&'static mut [u8; N]>,
+    errors: Errors,
+}
+
+impl<const N: usize> Rx<N> {
+    /// Services the stream, to be called from both the `USART2` and the
+    /// `DMA1_STREAM5` interrupts.
+    ///
+    /// Returns at most one frame per call, a second pending event keeps its
+    /// interrupt pending.
+    pub fn on_interrupt(&mut self) -> Option<Frame<N>> {
+        let (dma, usart) = unsafe { (&*DMA::dma1(), &*USART::usart2()) };
+        let s = &dma.S[RX_STREAM];
+
+        if hisr::TCIF5.read(&dma.HISR) != 0 {
+            dma.HIFCR.write(hifcr::CTCIF5.bits(1));
+            // the DMA switched to the other buffer (CT), the previous one is full
+            let done = 1 - cr::CT.read(&s.CR) as usize;
+            return self.swap(s, done, N);
+        }
+
+        let status = usart.SR.read();
+        if sr::IDLE.bits(1) & status != 0 {
+            // reading DR after SR clears IDLE (and the error flags), the line
+            // is idle so no byte is taken from the DMA
+            let _ = usart.DR.read();
+            self.count(status);
+
+            stop(s);
+            clear_rx_flags(dma);
+            let current = cr::CT.read(&s.CR) as usize;
+            let len = N - ndtr::NDT.read(&s.NDTR) as usize;
+
+            let frame = if len == 0 {
+                None
+            } else {
+                self.swap(s, current, len)
+            };
+
+            // restart on the (new) current buffer
+            s.NDTR.write(ndtr::NDT.bits(N as u32));
+            compiler_fence(Ordering::SeqCst);
+            cr::EN.modify(&s.CR, 1);
+
+            return frame;
+        }
+
+        None
+    }
+
+    // hands out `bufs[target]` (holding `len` bytes), loading the spare in its place
+    fn swap(&mut self, s: &Stream, target: usize, len: usize) -> Option<Frame<N>> {
+        match self.spare.take() {
+            Some(spare) => {
+                let address = spare.as_ptr() as u32;
+                match target {
+                    0 => s.M0AR.write(address),
+                    _ => s.M1AR.write(address),
+                }
+                let buf = core::mem::replace(&mut self.bufs[target], spare);
+
+                compiler_fence(Ordering::SeqCst);
+                Some(Frame { buf, len })
+            }
+            None => {
+                // keep the buffer, the DMA overwrites it
+                self.errors.dropped += 1;
+                None
+            }
+        }
+    }
+
+    fn count(&mut self, status: u32) {
+        if sr::ORE.bits(1) & status != 0 {
+            self.errors.overrun += 1;
+        }
+        if sr::FE.bits(1) & status != 0 {
+            self.errors.framing += 1;
+        }
+        if sr::NF.bits(1) & status != 0 {
+            self.errors.noise += 1;
+        }
+    }
+
+    /// Gives the buffer of a handled frame back, to receive the next frame.
+    pub fn release(&mut self, frame: Frame<N>) {
+        self.spare = Some(frame.buf);
+    }
+
+    pub fn errors(&self) -> Errors {
+        self.errors
+    }
+}
+
+/// A buffer the DMA may read from.
+///
+/// # Safety
+///
+/// `as_slice` must return the same memory, valid for `'static`, on every
+/// call, the transfer runs on after `Tx::write` returns.
+pub unsafe trait TxBuffer {
+    fn as_slice(&self) -> &[u8];
+}
+
+unsafe impl TxBuffer for &'static [u8] {
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+}
+
+unsafe impl TxBuffer for &'static mut [u8] {
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+}
+
+unsafe impl<const N: usize> TxBuffer for &'static mut [u8; N] {
+    fn as_slice(&self) -> &[u8] {
+        &self[..]
+    }
+}
+
+// a received frame can be sent back as is
+unsafe impl<const N: usize> TxBuffer for Frame<N> {
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+}
+
+/// The transmitting end, owning DMA1 stream 6.
+pub struct Tx {
+    _private: (),
+}
+
+impl Tx {
+    /// Starts sending `buf`, which stays with the transfer until it is done.
+    pub fn write<B: TxBuffer>(self, buf: B) -> Transfer<B> {
+        let dma = unsafe { &*DMA::dma1() };
+        let s = &dma.S[TX_STREAM];
+        let bytes = buf.as_slice();
+        assert!(bytes.len() <= 0xFFFF);
+
+        clear_tx_flags(dma);
+        s.M0AR.write(bytes.as_ptr() as u32);
+        s.NDTR.write(ndtr::NDT.bits(bytes.len() as u32));
+
+        // the buffer must be written before the DMA reads it
+        compiler_fence(Ordering::SeqCst);
+        if !bytes.is_empty() {
+            cr::EN.modify(&s.CR, 1);
+        }
+
+        Transfer { tx: self, buf }
+    }
+}
+
+/// An ongoing transmission.
+pub struct Transfer<B> {
+    tx: Tx,
+    buf: B,
+}
+
+impl<B: TxBuffer> Transfer<B> {
+    /// The DMA has handed the last byte to the USART.
+    pub fn is_done(&self) -> bool {
+        let dma = unsafe { &*DMA::dma1() };
+        cr::EN.read(&dma.S[TX_STREAM].CR) == 0
+    }
+
+    /// Blocks until done, giving back the buffer and the `Tx`.
+    pub fn wait(self) -> (B, Tx) {
+        while !self.is_done() {}
+
+        clear_tx_flags(unsafe { &*DMA::dma1() });
+        compiler_fence(Ordering::SeqCst);
+        (self.buf, self.tx)
+    }
+}
@@ -0,0 +1,124 @@
+//! Task trace events over ITM
+//!
+//! Messages printed from RTFM tasks do not show when each task ran, or what
+//! it preempted. Instead, tasks and locks emit events to ITM stimulus port
+//! `PORT`, each stamped with the DWT cycle counter, and `events` (in
+//! `host/`) turns the stream into a waveform (VCD) or a Chrome trace (JSON):
+//!
+//! ``` ignore
+//! const TASK1: u16 = 2;
+//! const ITM: u16 = 0;
+//!
+//! // in `init`, before tasks run
+//! event::task(TASK1, "task1");
+//! event::resource(ITM, "itm");
+//!
+//! #[task(resources = [itm])]
+//! fn task1(mut cx: task1::Context) {
+//!     let _event = event::enter(TASK1);
+//!     cx.resources.itm.lock(|itm| {
+//!         let _event = event::lock(ITM);
+//!         ...
+//!     });
+//! }
+//! ```
+//!
+//! An event is a half word `kind << 12 | id` followed by the cycle counter
+//! (a word), written in a critical section so events are never interleaved.
+//! The names of tasks and resources follow their event byte by byte,
+//! ending with a 0:
+//!
+//! ``` text
+//! event = header (2) | cycles (4) [| name (1)* | 0 (1)]
+//! ```
+//!
+//! Events are dropped while the ITM or the port is disabled, enable port 3
+//! with `swo::Config::ports` or `monitor itm port 3 on`. The DWT cycle
+//! counter must be enabled.
+
+use cortex_m::{
+    interrupt,
+    peripheral::{DWT, ITM},
+};
+
+/// The stimulus port of the events.
+pub const PORT: usize = 3;
+
+/// Largest task or resource id.
+pub const MAX_ID: u16 = 0xFFF;
+
+/// The kind of an event, the high 4 bits of its header.
+pub mod kind {
+    /// Names a task, the name follows.
+    pub const TASK: u16 = 0;
+    /// Names a resource, the name follows.
+    pub const RESOURCE: u16 = 1;
+    pub const ENTER: u16 = 2;
+    pub const EXIT: u16 = 3;
+    pub const LOCK: u16 = 4;
+    pub const UNLOCK: u16 = 5;
+}
+
+// NOTE(unsafe) events only use `PORT` (in a critical section), so they do
+// not interfere with the owner of the `ITM` writing to other ports
+fn emit(event: u16, id: u16, name: &str) {
+    interrupt::free(|_| unsafe {
+        let itm = &mut *ITM::ptr();
+        // writing to a disabled port would wait forever for the FIFO
+        if itm.tcr.read() & 1 == 0 || itm.ter[0].read() & (1 << PORT) == 0 {
+            return;
+        }
+        let stim = &mut itm.stim[PORT];
+        while !stim.is_fifo_ready() {}
+        stim.write_u16(event << 12 | (id & MAX_ID));
+        while !stim.is_fifo_ready() {}
+        stim.write_u32(DWT::get_cycle_count());
+        if event == kind::TASK || event == kind::RESOURCE {
+            for &b in name.as_bytes().iter().chain(&[0]) {
+                while !stim.is_fifo_ready() {}
+                stim.write_u8(b);
+            }
+        }
+    });
+}
+
+/// Names task `id`.
+pub fn task(id: u16, name: &str) {
+    emit(kind::TASK, id, name);
+}
+
+/// Names resource `id`.
+pub fn resource(id: u16, name: &str) {
+    emit(kind::RESOURCE, id, name);
+}
+
+/// An event to emit when dropped.
+#[must_use]
+pub struct Scope {
+    kind: u16,
+    id: u16,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        emit(self.kind, self.id, "");
+    }
+}
+
+/// Task `id` starts, and exits when the scope is dropped.
+pub fn enter(id: u16) -> Scope {
+    emit(kind::ENTER, id, "");
+    Scope {
+        kind: kind::EXIT,
+        id,
+    }
+}
+
+/// Resource `id` is locked, and unlocked when the scope is dropped.
+pub fn lock(id: u16) -> Scope {
+    emit(kind::LOCK, id, "");
+    Scope {
+        kind: kind::UNLOCK,
+        id,
+    }
+}
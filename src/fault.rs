@@ -0,0 +1,285 @@
+//! Fault status decoding
+//!
+//! The exception frame tells where a fault happened, the fault status
+//! registers of the SCB tell why. `Registers::read` takes a snapshot of them
+//! (in the `HardFault` handler, or the handler of the configurable fault)
+//! and its `Display` lists the causes in words, the fault addresses included
+//! when valid:
+//!
+//! ``` ignore
+//! #[exception]
+//! fn HardFault(ef: &ExceptionFrame) -> ! {
+//!     let regs = unsafe { fault::Registers::read() };
+//!     panic!("{}{:?}", regs, ef);
+//! }
+//! ```
+//!
+//! ``` text
+//! HardFault, escalated from a configurable fault
+//!   precise data bus error at 0x2fffffff
+//! CFSR=0x00008200 HFSR=0x40000000 MMFAR=0x2fffffff BFAR=0x2fffffff AFSR=0x00000000
+//! ```
+//!
+//! The module is plain `core`, shared with the host library in `host/`.
+//!
+//! see ARMv7-M ARM B3.2.15 to B3.2.19, and PM0214 4.4.14 to 4.4.19
+
+use core::fmt;
+
+/// Configurable Fault Status Register.
+pub const CFSR: u32 = 0xE000_ED28;
+/// HardFault Status Register.
+pub const HFSR: u32 = 0xE000_ED2C;
+/// MemManage Fault Address Register.
+pub const MMFAR: u32 = 0xE000_ED34;
+/// BusFault Address Register.
+pub const BFAR: u32 = 0xE000_ED38;
+/// Auxiliary Fault Status Register.
+pub const AFSR: u32 = 0xE000_ED3C;
+
+/// `CFSR` bits, MemManage (`MMFSR`, bits 0-7), BusFault (`BFSR`, bits 8-15)
+/// and UsageFault (`UFSR`, bits 16-31).
+pub mod cfsr {
+    pub const IACCVIOL: u32 = 1 << 0;
+    pub const DACCVIOL: u32 = 1 << 1;
+    pub const MUNSTKERR: u32 = 1 << 3;
+    pub const MSTKERR: u32 = 1 << 4;
+    pub const MLSPERR: u32 = 1 << 5;
+    pub const MMARVALID: u32 = 1 << 7;
+
+    pub const IBUSERR: u32 = 1 << 8;
+    pub const PRECISERR: u32 = 1 << 9;
+    pub const IMPRECISERR: u32 = 1 << 10;
+    pub const UNSTKERR: u32 = 1 << 11;
+    pub const STKERR: u32 = 1 << 12;
+    pub const LSPERR: u32 = 1 << 13;
+    pub const BFARVALID: u32 = 1 << 15;
+
+    pub const UNDEFINSTR: u32 = 1 << 16;
+    pub const INVSTATE: u32 = 1 << 17;
+    pub const INVPC: u32 = 1 << 18;
+    pub const NOCP: u32 = 1 << 19;
+    pub const UNALIGNED: u32 = 1 << 24;
+    pub const DIVBYZERO: u32 = 1 << 25;
+}
+
+/// `HFSR` bits.
+pub mod hfsr {
+    pub const VECTTBL: u32 = 1 << 1;
+    pub const FORCED: u32 = 1 << 30;
+    pub const DEBUGEVT: u32 = 1 << 31;
+}
+
+/// A reason for a fault.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cause {
+    // MemManage
+    InstructionAccessViolation,
+    /// At the address, if known.
+    DataAccessViolation(Option<u32>),
+    MemManageUnstacking,
+    MemManageStacking,
+    MemManageLazyFp,
+    // BusFault
+    InstructionBusError,
+    /// At the address, if known.
+    PreciseBusError(Option<u32>),
+    ImpreciseBusError,
+    BusUnstacking,
+    BusStacking,
+    BusLazyFp,
+    // UsageFault
+    UndefinedInstruction,
+    InvalidState,
+    InvalidPc,
+    NoCoprocessor,
+    Unaligned,
+    DivideByZero,
+    // HardFault
+    VectorTable,
+    Forced,
+    Debug,
+}
+
+impl Cause {
+    /// Whether the fault hit the stack limit, pushing or popping the
+    /// exception frame.
+    pub fn is_stack_overflow(self) -> bool {
+        matches!(
+            self,
+            Cause::MemManageStacking
+                | Cause::MemManageUnstacking
+                | Cause::BusStacking
+                | Cause::BusUnstacking
+        )
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn at(f: &mut fmt::Formatter<'_>, what: &str, address: Option<u32>) -> fmt::Result {
+            match address {
+                Some(address) => write!(f, "{} at {:#010x}", what, address),
+                None => write!(f, "{} (address unknown)", what),
+            }
+        }
+
+        match *self {
+            Cause::InstructionAccessViolation => {
+                f.write_str("instruction fetch from a protected (or XN) region")
+            }
+            Cause::DataAccessViolation(a) => at(f, "data access violation", a),
+            Cause::MemManageUnstacking => {
+                f.write_str("stack overflow on exception return (MPU fault unstacking)")
+            }
+            Cause::MemManageStacking => {
+                f.write_str("stack overflow on exception entry (MPU fault stacking)")
+            }
+            Cause::MemManageLazyFp => f.write_str("MPU fault saving the FPU state"),
+            Cause::InstructionBusError => f.write_str("bus error on instruction fetch"),
+            Cause::PreciseBusError(a) => at(f, "precise data bus error", a),
+            Cause::ImpreciseBusError => {
+                f.write_str("imprecise data bus error (the frame pc is past the access)")
+            }
+            Cause::BusUnstacking => {
+                f.write_str("stack overflow on exception return (bus fault unstacking)")
+            }
+            Cause::BusStacking => {
+                f.write_str("stack overflow on exception entry (bus fault stacking)")
+            }
+            Cause::BusLazyFp => f.write_str("bus fault saving the FPU state"),
+            Cause::UndefinedInstruction => f.write_str("undefined instruction"),
+            Cause::InvalidState => f.write_str(
+                "invalid EPSR state (e.g., a branch to an even address, Thumb bit clear)",
+            ),
+            Cause::InvalidPc => f.write_str("invalid EXC_RETURN on exception return"),
+            Cause::NoCoprocessor => f.write_str("coprocessor (FPU) access while disabled"),
+            Cause::Unaligned => f.write_str("unaligned access"),
+            Cause::DivideByZero => f.write_str("divide by zero"),
+            Cause::VectorTable => f.write_str("bus fault reading the vector table"),
+            Cause::Forced => f.write_str("escalated from a configurable fault"),
+            Cause::Debug => f.write_str("debug event (e.g., `bkpt` without a debugger)"),
+        }
+    }
+}
+
+// the causes, in the order of their bits
+const CAUSES: [(bool, u32, Cause); 20] = [
+    (true, hfsr::VECTTBL, Cause::VectorTable),
+    (true, hfsr::FORCED, Cause::Forced),
+    (true, hfsr::DEBUGEVT, Cause::Debug),
+    (false, cfsr::IACCVIOL, Cause::InstructionAccessViolation),
+    (false, cfsr::DACCVIOL, Cause::DataAccessViolation(None)),
+    (false, cfsr::MUNSTKERR, Cause::MemManageUnstacking),
+    (false, cfsr::MSTKERR, Cause::MemManageStacking),
+    (false, cfsr::MLSPERR, Cause::MemManageLazyFp),
+    (false, cfsr::IBUSERR, Cause::InstructionBusError),
+    (false, cfsr::PRECISERR, Cause::PreciseBusError(None)),
+    (false, cfsr::IMPRECISERR, Cause::ImpreciseBusError),
+    (false, cfsr::UNSTKERR, Cause::BusUnstacking),
+    (false, cfsr::STKERR, Cause::BusStacking),
+    (false, cfsr::LSPERR, Cause::BusLazyFp),
+    (false, cfsr::UNDEFINSTR, Cause::UndefinedInstruction),
+    (false, cfsr::INVSTATE, Cause::InvalidState),
+    (false, cfsr::INVPC, Cause::InvalidPc),
+    (false, cfsr::NOCP, Cause::NoCoprocessor),
+    (false, cfsr::UNALIGNED, Cause::Unaligned),
+    (false, cfsr::DIVBYZERO, Cause::DivideByZero),
+];
+
+/// A snapshot of the fault status registers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Registers {
+    pub cfsr: u32,
+    pub hfsr: u32,
+    pub mmfar: u32,
+    pub bfar: u32,
+    pub afsr: u32,
+}
+
+impl Registers {
+    /// Reads the registers of the SCB.
+    ///
+    /// # Safety
+    ///
+    /// Only on the target, the addresses are those of a Cortex-M.
+    pub unsafe fn read() -> Self {
+        let read = |address: u32| core::ptr::read_volatile(address as *const u32);
+        Registers {
+            cfsr: read(CFSR),
+            hfsr: read(HFSR),
+            mmfar: read(MMFAR),
+            bfar: read(BFAR),
+            afsr: read(AFSR),
+        }
+    }
+
+    /// The memory management fault address, if valid.
+    pub fn mmfar(&self) -> Option<u32> {
+        Some(self.mmfar).filter(|_| self.cfsr & cfsr::MMARVALID != 0)
+    }
+
+    /// The bus fault address, if valid.
+    pub fn bfar(&self) -> Option<u32> {
+        Some(self.bfar).filter(|_| self.cfsr & cfsr::BFARVALID != 0)
+    }
+
+    /// The causes flagged, HardFault ones first.
+    pub fn causes(&self) -> impl Iterator<Item = Cause> + '_ {
+        CAUSES.iter().filter_map(move |&(hard, bit, cause)| {
+            let register = if hard { self.hfsr } else { self.cfsr };
+            if register & bit == 0 {
+                return None;
+            }
+            Some(match cause {
+                Cause::DataAccessViolation(_) => Cause::DataAccessViolation(self.mmfar()),
+                Cause::PreciseBusError(_) => Cause::PreciseBusError(self.bfar()),
+                cause => cause,
+            })
+        })
+    }
+
+    /// Whether a stack overflow caused the fault.
+    pub fn is_stack_overflow(&self) -> bool {
+        self.causes().any(Cause::is_stack_overflow)
+    }
+}
+
+impl fmt::Display for Registers {
+    /// The exception (`HardFault` or the configurable fault handled
+    /// directly), a line per cause and the raw registers.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (mut causes, mut n) = (self.causes(), 0);
+        if self.hfsr != 0 {
+            f.write_str("HardFault")?;
+            // the first, most specific, HardFault cause on the same line
+            if let Some(cause) = causes.next() {
+                write!(f, ", {}", cause)?;
+            }
+        } else {
+            let fault = if self.cfsr & 0xFF != 0 {
+                "MemManage"
+            } else if self.cfsr & 0xFF00 != 0 {
+                "BusFault"
+            } else if self.cfsr != 0 {
+                "UsageFault"
+            } else {
+                "no fault recorded"
+            };
+            f.write_str(fault)?;
+        }
+        f.write_str("\n")?;
+        for cause in causes {
+            writeln!(f, "  {}", cause)?;
+            n += 1;
+        }
+        if n == 0 && self.cfsr == 0 && self.hfsr & hfsr::FORCED != 0 {
+            writeln!(f, "  (configurable fault status cleared)")?;
+        }
+        writeln!(
+            f,
+            "CFSR={:#010x} HFSR={:#010x} MMFAR={:#010x} BFAR={:#010x} AFSR={:#010x}",
+            self.cfsr, self.hfsr, self.mmfar, self.bfar, self.afsr
+        )
+    }
+}
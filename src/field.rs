@@ -0,0 +1,198 @@
+//! Type-safe register fields
+//!
+//! `VolatileCell::modify(offset, width, value)` (see `examples/bare5.rs`) lets
+//! us pass any offset and width, and a wrong width silently clobbers the
+//! neighbouring bits. Here the offset and width are part of the *type* of a
+//! field, checked at compile time, and each field can only be applied to the
+//! register it belongs to.
+//!
+//! ``` ignore
+//! use app::stm32f40x::{gpio::{moder, Mode}, rcc::ahb1enr};
+//!
+//! ahb1enr::GPIOAEN.modify(&rcc.AHB1ENR, 1);       // power on GPIOA
+//! moder::MODER5.set(&gpioa.MODER, Mode::Output);  // PA5 as output
+//! ```
+//!
+//! The storage behind a register is anything implementing `Access`, that is
+//! a `VolatileCell<u32>` (memory mapped hardware) or a `Cell<u32>` (plain
+//! memory, e.g., for running on the host).
+
+use core::{cell::Cell, marker::PhantomData, ops::Deref};
+
+use crate::stm32f40x::VolatileCell;
+
+/// Read/write access to a 32 bit register.
+pub trait Access {
+    fn read(&self) -> u32;
+    fn write(&self, value: u32);
+}
+
+impl Access for VolatileCell<u32> {
+    #[inline(always)]
+    fn read(&self) -> u32 {
+        VolatileCell::read(self)
+    }
+
+    #[inline(always)]
+    fn write(&self, value: u32) {
+        VolatileCell::write(self, value)
+    }
+}
+
+impl Access for Cell<u32> {
+    #[inline(always)]
+    fn read(&self) -> u32 {
+        self.get()
+    }
+
+    #[inline(always)]
+    fn write(&self, value: u32) {
+        self.set(value)
+    }
+}
+
+/// A register of type `REG`, stored in `A`.
+///
+/// `REG` is a (zero sized) marker type, so `Reg<REG, A>` has the same layout
+/// as `A` and can be used in `#[repr(C)]` register blocks.
+#[repr(transparent)]
+pub struct Reg<REG, A = VolatileCell<u32>> {
+    cell: A,
+    _reg: PhantomData<REG>,
+}
+
+impl<REG, A> Reg<REG, A> {
+    pub const fn new(cell: A) -> Self {
+        Reg {
+            cell,
+            _reg: PhantomData,
+        }
+    }
+
+    /// The underlying storage.
+    pub fn cell(&self) -> &A {
+        &self.cell
+    }
+}
+
+impl<REG, A: Access> Reg<REG, A> {
+    #[inline(always)]
+    pub fn read(&self) -> u32 {
+        self.cell.read()
+    }
+
+    #[inline(always)]
+    pub fn write(&self, value: u32) {
+        self.cell.write(value)
+    }
+}
+
+// gives access to the untyped API, e.g., `VolatileCell::modify`
+impl<REG, A> Deref for Reg<REG, A> {
+    type Target = A;
+
+    fn deref(&self) -> &A {
+        &self.cell
+    }
+}
+
+/// An enumerated field value, e.g., `gpio::Mode`.
+pub trait Value: Copy {
+    /// Width (in bits) of the field the value fits.
+    const WIDTH: u8;
+
+    fn bits(self) -> u32;
+
+    /// `None` for bit patterns that are reserved.
+    fn from_bits(bits: u32) -> Option<Self>;
+}
+
+/// A field of register `REG`, `WIDTH` bits wide starting at bit `OFFSET`.
+///
+/// A field not fitting a 32 bit register is rejected at compile time.
+pub struct Field<REG, const OFFSET: u8, const WIDTH: u8> {
+    _reg: PhantomData<REG>,
+}
+
+// implemented by hand, `derive` would require `REG: Copy`
+impl<REG, const OFFSET: u8, const WIDTH: u8> Clone for Field<REG, OFFSET, WIDTH> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<REG, const OFFSET: u8, const WIDTH: u8> Copy for Field<REG, OFFSET, WIDTH> {}
+
+impl<REG, const OFFSET: u8, const WIDTH: u8> Default for Field<REG, OFFSET, WIDTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<REG, const OFFSET: u8, const WIDTH: u8> Field<REG, OFFSET, WIDTH> {
+    const FITS: () = assert!(
+        WIDTH > 0 && OFFSET as u32 + WIDTH as u32 <= 32,
+        "field does not fit a 32 bit register"
+    );
+
+    /// Mask of the field bits (in place).
+    pub const MASK: u32 = (u32::MAX >> (32 - WIDTH as u32)) << OFFSET;
+
+    pub const OFFSET: u8 = OFFSET;
+    pub const WIDTH: u8 = WIDTH;
+
+    pub const fn new() -> Self {
+        let () = Self::FITS;
+        Field { _reg: PhantomData }
+    }
+
+    /// The register value with `value` in the field (and all other bits 0).
+    ///
+    /// Useful for write-only registers like `BSRR`, or for masks in `const`
+    /// tables.
+    #[inline(always)]
+    pub const fn bits(self, value: u32) -> u32 {
+        debug_assert!(value <= Self::MASK >> OFFSET, "value does not fit field");
+        (value << OFFSET) & Self::MASK
+    }
+
+    /// Reads the field (shifted down to bit 0).
+    #[inline(always)]
+    pub fn read<A: Access>(self, reg: &Reg<REG, A>) -> u32 {
+        (reg.read() & Self::MASK) >> OFFSET
+    }
+
+    /// Reads the register, replaces the field by `value` and writes it back.
+    #[inline(always)]
+    pub fn modify<A: Access>(self, reg: &Reg<REG, A>, value: u32) {
+        reg.write(reg.read() & !Self::MASK | self.bits(value));
+    }
+
+    /// As `bits`, for an enumerated value of matching width.
+    #[inline(always)]
+    pub fn val<V: Value>(self, value: V) -> u32 {
+        let () = SameWidth::<V, WIDTH>::OK;
+        self.bits(value.bits())
+    }
+
+    /// Reads the field as an enumerated value, `None` if reserved.
+    #[inline(always)]
+    pub fn get<V: Value, A: Access>(self, reg: &Reg<REG, A>) -> Option<V> {
+        let () = SameWidth::<V, WIDTH>::OK;
+        V::from_bits(self.read(reg))
+    }
+
+    /// As `modify`, for an enumerated value of matching width.
+    #[inline(always)]
+    pub fn set<V: Value, A: Access>(self, reg: &Reg<REG, A>, value: V) {
+        let () = SameWidth::<V, WIDTH>::OK;
+        self.modify(reg, value.bits());
+    }
+}
+
+// compile time check that a value fits the field it is used with
+struct SameWidth<V, const WIDTH: u8>(PhantomData<V>);
+
+impl<V: Value, const WIDTH: u8> SameWidth<V, WIDTH> {
+    const OK: () = assert!(V::WIDTH == WIDTH, "value width does not match field");
+}
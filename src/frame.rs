@@ -0,0 +1,454 @@
+//! Framed packets over a byte stream
+//!
+//! Each packet is protected by a CRC and COBS encoded (Consistent Overhead
+//! Byte Stuffing), so that `0x00` only ever appears as the frame delimiter
+//! and a receiver can resynchronize after lost or corrupted bytes:
+//!
+//! ``` text
+//! packet = kind (1) | seq (1) | payload (0..=MAX_PAYLOAD) | crc (2, little endian)
+//! frame  = cobs(packet) | 0x00
+//! ```
+//!
+//! The CRC is CRC-16/CCITT-FALSE (polynomial `0x1021`, initial `0xFFFF`)
+//! over kind, seq and payload.
+//!
+//! `Link` adds reliable delivery on top (stop-and-wait ARQ): each `Data`
+//! packet is answered by an `Ack` carrying its sequence number, a corrupted
+//! frame by a `Nak`, and an unacknowledged packet is sent again after
+//! `TIMEOUT` ms, at most `MAX_RETRIES` times.
+//!
+//! The module is plain `core`, shared with the host library in `host/`.
+
+/// Largest payload of a packet.
+pub const MAX_PAYLOAD: usize = 128;
+/// Largest packet, kind, seq and CRC included.
+pub const MAX_PACKET: usize = MAX_PAYLOAD + 4;
+/// Largest frame, delimiter included.
+pub const MAX_FRAME: usize = cobs::max_encoded_len(MAX_PACKET) + 1;
+
+/// Time (ms) to wait for an `Ack` before sending again.
+pub const TIMEOUT: u32 = 100;
+/// Number of times a packet is sent again before giving up.
+pub const MAX_RETRIES: u8 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The payload is larger than `MAX_PAYLOAD`.
+    TooLong,
+    /// The frame is larger than `MAX_FRAME`.
+    Overflow,
+    /// Malformed COBS encoding.
+    Cobs,
+    /// The frame is too short to hold a packet.
+    Short,
+    /// Checksum mismatch.
+    Crc,
+    /// Unknown packet kind.
+    Kind,
+    /// A packet is still waiting for its `Ack`.
+    Busy,
+    /// No `Ack` after `MAX_RETRIES`, the packet is dropped.
+    Timeout,
+}
+
+pub mod cobs {
+    //! Consistent Overhead Byte Stuffing (Cheshire and Baker, 1999)
+
+    use super::Error;
+
+    /// Worst case size of `n` encoded bytes (one code byte per 254 bytes).
+    pub const fn max_encoded_len(n: usize) -> usize {
+        n + n / 254 + 1
+    }
+
+    /// Encodes `src` into `dst`, returning the number of bytes written.
+    ///
+    /// Panics if `dst` is shorter than `max_encoded_len(src.len())`.
+    pub fn encode(src: &[u8], dst: &mut [u8]) -> usize {
+        let mut code_at = 0;
+        let mut out = 1;
+        let mut code = 1u8;
+
+        for &byte in src {
+            if byte != 0 {
+                dst[out] = byte;
+                out += 1;
+                code += 1;
+            }
+            if byte == 0 || code == 0xFF {
+                dst[code_at] = code;
+                code_at = out;
+                out += 1;
+                code = 1;
+            }
+        }
+        dst[code_at] = code;
+        out
+    }
+
+    /// Decodes `src` (without the delimiter) into `dst`, returning the number
+    /// of bytes written.
+    pub fn decode(src: &[u8], dst: &mut [u8]) -> Result<usize, Error> {
+        let mut i = 0;
+        let mut out = 0;
+
+        while i < src.len() {
+            let code = src[i];
+            if code == 0 {
+                return Err(Error::Cobs);
+            }
+            i += 1;
+
+            for _ in 1..code {
+                let byte = *src.get(i).ok_or(Error::Cobs)?;
+                if byte == 0 {
+                    return Err(Error::Cobs);
+                }
+                *dst.get_mut(out).ok_or(Error::Overflow)? = byte;
+                out += 1;
+                i += 1;
+            }
+
+            // a maximal block is not followed by an implicit zero, nor is the last
+            if code != 0xFF && i < src.len() {
+                *dst.get_mut(out).ok_or(Error::Overflow)? = 0;
+                out += 1;
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// CRC-16/CCITT-FALSE, `crc16(b"123456789") == 0x29B1`.
+pub fn crc16(data: &[u8]) -> u16 {
+    crc16_update(0xFFFF, data)
+}
+
+/// Continues `crc16` over more `data`, for data that comes in pieces.
+pub fn crc16_update(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Data = 1,
+    Ack = 2,
+    Nak = 3,
+}
+
+impl Kind {
+    fn from_u8(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(Kind::Data),
+            2 => Some(Kind::Ack),
+            3 => Some(Kind::Nak),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Packet<'a> {
+    pub kind: Kind,
+    pub seq: u8,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Packet<'a> {
+    /// Encodes the packet into `frame`, returning the frame length
+    /// (delimiter included).
+    pub fn encode(&self, frame: &mut [u8; MAX_FRAME]) -> Result<usize, Error> {
+        let n = self.payload.len();
+        if n > MAX_PAYLOAD {
+            return Err(Error::TooLong);
+        }
+
+        let mut packet = [0; MAX_PACKET];
+        packet[0] = self.kind as u8;
+        packet[1] = self.seq;
+        packet[2..2 + n].copy_from_slice(self.payload);
+        let crc = crc16(&packet[..2 + n]);
+        packet[2 + n..4 + n].copy_from_slice(&crc.to_le_bytes());
+
+        let len = cobs::encode(&packet[..4 + n], frame);
+        frame[len] = 0;
+        Ok(len + 1)
+    }
+
+    /// Parses a decoded (un-stuffed) packet, checking its CRC.
+    pub fn parse(packet: &'a [u8]) -> Result<Self, Error> {
+        if packet.len() < 4 {
+            return Err(Error::Short);
+        }
+        let (body, crc) = packet.split_at(packet.len() - 2);
+        if crc16(body).to_le_bytes() != crc {
+            return Err(Error::Crc);
+        }
+
+        Ok(Packet {
+            kind: Kind::from_u8(body[0]).ok_or(Error::Kind)?,
+            seq: body[1],
+            payload: &body[2..],
+        })
+    }
+}
+
+/// Collects received bytes into frames.
+pub struct Decoder {
+    frame: [u8; MAX_FRAME],
+    len: usize,
+    overflow: bool,
+    packet: [u8; MAX_PACKET],
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Decoder {
+            frame: [0; MAX_FRAME],
+            len: 0,
+            overflow: false,
+            packet: [0; MAX_PACKET],
+        }
+    }
+
+    /// Adds a received byte, returning the packet (or error) when it ends a
+    /// frame.
+    pub fn feed(&mut self, byte: u8) -> Option<Result<Packet<'_>, Error>> {
+        if byte != 0 {
+            if self.len < MAX_FRAME {
+                self.frame[self.len] = byte;
+                self.len += 1;
+            } else {
+                self.overflow = true;
+            }
+            return None;
+        }
+
+        let len = core::mem::replace(&mut self.len, 0);
+        if core::mem::replace(&mut self.overflow, false) {
+            return Some(Err(Error::Overflow));
+        }
+        if len == 0 {
+            // back to back delimiters, e.g., used to flush the line
+            return None;
+        }
+
+        let n = match cobs::decode(&self.frame[..len], &mut self.packet) {
+            Ok(n) => n,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(Packet::parse(&self.packet[..n]))
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where `Link` sends its frames, e.g., the serial port.
+pub trait Sink {
+    fn send(&mut self, frame: &[u8]);
+}
+
+/// Link counters, since start.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// `Data` packets sent, not counting retransmissions.
+    pub sent: u32,
+    pub retransmitted: u32,
+    /// `Data` packets that were never acknowledged.
+    pub failed: u32,
+    pub delivered: u32,
+    /// Received again, as our `Ack` was lost.
+    pub duplicates: u32,
+    /// Corrupted frames received (answered by a `Nak`).
+    pub errors: u32,
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Stats {
+            sent: 0,
+            retransmitted: 0,
+            failed: 0,
+            delivered: 0,
+            duplicates: 0,
+            errors: 0,
+        }
+    }
+}
+
+/// Reliable, in order delivery of packets, one in flight at a time.
+///
+/// Time is passed in by the caller as a free running ms counter (wrapping).
+pub struct Link {
+    decoder: Decoder,
+    // transmit side, `seq` of the packet in flight (or the next one)
+    seq: u8,
+    pending: Option<usize>,
+    buf: [u8; MAX_PAYLOAD],
+    sent_at: u32,
+    retries: u8,
+    // receive side, the last packet delivered
+    last: Option<u8>,
+    received: [u8; MAX_PAYLOAD],
+    stats: Stats,
+}
+
+impl Link {
+    pub const fn new() -> Self {
+        Link {
+            decoder: Decoder::new(),
+            seq: 0,
+            pending: None,
+            buf: [0; MAX_PAYLOAD],
+            sent_at: 0,
+            retries: 0,
+            last: None,
+            received: [0; MAX_PAYLOAD],
+            stats: Stats::new(),
+        }
+    }
+
+    /// No packet waiting for its `Ack`, `send` will succeed.
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Sends `payload`, kept for retransmission until acknowledged.
+    pub fn send(&mut self, payload: &[u8], now: u32, sink: &mut dyn Sink) -> Result<(), Error> {
+        if payload.len() > MAX_PAYLOAD {
+            return Err(Error::TooLong);
+        }
+        if self.pending.is_some() {
+            return Err(Error::Busy);
+        }
+
+        self.buf[..payload.len()].copy_from_slice(payload);
+        self.pending = Some(payload.len());
+        self.retries = 0;
+        self.stats.sent += 1;
+        self.transmit(now, sink);
+        Ok(())
+    }
+
+    /// Sends the packet in flight again if its `Ack` is overdue, to be called
+    /// periodically.
+    pub fn poll(&mut self, now: u32, sink: &mut dyn Sink) -> Result<(), Error> {
+        if self.pending.is_some() && now.wrapping_sub(self.sent_at) >= TIMEOUT {
+            self.retransmit(now, sink)?;
+        }
+        Ok(())
+    }
+
+    /// Handles a received byte, returning the payload of a newly received
+    /// `Data` packet.
+    pub fn receive(&mut self, byte: u8, now: u32, sink: &mut dyn Sink) -> Option<&[u8]> {
+        let (kind, seq, n) = match self.decoder.feed(byte)? {
+            Ok(packet) => {
+                let n = packet.payload.len();
+                self.received[..n].copy_from_slice(packet.payload);
+                (packet.kind, packet.seq, n)
+            }
+            Err(_) => {
+                self.stats.errors += 1;
+                reply(Kind::Nak, self.last.map_or(0, |s| s.wrapping_add(1)), sink);
+                return None;
+            }
+        };
+
+        match kind {
+            Kind::Data => {
+                // acknowledge also duplicates, the sender missed our `Ack`
+                reply(Kind::Ack, seq, sink);
+                if self.last == Some(seq) {
+                    self.stats.duplicates += 1;
+                    None
+                } else {
+                    // any other number is accepted, so a restarted peer is
+                    // picked up without a handshake
+                    self.last = Some(seq);
+                    self.stats.delivered += 1;
+                    Some(&self.received[..n])
+                }
+            }
+            Kind::Ack => {
+                if self.pending.is_some() && seq == self.seq {
+                    self.pending = None;
+                    self.seq = self.seq.wrapping_add(1);
+                }
+                None
+            }
+            Kind::Nak => {
+                if self.pending.is_some() {
+                    // giving up is counted in `Stats::failed`
+                    let _ = self.retransmit(now, sink);
+                }
+                None
+            }
+        }
+    }
+
+    fn retransmit(&mut self, now: u32, sink: &mut dyn Sink) -> Result<(), Error> {
+        if self.retries == MAX_RETRIES {
+            self.pending = None;
+            self.seq = self.seq.wrapping_add(1);
+            self.stats.failed += 1;
+            return Err(Error::Timeout);
+        }
+        self.retries += 1;
+        self.stats.retransmitted += 1;
+        self.transmit(now, sink);
+        Ok(())
+    }
+
+    fn transmit(&mut self, now: u32, sink: &mut dyn Sink) {
+        if let Some(n) = self.pending {
+            let mut frame = [0; MAX_FRAME];
+            let packet = Packet {
+                kind: Kind::Data,
+                seq: self.seq,
+                payload: &self.buf[..n],
+            };
+            // the length is checked by `send`
+            if let Ok(len) = packet.encode(&mut frame) {
+                sink.send(&frame[..len]);
+            }
+            self.sent_at = now;
+        }
+    }
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn reply(kind: Kind, seq: u8, sink: &mut dyn Sink) {
+    let mut frame = [0; MAX_FRAME];
+    let packet = Packet {
+        kind,
+        seq,
+        payload: &[],
+    };
+    if let Ok(len) = packet.encode(&mut frame) {
+        sink.send(&frame[..len]);
+    }
+}
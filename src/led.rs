@@ -0,0 +1,28 @@
+//! The user LED (LD2) on PA5, using the `stm32f40x` register blocks
+//!
+//! Generic over the register storage, so it runs both on the MCU and against
+//! the `mock` register file.
+
+use crate::field::Access;
+use crate::stm32f40x::{
+    gpio::{bsrr, moder, Mode},
+    rcc::ahb1enr,
+    GPIOA, RCC,
+};
+
+/// Powers GPIOA and configures PA5 as output (RM0368 6.3.9, 8.4.1).
+pub fn init<A: Access>(rcc: &RCC<A>, gpioa: &GPIOA<A>) {
+    // the port must be powered before it is configured
+    ahb1enr::GPIOAEN.modify(&rcc.AHB1ENR, 1);
+    moder::MODER5.set(&gpioa.MODER, Mode::Output);
+}
+
+/// Sets PA5 high (RM0368 8.4.7).
+pub fn on<A: Access>(gpioa: &GPIOA<A>) {
+    gpioa.BSRR.write(bsrr::BS5.bits(1));
+}
+
+/// Sets PA5 low (RM0368 8.4.7).
+pub fn off<A: Access>(gpioa: &GPIOA<A>) {
+    gpioa.BSRR.write(bsrr::BR5.bits(1));
+}
@@ -0,0 +1,41 @@
+//! Library part of `app`
+//!
+//! Peripheral abstractions shared by the examples. Run `cargo doc --open` to
+//! browse the API.
+
+#![no_std]
+
+#[cfg(all(feature = "f401", feature = "f411"))]
+compile_error!("select one MCU, `f411` needs `--no-default-features`");
+#[cfg(not(any(feature = "f401", feature = "f411")))]
+compile_error!("select the MCU, the `f401` or `f411` feature");
+
+pub mod backtrace;
+pub mod bench;
+#[cfg(feature = "stm32f4xx-hal")]
+pub mod board;
+pub mod boot;
+pub mod clocks;
+pub mod coredump;
+pub mod crash;
+pub mod defer;
+#[cfg(feature = "stm32f4xx-hal")]
+pub mod dma;
+pub mod event;
+pub mod fault;
+pub mod field;
+pub mod frame;
+pub mod led;
+pub mod log;
+pub mod mock;
+pub mod panic_serial;
+pub mod reboot;
+pub mod ring;
+#[cfg(feature = "stm32f4xx-hal")]
+pub mod serial;
+pub mod shell;
+pub mod stm32f40x;
+pub mod swo;
+pub mod time;
+pub mod trace;
+pub mod wcet;
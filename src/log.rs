@@ -0,0 +1,244 @@
+//! Leveled logging over ITM
+//!
+//! The `error!`, `warn!`, `info!`, `debug!` and `trace!` macros format a
+//! record and send it to the ITM stimulus port of its level:
+//!
+//! ``` text
+//! port 0   info                (the same port as plain `iprintln!`)
+//! port 1   error, warn
+//! port 2   debug, trace
+//! ```
+//!
+//! (`openocd.gdb` enables ports 0, 1 and 2). Each record is a line, tagged
+//! with the first letter of its level and the module it came from:
+//!
+//! ``` text
+//! W app::serial: overrun
+//! ```
+//!
+//! which `itm -l <level>` (in `host/`) filters and colours.
+//!
+//! Levels are filtered at compile time, records above `LOG_LEVEL` are not
+//! even formatted. The default is `Info` (`Debug` or `Trace` with the
+//! `log-debug` or `log-trace` features), a module may set its own by
+//! shadowing the one of the prelude:
+//!
+//! ``` ignore
+//! use app::log::prelude::*;
+//!
+//! const LOG_LEVEL: Level = Level::Trace; // this module only
+//!
+//! app::log::init(p.ITM);
+//! info!("sysclk {} Hz", clocks.sysclk());
+//! trace!("entering {}", "main");
+//! ```
+//!
+//! With the `log-deferred` feature records are not formatted on the target,
+//! only the index of the interned format string and the raw arguments are
+//! sent (see `defer.rs`), which leaves the formatting code out of flash.
+//! Arguments are then limited to the types implementing `defer::Arg`
+//! (integers, `bool`, `char`, `f32`, `str` and bytes), and records may go to
+//! any `Sink` instead of the ITM, e.g., the (polled) USART2:
+//!
+//! ``` ignore
+//! let (tx, _rx) = vcp.split();
+//! app::log::init_sink(cortex_m::singleton!(: Tx<USART2> = tx).unwrap());
+//! ```
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use cortex_m::{interrupt, itm, peripheral::itm::Stim, peripheral::ITM};
+
+#[cfg(feature = "log-deferred")]
+use {
+    crate::defer::{Arg, Record, MAX_FRAME},
+    crate::frame::Sink,
+    core::cell::RefCell,
+    cortex_m::interrupt::Mutex,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// The stimulus port of the level.
+    pub const fn port(self) -> usize {
+        match self {
+            Level::Error | Level::Warn => 1,
+            Level::Info => 0,
+            Level::Debug | Level::Trace => 2,
+        }
+    }
+
+    /// The tag starting each record.
+    pub const fn tag(self) -> char {
+        match self {
+            Level::Error => 'E',
+            Level::Warn => 'W',
+            Level::Info => 'I',
+            Level::Debug => 'D',
+            Level::Trace => 'T',
+        }
+    }
+}
+
+/// The default maximum level, see the module documentation.
+#[cfg(not(any(feature = "log-debug", feature = "log-trace")))]
+pub const LOG_LEVEL: Level = Level::Info;
+#[cfg(all(feature = "log-debug", not(feature = "log-trace")))]
+pub const LOG_LEVEL: Level = Level::Debug;
+#[cfg(feature = "log-trace")]
+pub const LOG_LEVEL: Level = Level::Trace;
+
+pub mod prelude {
+    pub use super::{Level, LOG_LEVEL};
+    pub use crate::{debug, error, info, trace, warn};
+}
+
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// Enables logging, taking the ITM to prove nobody else writes to it.
+///
+/// Records logged before `init` are dropped.
+pub fn init(_itm: ITM) {
+    READY.store(true, Ordering::Release);
+}
+
+#[cfg(feature = "log-deferred")]
+static SINK: Mutex<RefCell<Option<&'static mut (dyn Sink + Send)>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Enables deferred logging to `sink` instead of the ITM.
+///
+/// The sink is called in a critical section, so it must not wait for an
+/// interrupt (as the buffered `serial::Writer` does).
+#[cfg(feature = "log-deferred")]
+pub fn init_sink(sink: &'static mut (dyn Sink + Send)) {
+    interrupt::free(|cs| *SINK.borrow(cs).borrow_mut() = Some(sink));
+    READY.store(true, Ordering::Release);
+}
+
+// NOTE(unsafe) `init` took the only `ITM`, and records are written in a
+// critical section, so they are not interleaved
+unsafe fn stim(port: usize) -> Option<&'static mut Stim> {
+    let itm = &mut *ITM::ptr();
+    // writing to a disabled port would wait forever for the FIFO
+    if itm.tcr.read() & 1 == 0 || itm.ter[0].read() & (1 << port) == 0 {
+        None
+    } else {
+        Some(&mut itm.stim[port])
+    }
+}
+
+/// Writes a record, use the macros instead.
+#[doc(hidden)]
+pub fn write(level: Level, module: &str, args: fmt::Arguments) {
+    if !READY.load(Ordering::Acquire) {
+        return;
+    }
+
+    interrupt::free(|_| {
+        if let Some(stim) = unsafe { stim(level.port()) } {
+            itm::write_all(stim, &[level.tag() as u8, b' ']);
+            itm::write_str(stim, module);
+            itm::write_str(stim, ": ");
+            itm::write_fmt(stim, args);
+            itm::write_str(stim, "\n");
+        }
+    });
+}
+
+/// Writes a deferred record, use the macros instead.
+#[cfg(feature = "log-deferred")]
+#[doc(hidden)]
+pub fn write_deferred(level: Level, index: u16, args: &[&dyn Arg]) {
+    if !READY.load(Ordering::Acquire) {
+        return;
+    }
+
+    let mut record = Record::new(index);
+    for arg in args {
+        arg.encode(&mut record);
+    }
+    let mut frame = [0; MAX_FRAME];
+    let n = record.frame(&mut frame);
+
+    interrupt::free(|cs| {
+        if let Some(sink) = SINK.borrow(cs).borrow_mut().as_mut() {
+            sink.send(&frame[..n]);
+        } else if let Some(stim) = unsafe { stim(level.port()) } {
+            itm::write_all(stim, &frame[..n]);
+        }
+    });
+}
+
+#[cfg(not(feature = "log-deferred"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:expr, $($arg:tt)+) => {{
+        // `LOG_LEVEL` is that of the calling module (or its prelude import)
+        const ENABLED: bool = ($level as u8) <= (LOG_LEVEL as u8);
+        if ENABLED {
+            $crate::log::write($level, module_path!(), format_args!($($arg)+));
+        }
+    }};
+}
+
+#[cfg(feature = "log-deferred")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:expr, $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        const ENABLED: bool = ($level as u8) <= (LOG_LEVEL as u8);
+        if ENABLED {
+            const S: &str = concat!(module_path!(), ": ", $fmt);
+            // the address in `.log` (starting at 0) is the index
+            #[link_section = ".log"]
+            static FMT: [u8; S.len() + 3] = $crate::defer::intern($level.tag() as u8, S);
+            // checks the arguments against the format string, formats nothing
+            if false {
+                let _ = format_args!($fmt $(, $arg)*);
+            }
+            let args: &[&dyn $crate::defer::Arg] = &[$(&$arg),*];
+            $crate::log::write_deferred($level, &FMT as *const _ as usize as u16, args);
+        }
+    }};
+}
+
+/// Logs at `Level::Error`, on port 1.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::__log!($crate::log::Level::Error, $($arg)+) };
+}
+
+/// Logs at `Level::Warn`, on port 1.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::__log!($crate::log::Level::Warn, $($arg)+) };
+}
+
+/// Logs at `Level::Info`, on port 0.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::__log!($crate::log::Level::Info, $($arg)+) };
+}
+
+/// Logs at `Level::Debug`, on port 2.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::__log!($crate::log::Level::Debug, $($arg)+) };
+}
+
+/// Logs at `Level::Trace`, on port 2.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::__log!($crate::log::Level::Trace, $($arg)+) };
+}
@@ -0,0 +1,219 @@
+//! In-memory register file
+//!
+//! The register blocks in `stm32f40x` are generic over their storage, so the
+//! same driver code can run on the MCU (`VolatileCell<u32>`, at the hardware
+//! address given by `RCC::get()`/`GPIOA::get()`) or against plain memory on
+//! the host (`Mem`), e.g., in `host/tests/mock.rs`:
+//!
+//! ``` ignore
+//! let rec: Recorder<64> = Recorder::new();
+//! let (rcc, gpioa) = (RCC::mock(&rec), GPIOA::mock(&rec));
+//!
+//! led::init(&rcc, &gpioa);
+//!
+//! assert_eq!(gpioa.MODER.read() >> 10 & 0b11, 0b01);
+//! let enabled = rec.position(|e| e.is_write_to(RCC_AHB1ENR) && e.value & 1 != 0);
+//! let moder = rec.position(|e| e.is_write_to(GPIOA_MODER));
+//! assert!(enabled < moder);
+//! ```
+//!
+//! Each register starts out at its reset value (RM0368) and every access is
+//! recorded (see `trace`) by its hardware address. Side effects of the hardware
+//! (e.g., a write to `BSRR` updating `ODR`) are not modelled, except for the
+//! status bits the clock setup waits on: the ready flags of `RCC_CR` and the
+//! switch status of `RCC_CFGR` follow their control bits at once, so
+//! `clocks::Config::freeze` runs against the mock, and `RMVF` clears the
+//! reset flags of `RCC_CSR` (see `boot::take_flags`).
+
+use core::cell::Cell;
+
+use crate::field::{Access, Reg};
+use crate::stm32f40x::{
+    address::*,
+    rcc::{cfgr, cr, csr},
+    FLASH, GPIOA, PWR, RCC,
+};
+use crate::trace::{Dir, Event, Record};
+
+/// A register in memory, recording its accesses.
+pub struct Mem<'a> {
+    value: Cell<u32>,
+    addr: u32,
+    rec: &'a dyn Record,
+    // the value the hardware settles to after a write
+    settle: fn(u32) -> u32,
+}
+
+impl<'a> Mem<'a> {
+    pub fn new(addr: u32, reset: u32, rec: &'a dyn Record) -> Self {
+        Self::with_settle(addr, reset, rec, |value| value)
+    }
+
+    /// A register whose hardware updates some bits after a write, e.g., the
+    /// ready flags following their enables.
+    pub fn with_settle(addr: u32, reset: u32, rec: &'a dyn Record, settle: fn(u32) -> u32) -> Self {
+        Mem {
+            value: Cell::new(reset),
+            addr,
+            rec,
+            settle,
+        }
+    }
+
+    /// The hardware address the register mocks.
+    pub fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    /// The current value, without recording an access.
+    pub fn peek(&self) -> u32 {
+        self.value.get()
+    }
+}
+
+impl<'a> Access for Mem<'a> {
+    fn read(&self) -> u32 {
+        let value = self.value.get();
+        self.rec.record(Event {
+            addr: self.addr,
+            value,
+            dir: Dir::Read,
+        });
+        value
+    }
+
+    fn write(&self, value: u32) {
+        self.value.set((self.settle)(value));
+        self.rec.record(Event {
+            addr: self.addr,
+            value,
+            dir: Dir::Write,
+        });
+    }
+}
+
+// HSIRDY, HSERDY and PLLRDY follow HSION, HSEON and PLLON (RM0368 6.3.1)
+fn cr_settle(value: u32) -> u32 {
+    let on = value & (cr::HSION.bits(1) | cr::HSEON.bits(1) | cr::PLLON.bits(1));
+    value & !(cr::HSIRDY.bits(1) | cr::HSERDY.bits(1) | cr::PLLRDY.bits(1)) | on << 1
+}
+
+// SWS follows SW (RM0368 6.3.3)
+fn cfgr_settle(value: u32) -> u32 {
+    value & !cfgr::SWS.bits(0b11) | cfgr::SWS.bits(value & 0b11)
+}
+
+// RMVF clears the reset flags, and reads as 0 (RM0368 6.3.18)
+fn csr_settle(value: u32) -> u32 {
+    if value & csr::RMVF.bits(1) != 0 {
+        value & !0xFF00_0000
+    } else {
+        value
+    }
+}
+
+impl<'a> RCC<Mem<'a>> {
+    /// RCC register file in reset state (RM0368 6.3).
+    #[rustfmt::skip]
+    pub fn mock(rec: &'a dyn Record) -> Self {
+        macro_rules! r {
+            ($offset:expr, $reset:expr) => {
+                Reg::new(Mem::new(RCC_BASE + $offset, $reset, rec))
+            };
+            ($offset:expr, $reset:expr, $settle:expr) => {
+                Reg::new(Mem::with_settle(RCC_BASE + $offset, $reset, rec, $settle))
+            };
+        }
+        RCC {
+            CR:         r!(0x00, 0x0000_0083, cr_settle),
+            PLLCFGR:    r!(0x04, 0x2400_3010),
+            CFGR:       r!(0x08, 0x0000_0000, cfgr_settle),
+            CIR:        r!(0x0C, 0x0000_0000),
+            AHB1RSTR:   r!(0x10, 0x0000_0000),
+            AHB2RSTR:   r!(0x14, 0x0000_0000),
+            AHB3RSTR:   r!(0x18, 0x0000_0000),
+            RESERVED0:  r!(0x1C, 0x0000_0000),
+            APB1RSTR:   r!(0x20, 0x0000_0000),
+            APB2RSTR:   r!(0x24, 0x0000_0000),
+            RESERVED1:  [r!(0x28, 0), r!(0x2C, 0)],
+            AHB1ENR:    r!(0x30, 0x0000_0000),
+            AHB2ENR:    r!(0x34, 0x0000_0000),
+            AHB3ENR:    r!(0x38, 0x0000_0000),
+            RESERVED2:  r!(0x3C, 0x0000_0000),
+            APB1ENR:    r!(0x40, 0x0000_0000),
+            APB2ENR:    r!(0x44, 0x0000_0000),
+            RESERVED3:  [r!(0x48, 0), r!(0x4C, 0)],
+            AHB1LPENR:  r!(0x50, 0x0061_900F),
+            AHB2LPENR:  r!(0x54, 0x0000_0080),
+            AHB3LPENR:  r!(0x58, 0x0000_0000),
+            RESERVED4:  r!(0x5C, 0x0000_0000),
+            APB1LPENR:  r!(0x60, 0x10E2_C80F),
+            APB2LPENR:  r!(0x64, 0x0007_7930),
+            RESERVED5:  [r!(0x68, 0), r!(0x6C, 0)],
+            BDCR:       r!(0x70, 0x0000_0000),
+            CSR:        r!(0x74, 0x0E00_0000, csr_settle),
+            RESERVED6:  [r!(0x78, 0), r!(0x7C, 0)],
+            SSCGR:      r!(0x80, 0x0000_0000),
+            PLLI2SCFGR: r!(0x84, 0x2400_3000),
+        }
+    }
+}
+
+impl<'a> GPIOA<Mem<'a>> {
+    /// GPIOA register file in reset state (RM0368 8.4).
+    #[rustfmt::skip]
+    pub fn mock(rec: &'a dyn Record) -> Self {
+        macro_rules! r {
+            ($offset:expr, $reset:expr) => {
+                Reg::new(Mem::new(GPIOA_BASE + $offset, $reset, rec))
+            };
+        }
+        GPIOA {
+            MODER:      r!(0x00, 0xA800_0000),
+            OTYPER:     r!(0x04, 0x0000_0000),
+            OSPEEDR:    r!(0x08, 0x0C00_0000),
+            PUPDR:      r!(0x0C, 0x6400_0000),
+            IDR:        r!(0x10, 0x0000_0000),
+            ODR:        r!(0x14, 0x0000_0000),
+            BSRR:       r!(0x18, 0x0000_0000),
+            LCKR:       r!(0x1C, 0x0000_0000),
+            AFR:        [r!(0x20, 0), r!(0x24, 0)],
+        }
+    }
+}
+
+impl<'a> FLASH<Mem<'a>> {
+    /// FLASH interface registers in reset state (RM0368 3.8).
+    #[rustfmt::skip]
+    pub fn mock(rec: &'a dyn Record) -> Self {
+        macro_rules! r {
+            ($offset:expr, $reset:expr) => {
+                Reg::new(Mem::new(FLASH_BASE + $offset, $reset, rec))
+            };
+        }
+        FLASH {
+            ACR:        r!(0x00, 0x0000_0000),
+            KEYR:       r!(0x04, 0x0000_0000),
+            OPTKEYR:    r!(0x08, 0x0000_0000),
+            SR:         r!(0x0C, 0x0000_0000),
+            CR:         r!(0x10, 0x8000_0000),
+            OPTCR:      r!(0x14, 0x0FFF_AAED),
+        }
+    }
+}
+
+impl<'a> PWR<Mem<'a>> {
+    /// PWR registers in reset state (RM0368 5.4).
+    #[rustfmt::skip]
+    pub fn mock(rec: &'a dyn Record) -> Self {
+        macro_rules! r {
+            ($offset:expr, $reset:expr) => {
+                Reg::new(Mem::new(PWR_BASE + $offset, $reset, rec))
+            };
+        }
+        PWR {
+            CR:         r!(0x00, 0x0000_8000),
+            CSR:        r!(0x04, 0x0000_0000),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Panic messages over the ST-LINK virtual COM port
+//!
+//! Semihosting needs a debugger and ITM needs the SWO pin, so a board on
+//! plain USB stays silent when it panics. With the `panic-serial` feature,
+//! this module provides the panic handler: it takes over USART2 (PA2/PA3,
+//! as set up by `board::setup`) and prints the message and location, which
+//! any terminal on the virtual COM port shows:
+//!
+//! ``` console
+//! > screen /dev/ttyACM0 115200
+//! panicked at 'index out of bounds: the len is 4 but the index is 4', examples/panic_serial.rs:40:16
+//! ```
+//!
+//! Whatever state the program left behind (another baud rate, a DMA
+//! transfer under way, the PLL driving SYSCLK, or USART2 never set up at
+//! all), `Tx::take_over` starts over by raw register access: SYSCLK back on
+//! the 16 MHz HSI without prescalers, the USART reset and reconfigured to
+//! 8N1 at `BAUDRATE`, and the pins to AF7. The clock switch also changes the
+//! SWO bit rate, the handler does not use ITM anyway.
+//!
+//! The feature is an alternative to the `panic_*` crates and `panic-reboot`,
+//! only one of them can provide the handler of a program. `Tx` can be used
+//! by other handlers as well, e.g., by a `HardFault` handler printing the
+//! exception frame.
+
+use core::fmt;
+#[cfg(feature = "panic-serial")]
+use core::panic::PanicInfo;
+
+#[cfg(feature = "panic-serial")]
+use cortex_m::asm;
+use cortex_m::interrupt;
+
+use crate::clocks::HSI;
+use crate::stm32f40x::{
+    gpio::{afr, moder, otyper, Mode, OutputType},
+    rcc::{ahb1enr, apb1enr, apb1rstr, cfgr, cr},
+    usart::{cr1, sr},
+    GPIOA, RCC, USART,
+};
+
+#[cfg(all(feature = "panic-serial", feature = "panic-reboot"))]
+compile_error!("`panic-serial` and `panic-reboot` both provide the panic handler");
+
+/// Baud rate, that of `board::VCP_BAUDRATE`.
+pub const BAUDRATE: u32 = 115_200;
+
+// alternate function of USART2 on PA2/PA3, RM0368 8.3.2 and the datasheet
+const AF7: u32 = 7;
+
+// BRR, USARTDIV in 12.4 fixed point with 16 times oversampling, rounded,
+// RM0368 19.3.4
+fn brr(pclk: u32, baudrate: u32) -> u32 {
+    (pclk + baudrate / 2) / baudrate
+}
+
+/// USART2 taken over, polled.
+pub struct Tx {
+    usart: &'static USART,
+}
+
+impl Tx {
+    /// Disables interrupts for good and reinitializes USART2 at `BAUDRATE`,
+    /// regardless of its (and the clock tree's) prior state.
+    pub fn take_over() -> Self {
+        interrupt::disable();
+        // NOTE(unsafe) interrupts disabled for good, nothing else runs
+        let (rcc, gpioa, usart) = unsafe { (&*RCC::get(), &*GPIOA::get(), &*USART::usart2()) };
+
+        // SYSCLK = HCLK = PCLK1 = HSI, RM0368 6.3.3 (slowing down needs no
+        // change of the flash wait states)
+        cr::HSION.modify(&rcc.CR, 1);
+        while cr::HSIRDY.read(&rcc.CR) == 0 {}
+        cfgr::SW.modify(&rcc.CFGR, 0b00);
+        while cfgr::SWS.read(&rcc.CFGR) != 0b00 {}
+        cfgr::HPRE.modify(&rcc.CFGR, 0);
+        cfgr::PPRE1.modify(&rcc.CFGR, 0);
+
+        // PA2 (TX) and PA3 (RX) to USART2, RM0368 8.3.2
+        ahb1enr::GPIOAEN.modify(&rcc.AHB1ENR, 1);
+        afr::AFR2.modify(&gpioa.AFR[0], AF7);
+        afr::AFR3.modify(&gpioa.AFR[0], AF7);
+        otyper::OT2.set(&gpioa.OTYPER, OutputType::PushPull);
+        moder::MODER2.set(&gpioa.MODER, Mode::Alternate);
+        moder::MODER3.set(&gpioa.MODER, Mode::Alternate);
+
+        // a reset of the peripheral clears all of its registers, ending any
+        // transfer, interrupt or DMA request
+        apb1enr::USART2EN.modify(&rcc.APB1ENR, 1);
+        apb1rstr::USART2RST.modify(&rcc.APB1RSTR, 1);
+        apb1rstr::USART2RST.modify(&rcc.APB1RSTR, 0);
+
+        // 8 data bits, no parity, 1 stop bit (the reset values)
+        usart.BRR.write(brr(HSI, BAUDRATE));
+        usart.CR1.write(cr1::UE.bits(1) | cr1::TE.bits(1));
+
+        Tx { usart }
+    }
+
+    /// Sends the bytes, and waits until the last one is out.
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            while sr::TXE.read(&self.usart.SR) == 0 {}
+            self.usart.DR.write(byte as u32);
+        }
+        while sr::TC.read(&self.usart.SR) == 0 {}
+    }
+}
+
+impl fmt::Write for Tx {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // terminals want "\r\n"
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                self.write(b"\r\n");
+            }
+            self.write(line.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "panic-serial")]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    use core::fmt::Write;
+
+    let mut tx = Tx::take_over();
+    // on a line of its own, whatever was sent before
+    write!(tx, "\n{}\n", info).ok();
+    loop {
+        asm::wfi();
+    }
+}
@@ -0,0 +1,174 @@
+//! Rebooting after a crash, with a boot-loop guard
+//!
+//! For a deployed board, neither halting (`panic_halt`) nor waiting for a
+//! debugger (`panic_semihosting`) helps. With the `panic-reboot` feature,
+//! this module provides the panic handler: it records the panic (see
+//! `crash.rs`), counts it and resets the MCU, `SCB::sys_reset`. At the next
+//! start, `boot` tells why the last run ended (see `boot.rs`):
+//!
+//! ``` ignore
+//! #[entry]
+//! fn main() -> ! {
+//!     let boot = reboot::boot();
+//!     if let Some(record) = &boot.crash {
+//!         write!(tx, "{}", record).ok();
+//!     }
+//!     ...
+//!     // up and running, the next crash is a first one again
+//!     reboot::healthy();
+//! }
+//! ```
+//!
+//! A crash that happens on every start would reset the MCU over and over.
+//! So the counters (kept in `.noinit` next to the crash record) hold the
+//! crashes in a row, cleared by a start without a crash record (e.g., after
+//! a power on) or by `healthy`, and the `LIMIT`th crash in a row halts
+//! instead of resetting. Its record can then be read by a debugger, or by
+//! the start after a press of the reset pin (B2), but not after a power
+//! cycle: that loses the `.noinit` RAM, and `BootReason` does not take a
+//! record found at power on as the cause anyway.
+//!
+//! A `HardFault` handler does the same by `crash::record_fault` and `reset`.
+//! The feature is an alternative to the `panic_*` crates, only one of them
+//! can provide the handler of a program.
+
+use core::fmt;
+use core::mem::{size_of, MaybeUninit};
+#[cfg(feature = "panic-reboot")]
+use core::panic::PanicInfo;
+use core::{ptr, slice};
+
+use cortex_m::{asm, interrupt, peripheral::SCB};
+
+use crate::boot::{self, BootReason};
+use crate::crash::{self, Record};
+use crate::frame::crc16;
+use crate::stm32f40x::RCC;
+
+/// Marks valid counters.
+pub const MAGIC: u32 = 0xB007_C0DE;
+/// Crashes in a row before `reset` halts instead.
+pub const LIMIT: u32 = 3;
+
+#[repr(C)]
+struct Counters {
+    magic: u32,
+    // of the bytes after `crc`
+    crc: u32,
+    resets: u32,
+    crashes: u32,
+}
+
+#[link_section = ".noinit.reboot"]
+static mut COUNTERS: MaybeUninit<Counters> = MaybeUninit::uninit();
+
+impl Counters {
+    fn body(&self) -> &[u8] {
+        // NOTE(unsafe) `repr(C)` of words
+        let bytes =
+            unsafe { slice::from_raw_parts(self as *const _ as *const u8, size_of::<Self>()) };
+        &bytes[8..]
+    }
+
+    fn seal(&mut self) {
+        self.crc = crc16(self.body()) as u32;
+        self.magic = MAGIC;
+    }
+}
+
+// the counters, zeroed if not valid (e.g., after power on)
+unsafe fn counters() -> &'static mut Counters {
+    let c = &mut *(*ptr::addr_of_mut!(COUNTERS)).as_mut_ptr();
+    if c.magic != MAGIC || c.crc != crc16(c.body()) as u32 {
+        c.resets = 0;
+        c.crashes = 0;
+        c.seal();
+    }
+    c
+}
+
+/// Why the last run ended, as far as the handlers know.
+pub struct Boot {
+    /// The reset flags and the crash record, combined.
+    pub reason: BootReason,
+    /// The record of the crash, `None` if the run ended otherwise (power
+    /// off, reset pin or a reset by the program).
+    pub crash: Option<Record>,
+    /// Resets by `reset`, since power on.
+    pub resets: u32,
+    /// Crashes in a row, up to this start.
+    pub crashes: u32,
+}
+
+impl fmt::Display for Boot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // resets by `reset`, crashes in a row
+        writeln!(
+            f,
+            "boot: {}, resets={} crashes={}",
+            self.reason, self.resets, self.crashes
+        )?;
+        match &self.crash {
+            Some(record) => write!(f, "{}", record),
+            None => writeln!(f, "no crash recorded"),
+        }
+    }
+}
+
+/// The reset flags, crash record and counters, once at start (see
+/// `boot::take_flags` and `crash::take`).
+pub fn boot() -> Boot {
+    let crash = crash::take();
+    // NOTE(unsafe) the `RCC` of the target, only `RCC_CSR` is touched
+    let csr = boot::take_flags(unsafe { &*RCC::get() });
+    let reason = BootReason::new(csr, crash.as_ref().map(|r| r.kind().into()));
+    interrupt::free(|_| {
+        // NOTE(unsafe) in a critical section, `reset` does not return
+        let c = unsafe { counters() };
+        if crash.is_none() {
+            c.crashes = 0;
+            c.seal();
+        }
+        Boot {
+            reason,
+            crash,
+            resets: c.resets,
+            crashes: c.crashes,
+        }
+    })
+}
+
+/// Clears the crashes in a row, the program being up and running.
+pub fn healthy() {
+    interrupt::free(|_| {
+        // NOTE(unsafe) see `boot`
+        let c = unsafe { counters() };
+        c.crashes = 0;
+        c.seal();
+    })
+}
+
+/// Counts a crash and resets the MCU, or halts if it is the `LIMIT`th in a
+/// row.
+pub fn reset() -> ! {
+    interrupt::disable();
+    // NOTE(unsafe) interrupts disabled for good
+    let c = unsafe { counters() };
+    c.crashes = c.crashes.saturating_add(1);
+    if c.crashes >= LIMIT {
+        c.seal();
+        loop {
+            asm::wfi();
+        }
+    }
+    c.resets = c.resets.saturating_add(1);
+    c.seal();
+    SCB::sys_reset()
+}
+
+#[cfg(feature = "panic-reboot")]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    crash::record_panic(info);
+    reset()
+}
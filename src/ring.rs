@@ -0,0 +1,150 @@
+//! Lock-free single producer single consumer ring buffer
+//!
+//! `Queue::split` hands out a `Producer` and a `Consumer` that may live in
+//! different contexts (e.g., an interrupt handler and a task) without
+//! locking, as each index is only ever written by one side.
+//!
+//! ``` ignore
+//! static mut Q: Queue<u8, 4> = Queue::new();
+//!
+//! let (mut p, mut c) = unsafe { Q.split() };
+//! p.enqueue(1).unwrap();
+//! assert_eq!(c.dequeue(), Some(1));
+//! ```
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A queue holding up to `N` elements.
+pub struct Queue<T, const N: usize> {
+    buf: UnsafeCell<[MaybeUninit<T>; N]>,
+    // indices run modulo 2 * N, so that a full queue (`tail - head == N`)
+    // can be told apart from an empty one (`tail == head`)
+    head: AtomicUsize, // written by the consumer only
+    tail: AtomicUsize, // written by the producer only
+}
+
+impl<T: Copy, const N: usize> Queue<T, N> {
+    const NONZERO: () = assert!(N > 0, "queue of no elements");
+
+    pub const fn new() -> Self {
+        let () = Self::NONZERO;
+        Queue {
+            buf: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Number of elements in the queue.
+    pub fn len(&self) -> usize {
+        distance(
+            self.head.load(Ordering::Acquire),
+            self.tail.load(Ordering::Acquire),
+            N,
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Splits the queue into its producer and consumer ends.
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { q: self }, Consumer { q: self })
+    }
+}
+
+impl<T: Copy, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn distance(head: usize, tail: usize, n: usize) -> usize {
+    (tail + 2 * n - head) % (2 * n)
+}
+
+/// The enqueueing end of a `Queue`.
+pub struct Producer<'a, T, const N: usize> {
+    q: &'a Queue<T, N>,
+}
+
+// the producer only touches the slot at `tail`, which the consumer does not
+// read until `tail` is advanced
+unsafe impl<'a, T: Send, const N: usize> Send for Producer<'a, T, N> {}
+
+impl<'a, T: Copy, const N: usize> Producer<'a, T, N> {
+    /// Adds `value` to the queue, giving it back if the queue is full.
+    pub fn enqueue(&mut self, value: T) -> Result<(), T> {
+        let tail = self.q.tail.load(Ordering::Relaxed);
+        let head = self.q.head.load(Ordering::Acquire);
+        if distance(head, tail, N) == N {
+            return Err(value);
+        }
+
+        unsafe { (*self.q.buf.get())[tail % N] = MaybeUninit::new(value) };
+        self.q.tail.store((tail + 1) % (2 * N), Ordering::Release);
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.q.len() == N
+    }
+
+    pub fn len(&self) -> usize {
+        self.q.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.q.is_empty()
+    }
+}
+
+/// The dequeueing end of a `Queue`.
+pub struct Consumer<'a, T, const N: usize> {
+    q: &'a Queue<T, N>,
+}
+
+// the consumer only touches the slot at `head`, which the producer does not
+// write until `head` is advanced
+unsafe impl<'a, T: Send, const N: usize> Send for Consumer<'a, T, N> {}
+
+impl<'a, T: Copy, const N: usize> Consumer<'a, T, N> {
+    /// Removes the oldest element, `None` if the queue is empty.
+    pub fn dequeue(&mut self) -> Option<T> {
+        let head = self.q.head.load(Ordering::Relaxed);
+        let tail = self.q.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        let value = unsafe { (*self.q.buf.get())[head % N].assume_init() };
+        self.q.head.store((head + 1) % (2 * N), Ordering::Release);
+        Some(value)
+    }
+
+    /// The oldest element, without removing it.
+    pub fn peek(&self) -> Option<T> {
+        let head = self.q.head.load(Ordering::Relaxed);
+        let tail = self.q.tail.load(Ordering::Acquire);
+        if head == tail {
+            None
+        } else {
+            Some(unsafe { (*self.q.buf.get())[head % N].assume_init() })
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.q.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.q.is_empty()
+    }
+}
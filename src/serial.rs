@@ -0,0 +1,210 @@
+//! Interrupt driven, buffered USART2
+//!
+//! The USART only buffers a single received byte, so polling with
+//! `block!(rx.read())` (as in `examples/serial.rs`) overruns as soon as more
+//! than one byte arrives while we are busy. Here the RXNE and TXE interrupts
+//! move bytes between the data register and a pair of `ring::Queue`s.
+//!
+//! `usart2` splits the driver into three parts, suitable as RTFM resources:
+//! - `Isr`, owning the peripheral, call `Isr::on_interrupt` from the
+//!   `USART2` interrupt handler,
+//! - `Reader`, taking received bytes out of the RX queue,
+//! - `Writer`, putting bytes to send into the TX queue.
+//!
+//! The queues must be `&'static mut`, as are the `static mut`s of an RTFM
+//! `init` (see `examples/rtfm_serial.rs`):
+//!
+//! ``` ignore
+//! #[init]
+//! fn init(cx: init::Context) -> init::LateResources {
+//!     static mut RX: Queue<u8, 64> = Queue::new();
+//!     static mut TX: Queue<u8, 64> = Queue::new();
+//!
+//!     let (led, button, vcp, clocks) = board::setup(...);
+//!     let (isr, reader, writer) = serial::usart2(vcp, RX, TX);
+//!     ...
+//! }
+//! ```
+
+use core::fmt;
+
+use nb::block;
+use stm32f4xx_hal::{
+    prelude::*,
+    serial::{Event, Tx},
+    stm32::USART2,
+};
+
+use crate::board::Vcp;
+use crate::frame::Sink;
+use crate::ring::{Consumer, Producer, Queue};
+
+/// Receive errors, counted since start.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Errors {
+    /// A byte arrived before the previous one was read (the new one is lost).
+    pub overrun: u32,
+    /// Missing stop bit, the byte is discarded.
+    pub framing: u32,
+    /// Noise detected on the line, the byte is discarded.
+    pub noise: u32,
+    /// The RX queue was full, the byte is discarded.
+    pub dropped: u32,
+}
+
+/// Takes over the (configured) virtual COM port, using `rx` and `tx` as
+/// receive and transmit buffers.
+pub fn usart2<const N: usize>(
+    mut serial: Vcp,
+    rx: &'static mut Queue<u8, N>,
+    tx: &'static mut Queue<u8, N>,
+) -> (Isr<N>, Reader<N>, Writer<N>) {
+    serial.listen(Event::Rxne);
+    let (usart, _pins) = serial.release();
+
+    let (rx_p, rx_c) = rx.split();
+    let (tx_p, tx_c) = tx.split();
+
+    (
+        Isr {
+            usart,
+            rx: rx_p,
+            tx: tx_c,
+            errors: Errors::default(),
+        },
+        Reader { rx: rx_c },
+        Writer { tx: tx_p },
+    )
+}
+
+/// The interrupt side of the driver.
+pub struct Isr<const N: usize> {
+    usart: USART2,
+    rx: Producer<'static, u8, N>,
+    tx: Consumer<'static, u8, N>,
+    errors: Errors,
+}
+
+impl<const N: usize> Isr<N> {
+    /// Services the USART, to be called from the `USART2` interrupt.
+    pub fn on_interrupt(&mut self) {
+        let sr = self.usart.sr.read();
+
+        if sr.rxne().bit_is_set() || sr.ore().bit_is_set() {
+            // reading DR (after SR) clears RXNE and the error flags
+            let byte = self.usart.dr.read().dr().bits() as u8;
+
+            if sr.ore().bit_is_set() {
+                self.errors.overrun += 1;
+            }
+
+            if sr.fe().bit_is_set() {
+                self.errors.framing += 1;
+            } else if sr.nf().bit_is_set() {
+                self.errors.noise += 1;
+            } else if self.rx.enqueue(byte).is_err() {
+                self.errors.dropped += 1;
+            }
+        }
+
+        if sr.txe().bit_is_set() && self.usart.cr1.read().txeie().bit_is_set() {
+            match self.tx.dequeue() {
+                Some(byte) => self.usart.dr.write(|w| unsafe { w.dr().bits(byte as u16) }),
+                // nothing more to send
+                None => self.usart.cr1.modify(|_, w| w.txeie().clear_bit()),
+            }
+        }
+    }
+
+    pub fn errors(&self) -> Errors {
+        self.errors
+    }
+}
+
+/// The receiving end of the driver.
+pub struct Reader<const N: usize> {
+    rx: Consumer<'static, u8, N>,
+}
+
+impl<const N: usize> Reader<N> {
+    /// The next received byte, if any.
+    pub fn read(&mut self) -> Option<u8> {
+        self.rx.dequeue()
+    }
+
+    /// Number of bytes waiting.
+    pub fn len(&self) -> usize {
+        self.rx.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rx.is_empty()
+    }
+}
+
+/// The TX queue is full.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Full;
+
+/// The transmitting end of the driver.
+pub struct Writer<const N: usize> {
+    tx: Producer<'static, u8, N>,
+}
+
+impl<const N: usize> Writer<N> {
+    /// Queues `byte` for transmission.
+    pub fn write(&mut self, byte: u8) -> Result<(), Full> {
+        let r = self.tx.enqueue(byte).map_err(|_| Full);
+        self.start();
+        r
+    }
+
+    /// Queues as much of `bytes` as fits, returning the number queued.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> usize {
+        let n = bytes
+            .iter()
+            .take_while(|&&b| self.tx.enqueue(b).is_ok())
+            .count();
+        self.start();
+        n
+    }
+
+    // enables the TXE interrupt, which stays pending until the queue is drained
+    fn start(&mut self) {
+        // NOTE(unsafe) the read-modify-write may race the `Isr` clearing
+        // TXEIE, which is benign: the interrupt then finds the queue empty
+        // and disables itself again
+        let usart = unsafe { &*USART2::ptr() };
+        usart.cr1.modify(|_, w| w.txeie().set_bit());
+    }
+}
+
+impl<const N: usize> fmt::Write for Writer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.write_bytes(s.as_bytes()) == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
+// waits for room, so that frames are never cut short, the `Isr` must thus be
+// able to preempt the caller
+impl<const N: usize> Sink for Writer<N> {
+    fn send(&mut self, frame: &[u8]) {
+        for &byte in frame {
+            while self.write(byte).is_err() {}
+        }
+    }
+}
+
+// polls the data register, so it also works in a critical section (e.g., as
+// the sink of deferred logging, see `log::init_sink`)
+impl Sink for Tx<USART2> {
+    fn send(&mut self, frame: &[u8]) {
+        for &byte in frame {
+            let _ = block!(self.write(byte));
+        }
+    }
+}
@@ -0,0 +1,451 @@
+//! Line oriented command shell
+//!
+//! Bytes received from a terminal are fed to `Shell::feed`, which echoes
+//! them, handles line editing and runs the command on `Enter`:
+//! - backspace (`BS` or `DEL`) erases the last character,
+//! - `Ctrl-C` discards the line,
+//! - arrow up/down (`ESC [ A`/`ESC [ B`) browse the history.
+//!
+//! Commands reach the hardware only through the `Target` trait, and write to
+//! any `fmt::Write`, so the parser and dispatch run just as well on the host
+//! against scripted input and a mock target.
+//!
+//! ``` ignore
+//! let mut shell: Shell<64, 4> = Shell::new(shell::COMMANDS);
+//! shell.prompt(&mut out)?;
+//! while let Some(byte) = reader.read() {
+//!     shell.feed(byte, &mut target, &mut out)?;
+//! }
+//! ```
+//!
+//! `help` lists the commands of the registry.
+
+use core::fmt::{self, Write};
+
+use crate::clocks::Clocks;
+use crate::time::Duration;
+
+pub const PROMPT: &str = "> ";
+
+/// Maximum number of arguments, including the command name.
+pub const MAX_ARGS: usize = 8;
+
+/// The hardware, as seen by the commands.
+pub trait Target {
+    /// Switches the user LED.
+    fn led(&mut self, on: bool);
+    /// Reads the (aligned) word at `address`.
+    fn peek(&mut self, address: u32) -> u32;
+    /// Writes the (aligned) word at `address`.
+    fn poke(&mut self, address: u32, value: u32);
+    fn clocks(&self) -> Clocks;
+    /// Resets the MCU, does not return on the target.
+    fn reset(&mut self);
+    /// Time since reset.
+    fn uptime(&self) -> Duration;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// No such command.
+    Unknown,
+    /// Missing, extra or malformed arguments.
+    Usage,
+    /// Not a number (decimal, `0x` hex or `0b` binary).
+    Number,
+    /// The address is not word aligned.
+    Unaligned,
+    /// More than `MAX_ARGS` words.
+    TooManyArguments,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::Unknown => "unknown command, try `help`",
+            Error::Usage => "bad arguments",
+            Error::Number => "not a number",
+            Error::Unaligned => "address not word aligned",
+            Error::TooManyArguments => "too many arguments",
+        })
+    }
+}
+
+/// The words of a command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Args<'a> {
+    argv: [&'a str; MAX_ARGS],
+    argc: usize,
+}
+
+impl<'a> Args<'a> {
+    /// Splits `line` at whitespace.
+    pub fn parse(line: &'a str) -> Result<Self, Error> {
+        let mut args = Args {
+            argv: [""; MAX_ARGS],
+            argc: 0,
+        };
+        for word in line.split_whitespace() {
+            if args.argc == MAX_ARGS {
+                return Err(Error::TooManyArguments);
+            }
+            args.argv[args.argc] = word;
+            args.argc += 1;
+        }
+        Ok(args)
+    }
+
+    pub fn len(&self) -> usize {
+        self.argc
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.argc == 0
+    }
+
+    pub fn get(&self, i: usize) -> Option<&'a str> {
+        self.as_slice().get(i).copied()
+    }
+
+    pub fn as_slice(&self) -> &[&'a str] {
+        &self.argv[..self.argc]
+    }
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary number.
+pub fn parse_u32(s: &str) -> Result<u32, Error> {
+    let (digits, radix) = if let Some(hex) = s.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        (bin, 2)
+    } else {
+        (s, 10)
+    };
+    // `_` may be used as separator, as in `0x4002_3830`
+    let mut value: u32 = 0;
+    let mut any = false;
+    for c in digits.chars().filter(|&c| c != '_') {
+        let digit = c.to_digit(radix).ok_or(Error::Number)?;
+        value = value
+            .checked_mul(radix)
+            .and_then(|v| v.checked_add(digit))
+            .ok_or(Error::Number)?;
+        any = true;
+    }
+    if any {
+        Ok(value)
+    } else {
+        Err(Error::Number)
+    }
+}
+
+fn address(s: &str) -> Result<u32, Error> {
+    let address = parse_u32(s)?;
+    if address % 4 != 0 {
+        return Err(Error::Unaligned);
+    }
+    Ok(address)
+}
+
+/// The function implementing a command, the arguments exclude the command
+/// name.
+pub type Run = fn(&[&str], &mut dyn Target, &mut dyn Write) -> Result<(), Error>;
+
+pub struct Command {
+    pub name: &'static str,
+    /// The arguments, shown on `Error::Usage`.
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub run: Run,
+}
+
+/// The built in commands.
+#[rustfmt::skip]
+pub const COMMANDS: &[Command] = &[
+    Command { name: "led",      usage: "led on|off",                help: "switch LD2",                 run: led },
+    Command { name: "peek",     usage: "peek <address>",            help: "read a word",                run: peek },
+    Command { name: "poke",     usage: "poke <address> <value>",    help: "write a word",               run: poke },
+    Command { name: "clocks",   usage: "clocks",                    help: "show the clock tree",        run: clocks },
+    Command { name: "reset",    usage: "reset",                     help: "reset the MCU",              run: reset },
+    Command { name: "uptime",   usage: "uptime",                    help: "time since reset",           run: uptime },
+];
+
+// errors writing to `out` are ignored, there is no one to tell
+
+fn led(args: &[&str], target: &mut dyn Target, _out: &mut dyn Write) -> Result<(), Error> {
+    match args {
+        ["on"] => target.led(true),
+        ["off"] => target.led(false),
+        _ => return Err(Error::Usage),
+    }
+    Ok(())
+}
+
+fn peek(args: &[&str], target: &mut dyn Target, out: &mut dyn Write) -> Result<(), Error> {
+    match args {
+        [a] => {
+            let address = address(a)?;
+            let value = target.peek(address);
+            let _ = write!(out, "{:#010x}: {:#010x}\r\n", address, value);
+            Ok(())
+        }
+        _ => Err(Error::Usage),
+    }
+}
+
+fn poke(args: &[&str], target: &mut dyn Target, _out: &mut dyn Write) -> Result<(), Error> {
+    match args {
+        [a, v] => {
+            let address = address(a)?;
+            target.poke(address, parse_u32(v)?);
+            Ok(())
+        }
+        _ => Err(Error::Usage),
+    }
+}
+
+fn clocks(args: &[&str], target: &mut dyn Target, out: &mut dyn Write) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::Usage);
+    }
+    let c = target.clocks();
+    let _ = write!(
+        out,
+        "sysclk {} Hz\r\nhclk   {} Hz\r\npclk1  {} Hz\r\npclk2  {} Hz\r\n",
+        c.sysclk(),
+        c.hclk(),
+        c.pclk1(),
+        c.pclk2()
+    );
+    if let Some(pll48clk) = c.pll48clk() {
+        let _ = write!(out, "pll48  {} Hz\r\n", pll48clk);
+    }
+    Ok(())
+}
+
+fn reset(args: &[&str], target: &mut dyn Target, _out: &mut dyn Write) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::Usage);
+    }
+    target.reset();
+    Ok(())
+}
+
+fn uptime(args: &[&str], target: &mut dyn Target, out: &mut dyn Write) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::Usage);
+    }
+    let ms = target.uptime().as_millis();
+    let _ = write!(out, "{}.{:03} s\r\n", ms / 1000, ms % 1000);
+    Ok(())
+}
+
+/// Runs `line` against `commands`, reporting errors to `out`.
+pub fn dispatch(
+    commands: &[Command],
+    line: &str,
+    target: &mut dyn Target,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let result = run(commands, line, target, out);
+    match result {
+        Err(Error::Usage) => {
+            // `run` only reports usage errors for known commands
+            let name = line.split_whitespace().next().unwrap_or("");
+            let usage = commands.iter().find(|c| c.name == name).map(|c| c.usage);
+            let _ = write!(out, "usage: {}\r\n", usage.unwrap_or(name));
+        }
+        Err(e) => {
+            let _ = write!(out, "error: {}\r\n", e);
+        }
+        Ok(()) => {}
+    }
+    result
+}
+
+fn run(
+    commands: &[Command],
+    line: &str,
+    target: &mut dyn Target,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let args = Args::parse(line)?;
+    let (name, rest) = match args.as_slice().split_first() {
+        Some((name, rest)) => (*name, rest),
+        // empty line
+        None => return Ok(()),
+    };
+
+    if name == "help" {
+        for c in commands {
+            let _ = write!(out, "{:<24} {}\r\n", c.usage, c.help);
+        }
+        return Ok(());
+    }
+
+    let command = commands
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or(Error::Unknown)?;
+    (command.run)(rest, target, out)
+}
+
+const BS: u8 = 0x08;
+const DEL: u8 = 0x7f;
+const ESC: u8 = 0x1b;
+const CTRL_C: u8 = 0x03;
+const BELL: u8 = 0x07;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Escape {
+    None,
+    Esc,
+    Csi,
+}
+
+/// A command line of up to `N` bytes, remembering the last `H` lines.
+pub struct Shell<const N: usize, const H: usize> {
+    commands: &'static [Command],
+    line: [u8; N],
+    len: usize,
+    history: [[u8; N]; H],
+    lengths: [usize; H],
+    // lines in the history, the newest at `newest`
+    stored: usize,
+    newest: usize,
+    // how far back we are browsing, 0 is the line being edited
+    browsing: usize,
+    escape: Escape,
+    cr: bool,
+}
+
+impl<const N: usize, const H: usize> Shell<N, H> {
+    pub const fn new(commands: &'static [Command]) -> Self {
+        Shell {
+            commands,
+            line: [0; N],
+            len: 0,
+            history: [[0; N]; H],
+            lengths: [0; H],
+            stored: 0,
+            newest: 0,
+            browsing: 0,
+            escape: Escape::None,
+            cr: false,
+        }
+    }
+
+    pub fn prompt(&self, out: &mut dyn Write) -> fmt::Result {
+        out.write_str(PROMPT)
+    }
+
+    /// The line being edited.
+    pub fn line(&self) -> &str {
+        // only printable ASCII is ever stored
+        core::str::from_utf8(&self.line[..self.len]).unwrap_or("")
+    }
+
+    /// Handles one received byte, echoing to `out` and running the line on
+    /// `Enter` (`CR`, `LF` or `CR LF`).
+    pub fn feed(&mut self, byte: u8, target: &mut dyn Target, out: &mut dyn Write) -> fmt::Result {
+        let cr = core::mem::replace(&mut self.cr, byte == b'\r');
+
+        match self.escape {
+            Escape::Esc => {
+                self.escape = if byte == b'[' {
+                    Escape::Csi
+                } else {
+                    Escape::None
+                };
+                return Ok(());
+            }
+            Escape::Csi => {
+                self.escape = Escape::None;
+                return match byte {
+                    b'A' => self.browse(self.browsing + 1, out),
+                    b'B' => self.browse(self.browsing.saturating_sub(1), out),
+                    _ => Ok(()),
+                };
+            }
+            Escape::None => {}
+        }
+
+        match byte {
+            b'\n' if cr => Ok(()),
+            b'\r' | b'\n' => {
+                out.write_str("\r\n")?;
+                self.remember();
+                // copied, as the history may be browsed by the command
+                let (line, len) = (self.line, self.len);
+                self.len = 0;
+                self.browsing = 0;
+                let line = core::str::from_utf8(&line[..len]).unwrap_or("");
+                let _ = dispatch(self.commands, line, target, out);
+                self.prompt(out)
+            }
+            BS | DEL => {
+                if self.len > 0 {
+                    self.len -= 1;
+                    out.write_str("\x08 \x08")?;
+                }
+                Ok(())
+            }
+            CTRL_C => {
+                self.len = 0;
+                self.browsing = 0;
+                out.write_str("^C\r\n")?;
+                self.prompt(out)
+            }
+            ESC => {
+                self.escape = Escape::Esc;
+                Ok(())
+            }
+            b' '..=b'~' if self.len < N => {
+                self.line[self.len] = byte;
+                self.len += 1;
+                out.write_char(byte as char)
+            }
+            _ => out.write_char(BELL as char),
+        }
+    }
+
+    // adds the line to the history, unless empty or a repeat of the newest
+    fn remember(&mut self) {
+        if H == 0 || self.line().trim().is_empty() {
+            return;
+        }
+        if self.stored > 0
+            && self.history[self.newest][..self.lengths[self.newest]] == self.line[..self.len]
+        {
+            return;
+        }
+        self.newest = if self.stored == 0 {
+            0
+        } else {
+            (self.newest + 1) % H
+        };
+        self.history[self.newest] = self.line;
+        self.lengths[self.newest] = self.len;
+        self.stored = H.min(self.stored + 1);
+    }
+
+    // replaces the line with history entry `back` (0 for an empty line)
+    fn browse(&mut self, back: usize, out: &mut dyn Write) -> fmt::Result {
+        if back > self.stored || back == self.browsing {
+            return out.write_char(BELL as char);
+        }
+        self.browsing = back;
+
+        for _ in 0..self.len {
+            out.write_str("\x08 \x08")?;
+        }
+        if back == 0 {
+            self.len = 0;
+        } else {
+            let i = (self.newest + H - (back - 1)) % H;
+            self.line = self.history[i];
+            self.len = self.lengths[i];
+        }
+        out.write_str(self.line())
+    }
+}
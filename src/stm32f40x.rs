@@ -0,0 +1,758 @@
+//! C like peripheral API
+//!
+//! The `RCC` and `GPIOA` register blocks from `examples/bare5.rs` (and the
+//! `FLASH` interface and `PWR` controller needed for clock configuration, the
+//! `DMA` controllers and the `USART`s), with each register typed so that the `field` API can
+//! check that a field is applied to the register it belongs to.
+//!
+//! see the Reference Manual RM0368 (www.st.com/resource/en/reference_manual/dm00096844.pdf)
+//! flash,   chapter 3
+//! pwr,     chapter 5
+//! rcc,     chapter 6
+//! gpio,    chapter 8
+//! dma,     chapter 9
+//! usart,   chapter 19
+
+use core::{cell, ptr};
+
+use crate::field::Reg;
+
+#[rustfmt::skip]
+#[allow(clippy::identity_op)] // offsets as in the reference manual
+pub mod address {
+    pub const SRAM_BASE: u32        = 0x20000000;
+    pub const PERIPH_BASE: u32      = 0x40000000;
+    pub const APB1PERIPH_BASE: u32  = PERIPH_BASE + 0x00000000;
+    pub const AHB1PERIPH_BASE: u32  = PERIPH_BASE + 0x00020000;
+    pub const USART2_BASE: u32      = APB1PERIPH_BASE + 0x4400;
+    pub const RCC_BASE: u32         = AHB1PERIPH_BASE + 0x3800;
+    pub const GPIOA_BASE: u32       = AHB1PERIPH_BASE + 0x0000;
+    pub const FLASH_BASE: u32       = AHB1PERIPH_BASE + 0x3C00;
+    pub const PWR_BASE: u32         = APB1PERIPH_BASE + 0x7000;
+    pub const DMA1_BASE: u32        = AHB1PERIPH_BASE + 0x6000;
+    pub const DMA2_BASE: u32        = AHB1PERIPH_BASE + 0x6400;
+    pub const DBGMCU_BASE: u32      = 0xE0042000;
+    pub const RCC_AHB1ENR: u32      = RCC_BASE + 0x30;
+    pub const GPIOA_MODER: u32      = GPIOA_BASE + 0x00;
+    pub const GPIOA_BSRR: u32       = GPIOA_BASE + 0x18;
+}
+use address::*;
+
+pub struct VolatileCell<T> {
+    value: cell::UnsafeCell<T>,
+}
+
+impl<T> VolatileCell<T> {
+    pub const fn new(value: T) -> Self {
+        VolatileCell {
+            value: cell::UnsafeCell::new(value),
+        }
+    }
+
+    #[inline(always)]
+    pub fn read(&self) -> T
+    where
+        T: Copy,
+    {
+        unsafe { ptr::read_volatile(self.value.get()) }
+    }
+
+    #[inline(always)]
+    pub fn write(&self, value: T)
+    where
+        T: Copy,
+    {
+        unsafe { ptr::write_volatile(self.value.get(), value) }
+    }
+}
+
+// modify (reads, modifies a field, and writes the volatile cell)
+//
+// parameters:
+// offset (field offset)
+// width  (field width)
+// value  (new value that the field should take)
+//
+// prefer the typed `Field` API, which checks offset and width at compile time
+impl VolatileCell<u32> {
+    #[inline(always)]
+    pub fn modify(&self, offset: u8, width: u8, value: u32) {
+        let mask: u32 = (0b1 << width) - 1;
+        self.write(!(mask << offset) & self.read() | (value & mask) << offset);
+    }
+}
+
+macro_rules! registers {
+    ($($name:ident),*) => {
+        $(
+            #[allow(non_camel_case_types)]
+            pub struct $name;
+        )*
+    };
+}
+
+// one field per pin, `$width` bits each
+macro_rules! pin_fields {
+    ($reg:ident, $width:literal, $shift:literal, $($name:ident = $pin:literal),*) => {
+        $(
+            pub const $name: Field<$reg, { $shift + $pin * $width }, $width> = Field::new();
+        )*
+    };
+}
+
+pub mod rcc {
+    registers!(
+        CR, PLLCFGR, CFGR, CIR, AHB1RSTR, AHB2RSTR, AHB3RSTR, APB1RSTR, APB2RSTR,
+        AHB1ENR, AHB2ENR, AHB3ENR, APB1ENR, APB2ENR, AHB1LPENR, AHB2LPENR,
+        AHB3LPENR, APB1LPENR, APB2LPENR, BDCR, CSR, SSCGR, PLLI2SCFGR, Reserved
+    );
+
+    /// RM0368 6.3.1
+    #[rustfmt::skip]
+    pub mod cr {
+        use super::CR;
+        use crate::field::Field;
+
+        pub const HSION: Field<CR, 0, 1>            = Field::new();
+        pub const HSIRDY: Field<CR, 1, 1>           = Field::new();
+        pub const HSEON: Field<CR, 16, 1>           = Field::new();
+        pub const HSERDY: Field<CR, 17, 1>          = Field::new();
+        pub const HSEBYP: Field<CR, 18, 1>          = Field::new();
+        pub const PLLON: Field<CR, 24, 1>           = Field::new();
+        pub const PLLRDY: Field<CR, 25, 1>          = Field::new();
+    }
+
+    /// RM0368 6.3.2
+    #[rustfmt::skip]
+    pub mod pllcfgr {
+        use super::PLLCFGR;
+        use crate::field::Field;
+
+        pub const PLLM: Field<PLLCFGR, 0, 6>        = Field::new();
+        pub const PLLN: Field<PLLCFGR, 6, 9>        = Field::new();
+        pub const PLLP: Field<PLLCFGR, 16, 2>       = Field::new();
+        pub const PLLSRC: Field<PLLCFGR, 22, 1>     = Field::new();
+        pub const PLLQ: Field<PLLCFGR, 24, 4>       = Field::new();
+    }
+
+    /// RM0368 6.3.3
+    #[rustfmt::skip]
+    pub mod cfgr {
+        use super::CFGR;
+        use crate::field::Field;
+
+        pub const SW: Field<CFGR, 0, 2>             = Field::new();
+        pub const SWS: Field<CFGR, 2, 2>            = Field::new();
+        pub const HPRE: Field<CFGR, 4, 4>           = Field::new();
+        pub const PPRE1: Field<CFGR, 10, 3>         = Field::new();
+        pub const PPRE2: Field<CFGR, 13, 3>         = Field::new();
+    }
+
+    /// RM0368 6.3.9
+    #[rustfmt::skip]
+    pub mod ahb1enr {
+        use super::AHB1ENR;
+        use crate::field::Field;
+
+        pub const GPIOAEN: Field<AHB1ENR, 0, 1>     = Field::new();
+        pub const GPIOBEN: Field<AHB1ENR, 1, 1>     = Field::new();
+        pub const GPIOCEN: Field<AHB1ENR, 2, 1>     = Field::new();
+        pub const GPIODEN: Field<AHB1ENR, 3, 1>     = Field::new();
+        pub const GPIOEEN: Field<AHB1ENR, 4, 1>     = Field::new();
+        pub const GPIOHEN: Field<AHB1ENR, 7, 1>     = Field::new();
+        pub const CRCEN: Field<AHB1ENR, 12, 1>      = Field::new();
+        pub const DMA1EN: Field<AHB1ENR, 21, 1>     = Field::new();
+        pub const DMA2EN: Field<AHB1ENR, 22, 1>     = Field::new();
+    }
+
+    /// RM0368 6.3.7
+    #[rustfmt::skip]
+    pub mod apb1rstr {
+        use super::APB1RSTR;
+        use crate::field::Field;
+
+        pub const USART2RST: Field<APB1RSTR, 17, 1> = Field::new();
+    }
+
+    /// RM0368 6.3.11
+    #[rustfmt::skip]
+    pub mod apb1enr {
+        use super::APB1ENR;
+        use crate::field::Field;
+
+        pub const USART2EN: Field<APB1ENR, 17, 1>   = Field::new();
+        pub const PWREN: Field<APB1ENR, 28, 1>      = Field::new();
+    }
+
+    /// RM0368 6.3.18, the reset flags stay set until cleared by `RMVF`
+    #[rustfmt::skip]
+    pub mod csr {
+        use super::CSR;
+        use crate::field::Field;
+
+        pub const LSION: Field<CSR, 0, 1>           = Field::new();
+        pub const LSIRDY: Field<CSR, 1, 1>          = Field::new();
+        pub const RMVF: Field<CSR, 24, 1>           = Field::new();
+        pub const BORRSTF: Field<CSR, 25, 1>        = Field::new();
+        pub const PINRSTF: Field<CSR, 26, 1>        = Field::new();
+        pub const PORRSTF: Field<CSR, 27, 1>        = Field::new();
+        pub const SFTRSTF: Field<CSR, 28, 1>        = Field::new();
+        pub const IWDGRSTF: Field<CSR, 29, 1>       = Field::new();
+        pub const WWDGRSTF: Field<CSR, 30, 1>       = Field::new();
+        pub const LPWRRSTF: Field<CSR, 31, 1>       = Field::new();
+    }
+}
+
+pub mod pwr {
+    registers!(CR, CSR);
+
+    /// RM0368 5.4.1, regulator scale 2 after reset: 84 MHz at most, the
+    /// F411 needs scale 1 above (RM0383 5.4.1, the F401 has no scale 1)
+    #[rustfmt::skip]
+    pub mod cr {
+        use super::CR;
+        use crate::field::Field;
+
+        pub const VOS: Field<CR, 14, 2>             = Field::new();
+    }
+
+    /// `VOS` values.
+    pub const SCALE2: u32 = 0b10;
+    pub const SCALE1: u32 = 0b11;
+}
+
+pub mod flash {
+    registers!(ACR, KEYR, OPTKEYR, SR, CR, OPTCR);
+
+    /// RM0368 3.8.1
+    #[rustfmt::skip]
+    pub mod acr {
+        use super::ACR;
+        use crate::field::Field;
+
+        pub const LATENCY: Field<ACR, 0, 4>         = Field::new();
+        pub const PRFTEN: Field<ACR, 8, 1>          = Field::new();
+        pub const ICEN: Field<ACR, 9, 1>            = Field::new();
+        pub const DCEN: Field<ACR, 10, 1>           = Field::new();
+    }
+}
+
+pub mod gpio {
+    use crate::field::Value;
+
+    registers!(MODER, OTYPER, OSPEEDR, PUPDR, IDR, ODR, BSRR, LCKR, AFR);
+
+    /// Pin mode, RM0368 8.4.1
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Mode {
+        Input = 0b00,
+        Output = 0b01,
+        Alternate = 0b10,
+        Analog = 0b11,
+    }
+
+    impl Value for Mode {
+        const WIDTH: u8 = 2;
+
+        fn bits(self) -> u32 {
+            self as u32
+        }
+
+        fn from_bits(bits: u32) -> Option<Self> {
+            match bits {
+                0b00 => Some(Mode::Input),
+                0b01 => Some(Mode::Output),
+                0b10 => Some(Mode::Alternate),
+                0b11 => Some(Mode::Analog),
+                _ => None,
+            }
+        }
+    }
+
+    /// Output type, RM0368 8.4.2
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum OutputType {
+        PushPull = 0,
+        OpenDrain = 1,
+    }
+
+    impl Value for OutputType {
+        const WIDTH: u8 = 1;
+
+        fn bits(self) -> u32 {
+            self as u32
+        }
+
+        fn from_bits(bits: u32) -> Option<Self> {
+            match bits {
+                0 => Some(OutputType::PushPull),
+                1 => Some(OutputType::OpenDrain),
+                _ => None,
+            }
+        }
+    }
+
+    /// Pull-up/pull-down, RM0368 8.4.4 (`0b11` is reserved)
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Pull {
+        Floating = 0b00,
+        Up = 0b01,
+        Down = 0b10,
+    }
+
+    impl Value for Pull {
+        const WIDTH: u8 = 2;
+
+        fn bits(self) -> u32 {
+            self as u32
+        }
+
+        fn from_bits(bits: u32) -> Option<Self> {
+            match bits {
+                0b00 => Some(Pull::Floating),
+                0b01 => Some(Pull::Up),
+                0b10 => Some(Pull::Down),
+                _ => None,
+            }
+        }
+    }
+
+    /// RM0368 8.4.1
+    #[rustfmt::skip]
+    pub mod moder {
+        use super::MODER;
+        use crate::field::Field;
+
+        pin_fields!(MODER, 2, 0,
+            MODER0 = 0, MODER1 = 1, MODER2 = 2, MODER3 = 3,
+            MODER4 = 4, MODER5 = 5, MODER6 = 6, MODER7 = 7,
+            MODER8 = 8, MODER9 = 9, MODER10 = 10, MODER11 = 11,
+            MODER12 = 12, MODER13 = 13, MODER14 = 14, MODER15 = 15
+        );
+    }
+
+    /// RM0368 8.4.2
+    #[rustfmt::skip]
+    pub mod otyper {
+        use super::OTYPER;
+        use crate::field::Field;
+
+        pin_fields!(OTYPER, 1, 0,
+            OT0 = 0, OT1 = 1, OT2 = 2, OT3 = 3,
+            OT4 = 4, OT5 = 5, OT6 = 6, OT7 = 7,
+            OT8 = 8, OT9 = 9, OT10 = 10, OT11 = 11,
+            OT12 = 12, OT13 = 13, OT14 = 14, OT15 = 15
+        );
+    }
+
+    /// RM0368 8.4.4
+    #[rustfmt::skip]
+    pub mod pupdr {
+        use super::PUPDR;
+        use crate::field::Field;
+
+        pin_fields!(PUPDR, 2, 0,
+            PUPDR0 = 0, PUPDR1 = 1, PUPDR2 = 2, PUPDR3 = 3,
+            PUPDR4 = 4, PUPDR5 = 5, PUPDR6 = 6, PUPDR7 = 7,
+            PUPDR8 = 8, PUPDR9 = 9, PUPDR10 = 10, PUPDR11 = 11,
+            PUPDR12 = 12, PUPDR13 = 13, PUPDR14 = 14, PUPDR15 = 15
+        );
+    }
+
+    /// RM0368 8.4.6
+    #[rustfmt::skip]
+    pub mod odr {
+        use super::ODR;
+        use crate::field::Field;
+
+        pin_fields!(ODR, 1, 0,
+            ODR0 = 0, ODR1 = 1, ODR2 = 2, ODR3 = 3,
+            ODR4 = 4, ODR5 = 5, ODR6 = 6, ODR7 = 7,
+            ODR8 = 8, ODR9 = 9, ODR10 = 10, ODR11 = 11,
+            ODR12 = 12, ODR13 = 13, ODR14 = 14, ODR15 = 15
+        );
+    }
+
+    /// RM0368 8.4.7, write only, use `Field::bits` to build the value
+    #[rustfmt::skip]
+    pub mod bsrr {
+        use super::BSRR;
+        use crate::field::Field;
+
+        pin_fields!(BSRR, 1, 0,
+            BS0 = 0, BS1 = 1, BS2 = 2, BS3 = 3,
+            BS4 = 4, BS5 = 5, BS6 = 6, BS7 = 7,
+            BS8 = 8, BS9 = 9, BS10 = 10, BS11 = 11,
+            BS12 = 12, BS13 = 13, BS14 = 14, BS15 = 15
+        );
+
+        pin_fields!(BSRR, 1, 16,
+            BR0 = 0, BR1 = 1, BR2 = 2, BR3 = 3,
+            BR4 = 4, BR5 = 5, BR6 = 6, BR7 = 7,
+            BR8 = 8, BR9 = 9, BR10 = 10, BR11 = 11,
+            BR12 = 12, BR13 = 13, BR14 = 14, BR15 = 15
+        );
+    }
+
+    /// RM0368 8.4.9, of `AFR[0]` (pins 0 to 7), `AFR[1]` holds pins 8 to 15
+    /// in the same fields
+    #[rustfmt::skip]
+    pub mod afr {
+        use super::AFR;
+        use crate::field::Field;
+
+        pin_fields!(AFR, 4, 0,
+            AFR0 = 0, AFR1 = 1, AFR2 = 2, AFR3 = 3,
+            AFR4 = 4, AFR5 = 5, AFR6 = 6, AFR7 = 7
+        );
+    }
+}
+
+pub mod dma {
+    use crate::field::Value;
+
+    registers!(LISR, HISR, LIFCR, HIFCR, CR, NDTR, PAR, M0AR, M1AR, FCR);
+
+    /// Data transfer direction, RM0368 9.5.5 (`0b11` is reserved)
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Dir {
+        PeripheralToMemory = 0b00,
+        MemoryToPeripheral = 0b01,
+        MemoryToMemory = 0b10,
+    }
+
+    impl Value for Dir {
+        const WIDTH: u8 = 2;
+
+        fn bits(self) -> u32 {
+            self as u32
+        }
+
+        fn from_bits(bits: u32) -> Option<Self> {
+            match bits {
+                0b00 => Some(Dir::PeripheralToMemory),
+                0b01 => Some(Dir::MemoryToPeripheral),
+                0b10 => Some(Dir::MemoryToMemory),
+                _ => None,
+            }
+        }
+    }
+
+    /// RM0368 9.5.2, status of streams 4 to 7 (read only)
+    #[rustfmt::skip]
+    pub mod hisr {
+        use super::HISR;
+        use crate::field::Field;
+
+        pub const FEIF4: Field<HISR, 0, 1>          = Field::new();
+        pub const DMEIF4: Field<HISR, 2, 1>         = Field::new();
+        pub const TEIF4: Field<HISR, 3, 1>          = Field::new();
+        pub const HTIF4: Field<HISR, 4, 1>          = Field::new();
+        pub const TCIF4: Field<HISR, 5, 1>          = Field::new();
+        pub const FEIF5: Field<HISR, 6, 1>          = Field::new();
+        pub const DMEIF5: Field<HISR, 8, 1>         = Field::new();
+        pub const TEIF5: Field<HISR, 9, 1>          = Field::new();
+        pub const HTIF5: Field<HISR, 10, 1>         = Field::new();
+        pub const TCIF5: Field<HISR, 11, 1>         = Field::new();
+        pub const FEIF6: Field<HISR, 16, 1>         = Field::new();
+        pub const DMEIF6: Field<HISR, 18, 1>        = Field::new();
+        pub const TEIF6: Field<HISR, 19, 1>         = Field::new();
+        pub const HTIF6: Field<HISR, 20, 1>         = Field::new();
+        pub const TCIF6: Field<HISR, 21, 1>         = Field::new();
+        pub const FEIF7: Field<HISR, 22, 1>         = Field::new();
+        pub const DMEIF7: Field<HISR, 24, 1>        = Field::new();
+        pub const TEIF7: Field<HISR, 25, 1>         = Field::new();
+        pub const HTIF7: Field<HISR, 26, 1>         = Field::new();
+        pub const TCIF7: Field<HISR, 27, 1>         = Field::new();
+    }
+
+    /// RM0368 9.5.4, write 1 to clear the `HISR` flag at the same position
+    #[rustfmt::skip]
+    pub mod hifcr {
+        use super::HIFCR;
+        use crate::field::Field;
+
+        pub const CFEIF4: Field<HIFCR, 0, 1>        = Field::new();
+        pub const CDMEIF4: Field<HIFCR, 2, 1>       = Field::new();
+        pub const CTEIF4: Field<HIFCR, 3, 1>        = Field::new();
+        pub const CHTIF4: Field<HIFCR, 4, 1>        = Field::new();
+        pub const CTCIF4: Field<HIFCR, 5, 1>        = Field::new();
+        pub const CFEIF5: Field<HIFCR, 6, 1>        = Field::new();
+        pub const CDMEIF5: Field<HIFCR, 8, 1>       = Field::new();
+        pub const CTEIF5: Field<HIFCR, 9, 1>        = Field::new();
+        pub const CHTIF5: Field<HIFCR, 10, 1>       = Field::new();
+        pub const CTCIF5: Field<HIFCR, 11, 1>       = Field::new();
+        pub const CFEIF6: Field<HIFCR, 16, 1>       = Field::new();
+        pub const CDMEIF6: Field<HIFCR, 18, 1>      = Field::new();
+        pub const CTEIF6: Field<HIFCR, 19, 1>       = Field::new();
+        pub const CHTIF6: Field<HIFCR, 20, 1>       = Field::new();
+        pub const CTCIF6: Field<HIFCR, 21, 1>       = Field::new();
+        pub const CFEIF7: Field<HIFCR, 22, 1>       = Field::new();
+        pub const CDMEIF7: Field<HIFCR, 24, 1>      = Field::new();
+        pub const CTEIF7: Field<HIFCR, 25, 1>       = Field::new();
+        pub const CHTIF7: Field<HIFCR, 26, 1>       = Field::new();
+        pub const CTCIF7: Field<HIFCR, 27, 1>       = Field::new();
+    }
+
+    /// RM0368 9.5.5, stream configuration
+    #[rustfmt::skip]
+    pub mod cr {
+        use super::CR;
+        use crate::field::Field;
+
+        pub const EN: Field<CR, 0, 1>               = Field::new();
+        pub const DMEIE: Field<CR, 1, 1>            = Field::new();
+        pub const TEIE: Field<CR, 2, 1>             = Field::new();
+        pub const HTIE: Field<CR, 3, 1>             = Field::new();
+        pub const TCIE: Field<CR, 4, 1>             = Field::new();
+        pub const PFCTRL: Field<CR, 5, 1>           = Field::new();
+        pub const DIR: Field<CR, 6, 2>              = Field::new();
+        pub const CIRC: Field<CR, 8, 1>             = Field::new();
+        pub const PINC: Field<CR, 9, 1>             = Field::new();
+        pub const MINC: Field<CR, 10, 1>            = Field::new();
+        pub const PSIZE: Field<CR, 11, 2>           = Field::new();
+        pub const MSIZE: Field<CR, 13, 2>           = Field::new();
+        pub const PINCOS: Field<CR, 15, 1>          = Field::new();
+        pub const PL: Field<CR, 16, 2>              = Field::new();
+        pub const DBM: Field<CR, 18, 1>             = Field::new();
+        pub const CT: Field<CR, 19, 1>              = Field::new();
+        pub const PBURST: Field<CR, 21, 2>          = Field::new();
+        pub const MBURST: Field<CR, 23, 2>          = Field::new();
+        pub const CHSEL: Field<CR, 25, 3>           = Field::new();
+    }
+
+    /// RM0368 9.5.6, items left to transfer
+    #[rustfmt::skip]
+    pub mod ndtr {
+        use super::NDTR;
+        use crate::field::Field;
+
+        pub const NDT: Field<NDTR, 0, 16>           = Field::new();
+    }
+}
+
+pub mod usart {
+    registers!(SR, DR, BRR, CR1, CR2, CR3, GTPR);
+
+    /// RM0368 19.6.1
+    #[rustfmt::skip]
+    pub mod sr {
+        use super::SR;
+        use crate::field::Field;
+
+        pub const PE: Field<SR, 0, 1>               = Field::new();
+        pub const FE: Field<SR, 1, 1>               = Field::new();
+        pub const NF: Field<SR, 2, 1>               = Field::new();
+        pub const ORE: Field<SR, 3, 1>              = Field::new();
+        pub const IDLE: Field<SR, 4, 1>             = Field::new();
+        pub const RXNE: Field<SR, 5, 1>             = Field::new();
+        pub const TC: Field<SR, 6, 1>               = Field::new();
+        pub const TXE: Field<SR, 7, 1>              = Field::new();
+    }
+
+    /// RM0368 19.6.4
+    #[rustfmt::skip]
+    pub mod cr1 {
+        use super::CR1;
+        use crate::field::Field;
+
+        pub const RE: Field<CR1, 2, 1>              = Field::new();
+        pub const TE: Field<CR1, 3, 1>              = Field::new();
+        pub const IDLEIE: Field<CR1, 4, 1>          = Field::new();
+        pub const RXNEIE: Field<CR1, 5, 1>          = Field::new();
+        pub const TCIE: Field<CR1, 6, 1>            = Field::new();
+        pub const TXEIE: Field<CR1, 7, 1>           = Field::new();
+        pub const UE: Field<CR1, 13, 1>             = Field::new();
+    }
+
+    /// RM0368 19.6.6
+    #[rustfmt::skip]
+    pub mod cr3 {
+        use super::CR3;
+        use crate::field::Field;
+
+        pub const EIE: Field<CR3, 0, 1>             = Field::new();
+        pub const DMAR: Field<CR3, 6, 1>            = Field::new();
+        pub const DMAT: Field<CR3, 7, 1>            = Field::new();
+    }
+}
+
+pub mod dbgmcu {
+    registers!(IDCODE, CR, APB1_FZ, APB2_FZ);
+
+    /// RM0368 23.6.3
+    #[rustfmt::skip]
+    pub mod cr {
+        use super::CR;
+        use crate::field::Field;
+
+        pub const DBG_SLEEP: Field<CR, 0, 1>        = Field::new();
+        pub const DBG_STOP: Field<CR, 1, 1>         = Field::new();
+        pub const DBG_STANDBY: Field<CR, 2, 1>      = Field::new();
+        pub const TRACE_IOEN: Field<CR, 5, 1>       = Field::new();
+        pub const TRACE_MODE: Field<CR, 6, 2>       = Field::new();
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+#[rustfmt::skip]
+pub struct RCC<A = VolatileCell<u32>> {
+    pub CR:         Reg<rcc::CR, A>,                // < RCC clock control register,                                    Address offset: 0x00
+    pub PLLCFGR:    Reg<rcc::PLLCFGR, A>,           // < RCC PLL configuration register,                                Address offset: 0x04
+    pub CFGR:       Reg<rcc::CFGR, A>,              // < RCC clock configuration register,                              Address offset: 0x08
+    pub CIR:        Reg<rcc::CIR, A>,               // < RCC clock interrupt register,                                  Address offset: 0x0C
+    pub AHB1RSTR:   Reg<rcc::AHB1RSTR, A>,          // < RCC AHB1 peripheral reset register,                            Address offset: 0x10
+    pub AHB2RSTR:   Reg<rcc::AHB2RSTR, A>,          // < RCC AHB2 peripheral reset register,                            Address offset: 0x14
+    pub AHB3RSTR:   Reg<rcc::AHB3RSTR, A>,          // < RCC AHB3 peripheral reset register,                            Address offset: 0x18
+    pub RESERVED0:  Reg<rcc::Reserved, A>,          // < Reserved, 0x1C
+    pub APB1RSTR:   Reg<rcc::APB1RSTR, A>,          // < RCC APB1 peripheral reset register,                            Address offset: 0x20
+    pub APB2RSTR:   Reg<rcc::APB2RSTR, A>,          // < RCC APB2 peripheral reset register,                            Address offset: 0x24
+    pub RESERVED1:  [Reg<rcc::Reserved, A>; 2],     // < Reserved, 0x28-0x2C
+    pub AHB1ENR:    Reg<rcc::AHB1ENR, A>,           // < RCC AHB1 peripheral clock register,                            Address offset: 0x30
+    pub AHB2ENR:    Reg<rcc::AHB2ENR, A>,           // < RCC AHB2 peripheral clock register,                            Address offset: 0x34
+    pub AHB3ENR:    Reg<rcc::AHB3ENR, A>,           // < RCC AHB3 peripheral clock register,                            Address offset: 0x38
+    pub RESERVED2:  Reg<rcc::Reserved, A>,          // < Reserved, 0x3C
+    pub APB1ENR:    Reg<rcc::APB1ENR, A>,           // < RCC APB1 peripheral clock enable register,                     Address offset: 0x40
+    pub APB2ENR:    Reg<rcc::APB2ENR, A>,           // < RCC APB2 peripheral clock enable register,                     Address offset: 0x44
+    pub RESERVED3:  [Reg<rcc::Reserved, A>; 2],     // < Reserved, 0x48-0x4C
+    pub AHB1LPENR:  Reg<rcc::AHB1LPENR, A>,         // < RCC AHB1 peripheral clock enable in low power mode register,   Address offset: 0x50
+    pub AHB2LPENR:  Reg<rcc::AHB2LPENR, A>,         // < RCC AHB2 peripheral clock enable in low power mode register,   Address offset: 0x54
+    pub AHB3LPENR:  Reg<rcc::AHB3LPENR, A>,         // < RCC AHB3 peripheral clock enable in low power mode register,   Address offset: 0x58
+    pub RESERVED4:  Reg<rcc::Reserved, A>,          // < Reserved, 0x5C
+    pub APB1LPENR:  Reg<rcc::APB1LPENR, A>,         // < RCC APB1 peripheral clock enable in low power mode register,   Address offset: 0x60
+    pub APB2LPENR:  Reg<rcc::APB2LPENR, A>,         // < RCC APB2 peripheral clock enable in low power mode register,   Address offset: 0x64
+    pub RESERVED5:  [Reg<rcc::Reserved, A>; 2],     // < Reserved, 0x68-0x6C
+    pub BDCR:       Reg<rcc::BDCR, A>,              // < RCC Backup domain control register,                            Address offset: 0x70
+    pub CSR:        Reg<rcc::CSR, A>,               // < RCC clock control & status register,                           Address offset: 0x74
+    pub RESERVED6:  [Reg<rcc::Reserved, A>; 2],     // < Reserved, 0x78-0x7C
+    pub SSCGR:      Reg<rcc::SSCGR, A>,             // < RCC spread spectrum clock generation register,                 Address offset: 0x80
+    pub PLLI2SCFGR: Reg<rcc::PLLI2SCFGR, A>,        // < RCC PLLI2S configuration register,                             Address offset: 0x84
+}
+
+impl RCC {
+    pub fn get() -> *mut RCC {
+        RCC_BASE as *mut RCC
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+#[rustfmt::skip]
+pub struct GPIOA<A = VolatileCell<u32>> {
+    pub MODER:      Reg<gpio::MODER, A>,            // < GPIO port mode register,                                       Address offset: 0x00
+    pub OTYPER:     Reg<gpio::OTYPER, A>,           // < GPIO port output type register,                                Address offset: 0x04
+    pub OSPEEDR:    Reg<gpio::OSPEEDR, A>,          // < GPIO port output speed register,                               Address offset: 0x08
+    pub PUPDR:      Reg<gpio::PUPDR, A>,            // < GPIO port pull-up/pull-down register,                          Address offset: 0x0C
+    pub IDR:        Reg<gpio::IDR, A>,              // < GPIO port input data register,                                 Address offset: 0x10
+    pub ODR:        Reg<gpio::ODR, A>,              // < GPIO port output data register,                                Address offset: 0x14
+    pub BSRR:       Reg<gpio::BSRR, A>,             // < GPIO port bit set/reset register,                              Address offset: 0x18
+    pub LCKR:       Reg<gpio::LCKR, A>,             // < GPIO port configuration lock register,                         Address offset: 0x1C
+    pub AFR:        [Reg<gpio::AFR, A>; 2],         // < GPIO alternate function registers,                             Address offset: 0x20-0x24
+}
+
+impl GPIOA {
+    pub fn get() -> *mut GPIOA {
+        GPIOA_BASE as *mut GPIOA
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+#[rustfmt::skip]
+pub struct FLASH<A = VolatileCell<u32>> {
+    pub ACR:        Reg<flash::ACR, A>,             // < FLASH access control register,                                 Address offset: 0x00
+    pub KEYR:       Reg<flash::KEYR, A>,            // < FLASH key register,                                            Address offset: 0x04
+    pub OPTKEYR:    Reg<flash::OPTKEYR, A>,         // < FLASH option key register,                                     Address offset: 0x08
+    pub SR:         Reg<flash::SR, A>,              // < FLASH status register,                                         Address offset: 0x0C
+    pub CR:         Reg<flash::CR, A>,              // < FLASH control register,                                        Address offset: 0x10
+    pub OPTCR:      Reg<flash::OPTCR, A>,           // < FLASH option control register,                                 Address offset: 0x14
+}
+
+impl FLASH {
+    pub fn get() -> *mut FLASH {
+        FLASH_BASE as *mut FLASH
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+#[rustfmt::skip]
+pub struct PWR<A = VolatileCell<u32>> {
+    pub CR:         Reg<pwr::CR, A>,                // < PWR power control register,                                    Address offset: 0x00
+    pub CSR:        Reg<pwr::CSR, A>,               // < PWR power control/status register,                             Address offset: 0x04
+}
+
+impl PWR {
+    pub fn get() -> *mut PWR {
+        PWR_BASE as *mut PWR
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+#[rustfmt::skip]
+pub struct DMA<A = VolatileCell<u32>> {
+    pub LISR:       Reg<dma::LISR, A>,              // < DMA low interrupt status register,                             Address offset: 0x00
+    pub HISR:       Reg<dma::HISR, A>,              // < DMA high interrupt status register,                            Address offset: 0x04
+    pub LIFCR:      Reg<dma::LIFCR, A>,             // < DMA low interrupt flag clear register,                         Address offset: 0x08
+    pub HIFCR:      Reg<dma::HIFCR, A>,             // < DMA high interrupt flag clear register,                        Address offset: 0x0C
+    pub S:          [Stream<A>; 8],                 // < DMA streams 0 to 7,                                            Address offset: 0x10-0xCC
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+#[rustfmt::skip]
+pub struct Stream<A = VolatileCell<u32>> {
+    pub CR:         Reg<dma::CR, A>,                // < DMA stream x configuration register,                           Address offset: 0x10 + 0x18 * x
+    pub NDTR:       Reg<dma::NDTR, A>,              // < DMA stream x number of data register,                          Address offset: 0x14 + 0x18 * x
+    pub PAR:        Reg<dma::PAR, A>,               // < DMA stream x peripheral address register,                      Address offset: 0x18 + 0x18 * x
+    pub M0AR:       Reg<dma::M0AR, A>,              // < DMA stream x memory 0 address register,                        Address offset: 0x1C + 0x18 * x
+    pub M1AR:       Reg<dma::M1AR, A>,              // < DMA stream x memory 1 address register,                        Address offset: 0x20 + 0x18 * x
+    pub FCR:        Reg<dma::FCR, A>,               // < DMA stream x FIFO control register,                            Address offset: 0x24 + 0x18 * x
+}
+
+impl DMA {
+    pub fn dma1() -> *mut DMA {
+        DMA1_BASE as *mut DMA
+    }
+
+    pub fn dma2() -> *mut DMA {
+        DMA2_BASE as *mut DMA
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+#[rustfmt::skip]
+pub struct USART<A = VolatileCell<u32>> {
+    pub SR:         Reg<usart::SR, A>,              // < USART status register,                                         Address offset: 0x00
+    pub DR:         Reg<usart::DR, A>,              // < USART data register,                                           Address offset: 0x04
+    pub BRR:        Reg<usart::BRR, A>,             // < USART baud rate register,                                      Address offset: 0x08
+    pub CR1:        Reg<usart::CR1, A>,             // < USART control register 1,                                      Address offset: 0x0C
+    pub CR2:        Reg<usart::CR2, A>,             // < USART control register 2,                                      Address offset: 0x10
+    pub CR3:        Reg<usart::CR3, A>,             // < USART control register 3,                                      Address offset: 0x14
+    pub GTPR:       Reg<usart::GTPR, A>,            // < USART guard time and prescaler register,                       Address offset: 0x18
+}
+
+impl USART {
+    pub fn usart2() -> *mut USART {
+        USART2_BASE as *mut USART
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+#[rustfmt::skip]
+pub struct DBGMCU<A = VolatileCell<u32>> {
+    pub IDCODE:     Reg<dbgmcu::IDCODE, A>,         // < MCU device ID code,                                            Address offset: 0x00
+    pub CR:         Reg<dbgmcu::CR, A>,             // < Debug MCU configuration register,                              Address offset: 0x04
+    pub APB1_FZ:    Reg<dbgmcu::APB1_FZ, A>,        // < Debug MCU APB1 freeze register,                                Address offset: 0x08
+    pub APB2_FZ:    Reg<dbgmcu::APB2_FZ, A>,        // < Debug MCU APB2 freeze register,                                Address offset: 0x0C
+}
+
+impl DBGMCU {
+    pub fn get() -> *mut DBGMCU {
+        DBGMCU_BASE as *mut DBGMCU
+    }
+}
@@ -0,0 +1,178 @@
+//! ITM and TPIU (SWO) configuration
+//!
+//! `openocd.gdb` sets up tracing with `monitor tpiu config` and `monitor itm
+//! port`, which must agree with the clocks of the program. `Config` does the
+//! same from Rust, so it follows the `Clocks` in use, and also enables ITM
+//! local timestamps: each stimulus write is followed by a timestamp packet
+//! holding the core clock cycles (divided by the `Prescaler`) since the
+//! previous one, which `itm -t` (in `host/`) turns into the time of each line.
+//!
+//! ``` ignore
+//! let clocks = clocks::Config::hsi().sysclk(84_000_000).freeze(rcc, flash, pwr).unwrap();
+//! swo::Config::new(&clocks)
+//!     .baud(2_000_000)
+//!     .timestamps(Some(Prescaler::Div1))
+//!     .apply(&mut core.DCB, &core.TPIU, &mut core.ITM, &mut core.DWT)
+//!     .unwrap();
+//! ```
+//!
+//! The `tpiu config` line of `openocd.gdb` is then not needed, but
+//! `openocd` must still capture the SWO output, e.g.:
+//!
+//! ``` text
+//! monitor tpiu config internal /tmp/itm.fifo uart off 84000000 2000000
+//! ```
+//!
+//! see ARMv7-M ARM C1.7 (ITM), C1.10 (TPIU) and RM0368 chapter 23 (debug support)
+
+use cortex_m::peripheral::{DCB, DWT, ITM, TPIU};
+
+use crate::clocks::Clocks;
+use crate::stm32f40x::{dbgmcu::cr, DBGMCU};
+
+/// Default SWO bit rate, what the `stlink` on the Nucleo handles.
+pub const BAUD: u32 = 2_000_000;
+
+// `LAR` key unlocking the ITM registers
+const UNLOCK: u32 = 0xC5AC_CE55;
+
+// ITM_TCR
+const ITMENA: u32 = 1 << 0;
+const TSENA: u32 = 1 << 1;
+const SYNCENA: u32 = 1 << 2;
+const TXENA: u32 = 1 << 3;
+const TS_PRESCALE: u32 = 8;
+const TRACE_BUS_ID: u32 = 16;
+
+// DWT_CTRL, synchronization packets every 2^24 cycles
+const CYCCNTENA: u32 = 1 << 0;
+const SYNCTAP_24: u32 = 0b01 << 10;
+
+// TPIU_SPPR, asynchronous NRZ (UART)
+const NRZ: u32 = 2;
+// TPIU_FFCR, formatter off (ITM and DWT only), `TrigIn` as after reset
+const FFCR: u32 = 1 << 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The core clock cannot be divided down to the bit rate (within 3 %).
+    Baud,
+}
+
+/// Divider of the core clock for local timestamps (`TSPrescale`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Prescaler {
+    Div1 = 0,
+    Div4,
+    Div16,
+    Div64,
+}
+
+impl Prescaler {
+    pub fn div(self) -> u32 {
+        1 << (2 * self as u32)
+    }
+}
+
+/// Trace configuration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    hclk: u32,
+    baud: u32,
+    ports: u32,
+    timestamps: Option<Prescaler>,
+}
+
+impl Config {
+    /// SWO at `BAUD`, ports 0, 1 and 2, and timestamps of every cycle.
+    pub fn new(clocks: &Clocks) -> Self {
+        Config {
+            hclk: clocks.hclk(),
+            baud: BAUD,
+            ports: 0b111,
+            timestamps: Some(Prescaler::Div1),
+        }
+    }
+
+    /// SWO bit rate.
+    pub fn baud(mut self, baud: u32) -> Self {
+        self.baud = baud;
+        self
+    }
+
+    /// Enabled stimulus ports (bit mask).
+    pub fn ports(mut self, ports: u32) -> Self {
+        self.ports = ports;
+        self
+    }
+
+    /// Local timestamps, `None` to disable them.
+    pub fn timestamps(mut self, prescaler: Option<Prescaler>) -> Self {
+        self.timestamps = prescaler;
+        self
+    }
+
+    /// The TPIU prescaler, SWO bit rate = HCLK / (`ACPR` + 1).
+    pub fn acpr(&self) -> Result<u32, Error> {
+        let div = (self.hclk + self.baud / 2) / self.baud.max(1);
+        // the UART of the probe tolerates a few percent
+        let actual = self.hclk / div.max(1);
+        if div == 0
+            || div > 0x2000
+            || actual.max(self.baud) - actual.min(self.baud) > self.baud / 33
+        {
+            return Err(Error::Baud);
+        }
+        Ok(div - 1)
+    }
+
+    /// The ITM trace control register value.
+    pub fn tcr(&self) -> u32 {
+        let timestamps = match self.timestamps {
+            Some(prescaler) => TSENA | (prescaler as u32) << TS_PRESCALE,
+            None => 0,
+        };
+        ITMENA | SYNCENA | TXENA | timestamps | 1 << TRACE_BUS_ID
+    }
+
+    /// Local timestamp ticks per second.
+    pub fn tick_hz(&self) -> Option<u32> {
+        self.timestamps.map(|prescaler| self.hclk / prescaler.div())
+    }
+
+    /// Programs the TPIU, ITM and DWT (synchronization and cycle counter).
+    pub fn apply(
+        &self,
+        dcb: &mut DCB,
+        tpiu: &TPIU,
+        itm: &mut ITM,
+        dwt: &mut DWT,
+    ) -> Result<(), Error> {
+        let acpr = self.acpr()?;
+
+        // NOTE(unsafe) the debug MCU registers are only used here
+        let dbgmcu = unsafe { &*DBGMCU::get() };
+        // SWO on PB3 (its function after reset), asynchronous mode
+        cr::TRACE_IOEN.modify(&dbgmcu.CR, 1);
+        cr::TRACE_MODE.modify(&dbgmcu.CR, 0);
+
+        dcb.enable_trace();
+        // NOTE(unsafe) we own all of the trace peripherals
+        unsafe {
+            tpiu.cspsr.write(1); // port size 1 bit
+            tpiu.sppr.write(NRZ);
+            tpiu.acpr.write(acpr);
+            tpiu.ffcr.write(FFCR);
+
+            itm.lar.write(UNLOCK);
+            // disabled while reconfigured
+            itm.tcr.write(0);
+            itm.ter[0].write(self.ports);
+            itm.tpr.write(0);
+            itm.tcr.write(self.tcr());
+
+            dwt.ctrl.modify(|ctrl| ctrl | CYCCNTENA | SYNCTAP_24);
+        }
+        Ok(())
+    }
+}
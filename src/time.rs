@@ -0,0 +1,145 @@
+//! Time units
+//!
+//! Durations and frequencies that convert to core clock cycles using the
+//! frozen `Clocks`, instead of hard coded cycle counts (`16_000_000 // 1s`)
+//! that silently change meaning when the clock configuration changes.
+//!
+//! ``` ignore
+//! use app::time::U32Ext as _;
+//!
+//! syst.set_reload(1.s().systick_reload(&clocks).unwrap()); // period = 1s
+//! cx.schedule.foo(now + 500.ms().cyccnt(&clocks).unwrap()).unwrap();
+//! ```
+
+use crate::clocks::Clocks;
+
+/// Largest SysTick reload value (the counter is 24 bits).
+pub const SYST_RELOAD_MAX: u32 = 0x00FF_FFFF;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The number of cycles does not fit the target counter.
+    Overflow,
+    /// The duration is shorter than one clock cycle.
+    Zero,
+}
+
+/// A frequency in Hz.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hertz(pub u32);
+
+impl Hertz {
+    /// Number of core clock cycles in one period.
+    pub fn cycles(self, clocks: &Clocks) -> Result<u32, Error> {
+        match clocks.sysclk().checked_div(self.0) {
+            Some(0) | None => Err(Error::Zero),
+            Some(cycles) => Ok(cycles),
+        }
+    }
+}
+
+/// A duration, with microsecond resolution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Duration {
+    micros: u64,
+}
+
+impl Duration {
+    pub const fn from_secs(secs: u32) -> Self {
+        Duration {
+            micros: secs as u64 * 1_000_000,
+        }
+    }
+
+    pub const fn from_millis(millis: u32) -> Self {
+        Duration {
+            micros: millis as u64 * 1_000,
+        }
+    }
+
+    pub const fn from_micros(micros: u32) -> Self {
+        Duration {
+            micros: micros as u64,
+        }
+    }
+
+    pub fn as_micros(self) -> u64 {
+        self.micros
+    }
+
+    pub fn as_millis(self) -> u64 {
+        self.micros / 1_000
+    }
+
+    /// Number of core clock cycles (rounded down).
+    pub fn cycles(self, clocks: &Clocks) -> Result<u32, Error> {
+        // whole seconds and the rest apart, `micros * sysclk` overflows a
+        // `u64` beyond some 60 hours at 84 MHz
+        let sysclk = clocks.sysclk() as u64;
+        let secs = self.micros / 1_000_000;
+        let rest = self.micros % 1_000_000 * sysclk / 1_000_000;
+        secs.checked_mul(sysclk)
+            .and_then(|cycles| cycles.checked_add(rest))
+            .filter(|&cycles| cycles <= u32::MAX as u64)
+            .map(|cycles| cycles as u32)
+            .ok_or(Error::Overflow)
+    }
+
+    /// SysTick reload value for a period of `self`, clocked by the core.
+    ///
+    /// The period is `reload + 1` cycles, and must fit the 24 bit counter.
+    pub fn systick_reload(self, clocks: &Clocks) -> Result<u32, Error> {
+        match self.cycles(clocks)? {
+            0 => Err(Error::Zero),
+            cycles if cycles - 1 > SYST_RELOAD_MAX => Err(Error::Overflow),
+            cycles => Ok(cycles - 1),
+        }
+    }
+
+    /// As a `rtfm::cyccnt::Duration` (the DWT cycle counter runs at SYSCLK).
+    #[cfg(feature = "cortex-m-rtfm")]
+    pub fn cyccnt(self, clocks: &Clocks) -> Result<rtfm::cyccnt::Duration, Error> {
+        use rtfm::cyccnt::U32Ext as _;
+
+        Ok(self.cycles(clocks)?.cycles())
+    }
+}
+
+/// Extension trait, e.g., `500.ms()` or `115_200.hz()`.
+///
+/// `khz` and `mhz` panic if the frequency does not fit `u32` Hz (above
+/// some 4.29 GHz), the durations always fit.
+pub trait U32Ext {
+    fn hz(self) -> Hertz;
+    fn khz(self) -> Hertz;
+    fn mhz(self) -> Hertz;
+    fn s(self) -> Duration;
+    fn ms(self) -> Duration;
+    fn us(self) -> Duration;
+}
+
+impl U32Ext for u32 {
+    fn hz(self) -> Hertz {
+        Hertz(self)
+    }
+
+    fn khz(self) -> Hertz {
+        Hertz(self.checked_mul(1_000).expect("frequency overflow"))
+    }
+
+    fn mhz(self) -> Hertz {
+        Hertz(self.checked_mul(1_000_000).expect("frequency overflow"))
+    }
+
+    fn s(self) -> Duration {
+        Duration::from_secs(self)
+    }
+
+    fn ms(self) -> Duration {
+        Duration::from_millis(self)
+    }
+
+    fn us(self) -> Duration {
+        Duration::from_micros(self)
+    }
+}
@@ -0,0 +1,298 @@
+//! Register access tracing
+//!
+//! Records register accesses (address, value and direction) in a ring buffer
+//! and checks them against the clock-enable rules of the MCU. As discussed in
+//! `examples/bare4.rs`, GPIOA must be powered through `RCC_AHB1ENR` before
+//! `GPIOA_MODER` is touched, and accessing an unpowered peripheral is silently
+//! ignored by the hardware.
+//!
+//! On the MCU, use the traced `read_u32`/`write_u32` or the `TracedCell`
+//! register blocks (`rcc()`, `gpioa()`), which record into a global recorder
+//! (see `with_recorder`, only built for the MCU). On the host, the `mock`
+//! register file records into any `Recorder` you pass it, e.g.:
+//!
+//! ``` ignore
+//! let rec: Recorder<64> = Recorder::new();
+//! let (rcc, gpioa) = (RCC::mock(&rec), GPIOA::mock(&rec));
+//!
+//! led::init(&rcc, &gpioa);
+//!
+//! assert_eq!(check(RULES, rec.events()), None);
+//! ```
+
+use core::cell::Cell;
+
+use crate::stm32f40x::address::*;
+
+#[cfg(target_os = "none")]
+pub use self::target::*;
+
+/// Direction of a register access.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dir {
+    Read,
+    Write,
+}
+
+/// A recorded register access.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    pub addr: u32,
+    pub value: u32,
+    pub dir: Dir,
+}
+
+impl Event {
+    pub fn is_write_to(&self, addr: u32) -> bool {
+        self.dir == Dir::Write && self.addr == addr
+    }
+}
+
+/// Something register accesses can be recorded into.
+pub trait Record {
+    fn record(&self, event: Event);
+}
+
+/// Ring buffer holding the last `N` accesses.
+pub struct Recorder<const N: usize> {
+    events: [Cell<Event>; N],
+    // total number of recorded events, the next one goes to `next % N`
+    next: Cell<usize>,
+}
+
+impl<const N: usize> Recorder<N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Cell<Event> = Cell::new(Event {
+        addr: 0,
+        value: 0,
+        dir: Dir::Read,
+    });
+
+    pub const fn new() -> Self {
+        Recorder {
+            events: [Self::EMPTY; N],
+            next: Cell::new(0),
+        }
+    }
+
+    /// Number of events held (at most `N`).
+    pub fn len(&self) -> usize {
+        self.next.get().min(N)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of events overwritten since the last `clear`.
+    pub fn dropped(&self) -> usize {
+        self.next.get() - self.len()
+    }
+
+    /// The events held, oldest first.
+    pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        let next = self.next.get();
+        let first = next - self.len();
+        (first..next).map(move |i| self.events[i % N].get())
+    }
+
+    /// Index (among the events held) of the first event matching `pred`.
+    pub fn position(&self, mut pred: impl FnMut(&Event) -> bool) -> Option<usize> {
+        self.events().position(|e| pred(&e))
+    }
+
+    pub fn clear(&self) {
+        self.next.set(0);
+    }
+}
+
+impl<const N: usize> Default for Recorder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Record for Recorder<N> {
+    fn record(&self, event: Event) {
+        let next = self.next.get();
+        self.events[next % N].set(event);
+        self.next.set(next.wrapping_add(1));
+    }
+}
+
+/// A peripheral that must have its clock enabled before being accessed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rule {
+    pub name: &'static str,
+    pub base: u32,
+    pub size: u32,
+    /// Address of the RCC enable register.
+    pub enable: u32,
+    /// Enable bit in `enable`.
+    pub bit: u8,
+}
+
+impl Rule {
+    pub fn covers(&self, addr: u32) -> bool {
+        addr.wrapping_sub(self.base) < self.size
+    }
+}
+
+#[rustfmt::skip]
+const fn ahb1(name: &'static str, offset: u32, bit: u8) -> Rule {
+    Rule { name, base: AHB1PERIPH_BASE + offset, size: 0x400, enable: RCC_AHB1ENR, bit }
+}
+
+/// Clock-enable rules for the AHB1 peripherals, RM0368 2.3 and 6.3.9.
+#[rustfmt::skip]
+pub const RULES: &[Rule; 9] = &[
+    ahb1("GPIOA", 0x0000, 0),
+    ahb1("GPIOB", 0x0400, 1),
+    ahb1("GPIOC", 0x0800, 2),
+    ahb1("GPIOD", 0x0C00, 3),
+    ahb1("GPIOE", 0x1000, 4),
+    ahb1("GPIOH", 0x1C00, 7),
+    ahb1("CRC",   0x3000, 12),
+    ahb1("DMA1",  0x6000, 21),
+    ahb1("DMA2",  0x6400, 22),
+];
+
+/// An access to a peripheral whose clock was not enabled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Violation {
+    /// Index of the offending event.
+    pub index: usize,
+    pub event: Event,
+    pub rule: Rule,
+}
+
+/// Online rule checker, tracking writes to the enable registers.
+///
+/// Starts out in reset state, i.e., with all clocks disabled.
+pub struct Checker<'a, const N: usize> {
+    rules: &'a [Rule; N],
+    // enable registers as last written, `N` rules name at most `N` of them
+    enables: [(u32, u32); N],
+    index: usize,
+}
+
+impl<'a, const N: usize> Checker<'a, N> {
+    pub fn new(rules: &'a [Rule; N]) -> Self {
+        Checker {
+            rules,
+            enables: [(0, 0); N],
+            index: 0,
+        }
+    }
+
+    fn enable_value(&self, addr: u32) -> u32 {
+        self.enables
+            .iter()
+            .find(|(a, _)| *a == addr)
+            .map_or(0, |(_, v)| *v)
+    }
+
+    /// Feeds the next access, returning the violation if any.
+    pub fn check(&mut self, event: Event) -> Result<(), Violation> {
+        let index = self.index;
+        self.index += 1;
+
+        if event.dir == Dir::Write && self.rules.iter().any(|r| r.enable == event.addr) {
+            // there is a slot, the enable registers are among those of the rules
+            if let Some(slot) = self
+                .enables
+                .iter_mut()
+                .find(|(a, _)| *a == event.addr || *a == 0)
+            {
+                *slot = (event.addr, event.value);
+            }
+        }
+
+        match self.rules.iter().find(|r| r.covers(event.addr)) {
+            Some(rule) if self.enable_value(rule.enable) & (1 << rule.bit) == 0 => Err(Violation {
+                index,
+                event,
+                rule: *rule,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Checks a sequence of accesses, returning the first violation.
+pub fn check<const N: usize>(
+    rules: &[Rule; N],
+    events: impl IntoIterator<Item = Event>,
+) -> Option<Violation> {
+    let mut checker = Checker::new(rules);
+    events.into_iter().find_map(|e| checker.check(e).err())
+}
+
+// the global recorder, the host passes its own to the `mock` register file
+#[cfg(target_os = "none")]
+mod target {
+    use core::ptr;
+
+    use cortex_m::interrupt::{self, Mutex};
+
+    use super::{Dir, Event, Record, Recorder};
+    use crate::field::Access;
+    use crate::stm32f40x::{address::*, VolatileCell, GPIOA, RCC};
+
+    // the recorder used on the MCU
+    const CAPACITY: usize = 64;
+    static RECORDER: Mutex<Recorder<CAPACITY>> = Mutex::new(Recorder::new());
+
+    /// Gives access to the global recorder (in a critical section).
+    pub fn with_recorder<R>(f: impl FnOnce(&Recorder<CAPACITY>) -> R) -> R {
+        interrupt::free(|cs| f(RECORDER.borrow(cs)))
+    }
+
+    fn record(addr: u32, value: u32, dir: Dir) {
+        with_recorder(|rec| rec.record(Event { addr, value, dir }));
+    }
+
+    /// As `read_u32` in `examples/bare4.rs`, recording the access.
+    #[inline(always)]
+    pub fn read_u32(addr: u32) -> u32 {
+        let value = unsafe { ptr::read_volatile(addr as *const u32) };
+        record(addr, value, Dir::Read);
+        value
+    }
+
+    /// As `write_u32` in `examples/bare4.rs`, recording the access.
+    #[inline(always)]
+    pub fn write_u32(addr: u32, value: u32) {
+        unsafe { ptr::write_volatile(addr as *mut u32, value) };
+        record(addr, value, Dir::Write);
+    }
+
+    /// A `VolatileCell<u32>` recording its accesses in the global recorder.
+    #[repr(transparent)]
+    pub struct TracedCell(VolatileCell<u32>);
+
+    impl Access for TracedCell {
+        #[inline(always)]
+        fn read(&self) -> u32 {
+            let value = self.0.read();
+            record(self as *const _ as u32, value, Dir::Read);
+            value
+        }
+
+        #[inline(always)]
+        fn write(&self, value: u32) {
+            self.0.write(value);
+            record(self as *const _ as u32, value, Dir::Write);
+        }
+    }
+
+    /// The RCC register block, with traced accesses.
+    pub fn rcc() -> &'static RCC<TracedCell> {
+        unsafe { &*(RCC_BASE as *const RCC<TracedCell>) }
+    }
+
+    /// The GPIOA register block, with traced accesses.
+    pub fn gpioa() -> &'static GPIOA<TracedCell> {
+        unsafe { &*(GPIOA_BASE as *const GPIOA<TracedCell>) }
+    }
+}
@@ -0,0 +1,214 @@
+//! Execution time measurement of RTFM tasks and resource locks
+//!
+//! A `Probe` per task (and per lock of interest) collects the cycle counts of
+//! its runs, the worst case being what the response time analysis (`srp`, in
+//! `host/`) needs. Start a measurement first thing in the task, it ends when
+//! the guard is dropped:
+//!
+//! ``` ignore
+//! static TOGGLE: Probe = Probe::task("toggle", 1).period(8_000_000);
+//! static TOGGLE_COUNT: Probe = Probe::lock("toggle", "count", 2);
+//!
+//! #[task(priority = 1, resources = [count])]
+//! fn toggle(mut cx: toggle::Context) {
+//!     let _wcet = TOGGLE.start();
+//!     cx.resources.count.lock(|count| {
+//!         let _wcet = TOGGLE_COUNT.start();
+//!         *count += 1;
+//!     });
+//! }
+//! ```
+//!
+//! Tasks preempting a measured task are measured by their own probes and
+//! their cycles are taken out, so each probe holds the execution time of its
+//! own code only (this needs every task that may preempt to be measured). A
+//! lock is measured the same way; its time stays part of the task holding it.
+//!
+//! `report` writes one line per probe, e.g., for `itm` or `srp`:
+//!
+//! ``` text
+//! wcet task toggle: priority=1 period=8000000 n=20 max=1843 avg=1790
+//! wcet lock toggle.count: ceiling=2 n=20 max=112 avg=108
+//! ```
+//!
+//! Without the `wcet` feature `start` does nothing and the guard is empty, so
+//! the probes can stay in the code. The DWT cycle counter must be enabled
+//! (done by RTFM for the `CYCCNT` monotonic timer), the cycles of the
+//! measurement itself (a few tens) are included.
+
+use core::cell::Cell;
+use core::fmt::{self, Write};
+use core::marker::PhantomData;
+
+use cortex_m::interrupt::{self, Mutex};
+#[cfg(feature = "wcet")]
+use cortex_m::peripheral::DWT;
+
+/// Cycle counts of the runs so far.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub count: u32,
+    pub max: u32,
+    pub total: u64,
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Stats {
+            count: 0,
+            max: 0,
+            total: 0,
+        }
+    }
+
+    pub fn record(&mut self, cycles: u32) {
+        self.count = self.count.wrapping_add(1);
+        self.max = self.max.max(cycles);
+        self.total += cycles as u64;
+    }
+
+    /// The average, 0 before the first run.
+    pub fn avg(&self) -> u32 {
+        if self.count == 0 {
+            0
+        } else {
+            (self.total / self.count as u64) as u32
+        }
+    }
+}
+
+/// What a probe measures.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// A task at `priority`, `period` cycles apart (0 if not periodic).
+    Task { priority: u8, period: u32 },
+    /// A lock taken by `task` (of the probe's resource), raising the
+    /// priority to `ceiling`.
+    Lock { task: &'static str, ceiling: u8 },
+}
+
+/// A named measurement point.
+pub struct Probe {
+    pub name: &'static str,
+    pub kind: Kind,
+    stats: Mutex<Cell<Stats>>,
+}
+
+impl Probe {
+    pub const fn task(name: &'static str, priority: u8) -> Self {
+        Probe {
+            name,
+            kind: Kind::Task {
+                priority,
+                period: 0,
+            },
+            stats: Mutex::new(Cell::new(Stats::new())),
+        }
+    }
+
+    /// Period (or minimum inter-arrival time) of a task, in cycles.
+    pub const fn period(mut self, cycles: u32) -> Self {
+        if let Kind::Task { priority, .. } = self.kind {
+            self.kind = Kind::Task {
+                priority,
+                period: cycles,
+            };
+        }
+        self
+    }
+
+    pub const fn lock(task: &'static str, resource: &'static str, ceiling: u8) -> Self {
+        Probe {
+            name: resource,
+            kind: Kind::Lock { task, ceiling },
+            stats: Mutex::new(Cell::new(Stats::new())),
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        interrupt::free(|cs| self.stats.borrow(cs).get())
+    }
+
+    pub fn reset(&self) {
+        interrupt::free(|cs| self.stats.borrow(cs).set(Stats::new()));
+    }
+
+    /// Starts a measurement, ending when the guard is dropped.
+    #[inline(always)]
+    pub fn start(&self) -> Guard<'_> {
+        #[cfg(feature = "wcet")]
+        let (start, preempted) =
+            interrupt::free(|cs| (DWT::get_cycle_count(), PREEMPTED.borrow(cs).get()));
+        Guard {
+            #[cfg(feature = "wcet")]
+            probe: self,
+            #[cfg(feature = "wcet")]
+            start,
+            #[cfg(feature = "wcet")]
+            preempted,
+            _probe: PhantomData,
+        }
+    }
+}
+
+// the cycles of all measured tasks so far (their own, wrapping), what a task
+// was preempted for is the increase while it ran
+#[cfg(feature = "wcet")]
+static PREEMPTED: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// A running measurement.
+#[must_use]
+pub struct Guard<'a> {
+    #[cfg(feature = "wcet")]
+    probe: &'a Probe,
+    #[cfg(feature = "wcet")]
+    start: u32,
+    #[cfg(feature = "wcet")]
+    preempted: u32,
+    _probe: PhantomData<&'a Probe>,
+}
+
+#[cfg(feature = "wcet")]
+impl Drop for Guard<'_> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        interrupt::free(|cs| {
+            // read in the critical section, so nothing can preempt in between
+            let total = DWT::get_cycle_count().wrapping_sub(self.start);
+            let preempted = PREEMPTED.borrow(cs);
+            let own = total.wrapping_sub(preempted.get().wrapping_sub(self.preempted));
+            if let Kind::Task { .. } = self.probe.kind {
+                preempted.set(preempted.get().wrapping_add(own));
+            }
+            let stats = self.probe.stats.borrow(cs);
+            let mut s = stats.get();
+            s.record(own);
+            stats.set(s);
+        });
+    }
+}
+
+/// Writes a line per probe to `out`.
+pub fn report(probes: &[&Probe], out: &mut dyn Write) -> fmt::Result {
+    if cfg!(not(feature = "wcet")) {
+        return writeln!(out, "wcet disabled, build with `--features wcet`");
+    }
+    for probe in probes {
+        let s = probe.stats();
+        match probe.kind {
+            Kind::Task { priority, period } => {
+                write!(out, "wcet task {}: priority={}", probe.name, priority)?;
+                if period != 0 {
+                    write!(out, " period={}", period)?;
+                }
+            }
+            Kind::Lock { task, ceiling } => write!(
+                out,
+                "wcet lock {}.{}: ceiling={}",
+                task, probe.name, ceiling
+            )?,
+        }
+        writeln!(out, " n={} max={} avg={}", s.count, s.max, s.avg())?;
+    }
+    Ok(())
+}